@@ -0,0 +1,297 @@
+use std::{
+    fmt,
+    fs,
+    io::*,
+};
+
+use crate::unwind::{Arch, MemoryProvider};
+use crate::utils::Reader;
+
+/* Upper bound on strings read from the process' stack */
+const MAX_STRING_LEN: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, FromPrimitive)]
+pub enum AuxvType {
+    Null = 0,               /* End of vector */
+    Ignore = 1,             /* Entry should be ignored */
+    ExecFd = 2,             /* File descriptor of program */
+    Phdr = 3,               /* Program headers for program */
+    Phent = 4,              /* Size of program header entry */
+    Phnum = 5,              /* Number of program headers */
+    PageSz = 6,             /* System page size */
+    Base = 7,               /* Base address of interpreter */
+    Flags = 8,              /* Flags */
+    Entry = 9,              /* Entry point of program */
+    NotElf = 10,            /* Program is not ELF */
+    Uid = 11,               /* Real uid */
+    Euid = 12,              /* Effective uid */
+    Gid = 13,               /* Real gid */
+    Egid = 14,              /* Effective gid */
+    Platform = 15,          /* String identifying platform */
+    Hwcap = 16,             /* Machine dependent hints about processor capabilities */
+    ClkTck = 17,            /* Frequency of times() */
+    Secure = 23,            /* Boolean, was exec setuid-like? */
+    BasePlatform = 24,      /* String identifying real platform */
+    Random = 25,            /* Address of 16 random bytes */
+    Hwcap2 = 26,            /* More machine dependent hints */
+    RseqFeatureSize = 27,   /* rseq supported feature size */
+    RseqAlign = 28,         /* rseq allocation alignment */
+    Hwcap3 = 29,
+    Hwcap4 = 30,
+    ExecFn = 31,            /* Filename of executable */
+    SysinfoEhdr = 33,       /* Address of the vDSO ELF header */
+    MinSigStkSz = 51,       /* Minimal stack size for signal delivery */
+}
+
+impl AuxvType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AuxvType::Null => "AT_NULL",
+            AuxvType::Ignore => "AT_IGNORE",
+            AuxvType::ExecFd => "AT_EXECFD",
+            AuxvType::Phdr => "AT_PHDR",
+            AuxvType::Phent => "AT_PHENT",
+            AuxvType::Phnum => "AT_PHNUM",
+            AuxvType::PageSz => "AT_PAGESZ",
+            AuxvType::Base => "AT_BASE",
+            AuxvType::Flags => "AT_FLAGS",
+            AuxvType::Entry => "AT_ENTRY",
+            AuxvType::NotElf => "AT_NOTELF",
+            AuxvType::Uid => "AT_UID",
+            AuxvType::Euid => "AT_EUID",
+            AuxvType::Gid => "AT_GID",
+            AuxvType::Egid => "AT_EGID",
+            AuxvType::Platform => "AT_PLATFORM",
+            AuxvType::Hwcap => "AT_HWCAP",
+            AuxvType::ClkTck => "AT_CLKTCK",
+            AuxvType::Secure => "AT_SECURE",
+            AuxvType::BasePlatform => "AT_BASE_PLATFORM",
+            AuxvType::Random => "AT_RANDOM",
+            AuxvType::Hwcap2 => "AT_HWCAP2",
+            AuxvType::RseqFeatureSize => "AT_RSEQ_FEATURE_SIZE",
+            AuxvType::RseqAlign => "AT_RSEQ_ALIGN",
+            AuxvType::Hwcap3 => "AT_HWCAP3",
+            AuxvType::Hwcap4 => "AT_HWCAP4",
+            AuxvType::ExecFn => "AT_EXECFN",
+            AuxvType::SysinfoEhdr => "AT_SYSINFO_EHDR",
+            AuxvType::MinSigStkSz => "AT_MINSIGSTKSZ",
+        }
+    }
+}
+
+/* CPUID leaf 1 EDX, as named in /proc/cpuinfo */
+const X86_64_HWCAP: [&str; 32] = [
+    "fpu", "vme", "de", "pse", "tsc", "msr", "pae", "mce",
+    "cx8", "apic", "", "sep", "mtrr", "pge", "mca", "cmov",
+    "pat", "pse36", "pn", "clflush", "", "dts", "acpi", "mmx",
+    "fxsr", "sse", "sse2", "ss", "ht", "tm", "ia64", "pbe",
+];
+
+const X86_64_HWCAP2: [&str; 2] = [
+    "ring3mwait", "fsgsbase",
+];
+
+/* arch/arm64/include/uapi/asm/hwcap.h */
+const AARCH64_HWCAP: [&str; 33] = [
+    "fp", "asimd", "evtstrm", "aes", "pmull", "sha1", "sha2", "crc32",
+    "atomics", "fphp", "asimdhp", "cpuid", "asimdrdm", "jscvt", "fcma", "lrcpc",
+    "dcpop", "sha3", "sm3", "sm4", "asimddp", "sha512", "sve", "asimdfhm",
+    "dit", "uscat", "ilrcpc", "flagm", "ssbs", "sb", "paca", "pacg",
+    "gcs",
+];
+
+const AARCH64_HWCAP2: [&str; 64] = [
+    "dcpodp", "sve2", "sveaes", "svepmull", "svebitperm", "svesha3", "svesm4", "flagm2",
+    "frint", "svei8mm", "svef32mm", "svef64mm", "svebf16", "i8mm", "bf16", "dgh",
+    "rng", "bti", "mte", "ecv", "afp", "rpres", "mte3", "sme",
+    "smei16i64", "smef64f64", "smei8i32", "smef16f32", "smeb16f32", "smef32f32", "smefa64", "wfxt",
+    "ebf16", "sveebf16", "cssc", "rprfm", "sve2p1", "sme2", "sme2p1", "smei16i32",
+    "smebi32i32", "smeb16b16", "smef16f16", "mops", "hbc", "sveb16b16", "lrcpc3", "lse128",
+    "fpmr", "lut", "faminmax", "f8cvt", "f8fma", "f8dp4", "f8dp2", "f8e4m3",
+    "f8e5m2", "smelutv2", "smef8f16", "smef8f32", "smesf8fma", "smesf8dp4", "smesf8dp2", "poe",
+];
+
+/// Returns the names of the bits set in `bits`, using `names` indexed by bit.
+/// Bits without a name are reported as "bitN".
+fn decode_bits(bits: u64, names: &[&'static str]) -> Vec<String> {
+    (0..64)
+        .filter(|bit| bits & (1 << bit) != 0)
+        .map(|bit| match names.get(bit) {
+            Some(name) if !name.is_empty() => name.to_string(),
+            _ => format!("bit{}", bit),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuxvEntry {
+    pub typ: u64,
+    pub val: u64,
+}
+
+impl AuxvEntry {
+    pub fn kind(&self) -> Option<AuxvType> {
+        num_traits::FromPrimitive::from_u64(self.typ)
+    }
+}
+
+impl fmt::Display for AuxvEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind() {
+            Some(kind) => write!(f, "{:<20} {:#x}", kind.name(), self.val),
+            None => write!(f, "{:<20} {:#x}", format!("AT_{}", self.typ), self.val),
+        }
+    }
+}
+
+/// The auxiliary vector the kernel passes to a new program.
+#[derive(Debug, Clone, Default)]
+pub struct Auxv {
+    /// Used to decode the hwcap bits.
+    pub arch: Option<Arch>,
+    pub entries: Vec<AuxvEntry>,
+}
+
+impl Auxv {
+    /// Parses a raw auxiliary vector, as found in /proc/<pid>/auxv or an
+    /// NT_AUXV note.
+    pub fn parse(data: &[u8], arch: Option<Arch>) -> Result<Self> {
+        let mut r = Reader::new(data);
+        let mut entries = Vec::new();
+
+        while r.remaining() >= 16 {
+            let typ = r.u64()?;
+            let val = r.u64()?;
+            if typ == AuxvType::Null as u64 {
+                break;
+            }
+            entries.push(AuxvEntry { typ, val });
+        }
+
+        Ok(Auxv { arch, entries })
+    }
+
+    pub fn this() -> Result<Self> {
+        Auxv::parse(&fs::read("/proc/self/auxv")?, Arch::host())
+    }
+
+    pub fn from_pid(pid: u32) -> Result<Self> {
+        Auxv::parse(&fs::read(format!("/proc/{}/auxv", pid))?, Arch::host())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AuxvEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the value of the first entry of type `typ`.
+    pub fn get(&self, typ: AuxvType) -> Option<u64> {
+        self.entries.iter()
+            .find(|e| e.typ == typ as u64)
+            .map(|e| e.val)
+    }
+
+    /// Address of the executable's program headers.
+    pub fn phdr(&self) -> Option<u64> {
+        self.get(AuxvType::Phdr)
+    }
+
+    pub fn phnum(&self) -> Option<u64> {
+        self.get(AuxvType::Phnum)
+    }
+
+    /// Load address of the dynamic loader, None for static executables.
+    pub fn base(&self) -> Option<u64> {
+        self.get(AuxvType::Base).filter(|&base| base != 0)
+    }
+
+    pub fn entry(&self) -> Option<u64> {
+        self.get(AuxvType::Entry)
+    }
+
+    /// Address of the vDSO's ELF header.
+    pub fn sysinfo_ehdr(&self) -> Option<u64> {
+        self.get(AuxvType::SysinfoEhdr)
+    }
+
+    pub fn page_size(&self) -> Option<u64> {
+        self.get(AuxvType::PageSz)
+    }
+
+    pub fn hwcap(&self) -> Option<u64> {
+        self.get(AuxvType::Hwcap)
+    }
+
+    pub fn hwcap2(&self) -> Option<u64> {
+        self.get(AuxvType::Hwcap2)
+    }
+
+    /// Names of the AT_HWCAP bits set, following /proc/cpuinfo.
+    pub fn hwcap_features(&self) -> Vec<String> {
+        let names: &[&str] = match self.arch {
+            Some(Arch::X86_64) => &X86_64_HWCAP,
+            Some(Arch::Aarch64) => &AARCH64_HWCAP,
+            None => &[],
+        };
+        decode_bits(self.hwcap().unwrap_or(0), names)
+    }
+
+    /// Names of the AT_HWCAP2 bits set.
+    pub fn hwcap2_features(&self) -> Vec<String> {
+        let names: &[&str] = match self.arch {
+            Some(Arch::X86_64) => &X86_64_HWCAP2,
+            Some(Arch::Aarch64) => &AARCH64_HWCAP2,
+            None => &[],
+        };
+        decode_bits(self.hwcap2().unwrap_or(0), names)
+    }
+
+    /// Reads the 16 bytes of AT_RANDOM, the glibc stack protector and
+    /// pointer guard are derived from them.
+    pub fn random(&self, mem: &dyn MemoryProvider) -> Result<[u8; 16]> {
+        let addr = self.get(AuxvType::Random)
+            .ok_or_else(|| Error::other("no AT_RANDOM entry"))?;
+        let mut buf = [0; 16];
+        mem.read_memory(addr, &mut buf)?;
+        Ok(buf)
+    }
+
+    /// Reads the path the executable was run as.
+    pub fn execfn(&self, mem: &dyn MemoryProvider) -> Result<String> {
+        self.read_string(AuxvType::ExecFn, mem)
+    }
+
+    /// Reads the platform string, e.g. "x86_64" or "aarch64".
+    pub fn platform(&self, mem: &dyn MemoryProvider) -> Result<String> {
+        self.read_string(AuxvType::Platform, mem)
+    }
+
+    fn read_string(&self, typ: AuxvType, mem: &dyn MemoryProvider) -> Result<String> {
+        let mut addr = self.get(typ)
+            .ok_or_else(|| Error::other(format!("no {} entry", typ.name())))?;
+        let mut s = Vec::new();
+
+        loop {
+            let mut byte = [0; 1];
+            mem.read_memory(addr, &mut byte)?;
+            if byte[0] == 0 {
+                break;
+            }
+            if s.len() >= MAX_STRING_LEN {
+                return Err(Error::other(format!("{} string is not terminated", typ.name())));
+            }
+            s.push(byte[0]);
+            addr += 1;
+        }
+
+        Ok(String::from_utf8_lossy(&s).into_owned())
+    }
+}
+
+impl fmt::Display for Auxv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,26 @@
+use std::{env, process};
+
+use frieren::ptrace;
+
+fn main() {
+    let pid = match env::args().nth(1).and_then(|arg| arg.parse().ok()) {
+        Some(pid) => pid,
+        None => {
+            eprintln!("usage: pstack <pid>");
+            process::exit(2);
+        },
+    };
+
+    match ptrace::pstack(pid) {
+        Ok(stacks) => {
+            println!("PID {}", pid);
+            for stack in stacks {
+                print!("{}", stack);
+            }
+        },
+        Err(e) => {
+            eprintln!("pstack: {}: {}", pid, e);
+            process::exit(1);
+        },
+    }
+}
@@ -0,0 +1,606 @@
+use std::{
+    fmt,
+    io::*,
+    time::Duration,
+};
+
+use crate::auxv::Auxv;
+use crate::elf::Elf;
+use crate::header::ElfType;
+use crate::note::Note;
+use crate::segment::{Segment, SegmentType};
+use crate::unwind::{Arch, MemoryProvider, Registers, ThreadProvider};
+use crate::utils::Reader;
+
+pub const NT_PRSTATUS: u32 = 1;
+pub const NT_FPREGSET: u32 = 2;
+pub const NT_PRPSINFO: u32 = 3;
+pub const NT_AUXV: u32 = 6;
+pub const NT_SIGINFO: u32 = 0x53494749;
+pub const NT_FILE: u32 = 0x46494c45;
+
+/* Offset of pr_reg in struct elf_prstatus, identical on x86_64 and aarch64 */
+const PR_REG_OFFSET: usize = 112;
+
+const PRPSINFO_FNAME_LEN: usize = 16;
+const PRPSINFO_PSARGS_LEN: usize = 80;
+
+/* Signals for which si_addr holds the faulting address */
+const SIGILL: i32 = 4;
+const SIGTRAP: i32 = 5;
+const SIGBUS: i32 = 7;
+const SIGFPE: i32 = 8;
+const SIGSEGV: i32 = 11;
+
+/// struct user_regs_struct on x86_64.
+#[derive(Debug, Default, Clone)]
+pub struct X86_64Regs {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rax: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub orig_rax: u64,
+    pub rip: u64,
+    pub cs: u64,
+    pub eflags: u64,
+    pub rsp: u64,
+    pub ss: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+    pub ds: u64,
+    pub es: u64,
+    pub fs: u64,
+    pub gs: u64,
+}
+
+impl X86_64Regs {
+    pub fn parse(r: &mut Reader) -> Result<Self> {
+        Ok(X86_64Regs {
+            r15: r.u64()?,
+            r14: r.u64()?,
+            r13: r.u64()?,
+            r12: r.u64()?,
+            rbp: r.u64()?,
+            rbx: r.u64()?,
+            r11: r.u64()?,
+            r10: r.u64()?,
+            r9: r.u64()?,
+            r8: r.u64()?,
+            rax: r.u64()?,
+            rcx: r.u64()?,
+            rdx: r.u64()?,
+            rsi: r.u64()?,
+            rdi: r.u64()?,
+            orig_rax: r.u64()?,
+            rip: r.u64()?,
+            cs: r.u64()?,
+            eflags: r.u64()?,
+            rsp: r.u64()?,
+            ss: r.u64()?,
+            fs_base: r.u64()?,
+            gs_base: r.u64()?,
+            ds: r.u64()?,
+            es: r.u64()?,
+            fs: r.u64()?,
+            gs: r.u64()?,
+        })
+    }
+}
+
+/// struct user_pt_regs on aarch64.
+#[derive(Debug, Default, Clone)]
+pub struct Aarch64Regs {
+    pub regs: [u64; 31],
+    pub sp: u64,
+    pub pc: u64,
+    pub pstate: u64,
+}
+
+impl Aarch64Regs {
+    pub fn parse(r: &mut Reader) -> Result<Self> {
+        let mut regs = [0; 31];
+        for reg in regs.iter_mut() {
+            *reg = r.u64()?;
+        }
+
+        Ok(Aarch64Regs {
+            regs,
+            sp: r.u64()?,
+            pc: r.u64()?,
+            pstate: r.u64()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum GeneralRegisters {
+    X86_64(X86_64Regs),
+    Aarch64(Aarch64Regs),
+}
+
+impl GeneralRegisters {
+    /// Converts the registers into DWARF register numbering for unwinding.
+    pub fn to_registers(&self) -> Registers {
+        match self {
+            GeneralRegisters::X86_64(regs) => {
+                let mut out = Registers::new(Arch::X86_64);
+                let values = [
+                    regs.rax, regs.rdx, regs.rcx, regs.rbx,
+                    regs.rsi, regs.rdi, regs.rbp, regs.rsp,
+                    regs.r8, regs.r9, regs.r10, regs.r11,
+                    regs.r12, regs.r13, regs.r14, regs.r15,
+                    regs.rip,
+                ];
+                for (i, val) in values.iter().enumerate() {
+                    out.set(i as u16, *val);
+                }
+                out
+            },
+            GeneralRegisters::Aarch64(regs) => {
+                let mut out = Registers::new(Arch::Aarch64);
+                for (i, val) in regs.regs.iter().enumerate() {
+                    out.set(i as u16, *val);
+                }
+                out.set_sp(regs.sp);
+                out.set_pc(regs.pc);
+                out
+            },
+        }
+    }
+
+    pub fn pc(&self) -> u64 {
+        match self {
+            GeneralRegisters::X86_64(regs) => regs.rip,
+            GeneralRegisters::Aarch64(regs) => regs.pc,
+        }
+    }
+
+    pub fn sp(&self) -> u64 {
+        match self {
+            GeneralRegisters::X86_64(regs) => regs.rsp,
+            GeneralRegisters::Aarch64(regs) => regs.sp,
+        }
+    }
+}
+
+/// NT_PRSTATUS: struct elf_prstatus.
+#[derive(Debug, Clone)]
+pub struct PrStatus {
+    pub signo: i32,
+    pub code: i32,
+    pub errno: i32,
+    pub cursig: i16,
+    pub sigpend: u64,
+    pub sighold: u64,
+    pub pid: i32,
+    pub ppid: i32,
+    pub pgrp: i32,
+    pub sid: i32,
+    pub utime: Duration,
+    pub stime: Duration,
+    pub cutime: Duration,
+    pub cstime: Duration,
+    pub regs: GeneralRegisters,
+    pub fpvalid: bool,
+}
+
+impl PrStatus {
+    pub fn parse(desc: &[u8], arch: Arch) -> Result<Self> {
+        let mut r = Reader::new(desc);
+        let signo = r.i32()?;
+        let code = r.i32()?;
+        let errno = r.i32()?;
+        let cursig = r.i16()?;
+        r.skip(2)?;
+        let sigpend = r.u64()?;
+        let sighold = r.u64()?;
+        let pid = r.i32()?;
+        let ppid = r.i32()?;
+        let pgrp = r.i32()?;
+        let sid = r.i32()?;
+        let utime = read_timeval(&mut r)?;
+        let stime = read_timeval(&mut r)?;
+        let cutime = read_timeval(&mut r)?;
+        let cstime = read_timeval(&mut r)?;
+
+        r.seek(PR_REG_OFFSET);
+        let regs = match arch {
+            Arch::X86_64 => GeneralRegisters::X86_64(X86_64Regs::parse(&mut r)?),
+            Arch::Aarch64 => GeneralRegisters::Aarch64(Aarch64Regs::parse(&mut r)?),
+        };
+        let fpvalid = r.i32()? != 0;
+
+        Ok(PrStatus {
+            signo, code, errno, cursig, sigpend, sighold,
+            pid, ppid, pgrp, sid,
+            utime, stime, cutime, cstime,
+            regs, fpvalid,
+        })
+    }
+}
+
+fn read_timeval(r: &mut Reader) -> Result<Duration> {
+    let sec = r.u64()?;
+    let usec = r.u64()?;
+    Ok(Duration::from_secs(sec) + Duration::from_micros(usec))
+}
+
+/// NT_PRPSINFO: struct elf_prpsinfo.
+#[derive(Debug, Clone)]
+pub struct PrPsInfo {
+    pub state: u8,
+    pub sname: char,
+    pub zomb: u8,
+    pub nice: i8,
+    pub flag: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: i32,
+    pub ppid: i32,
+    pub pgrp: i32,
+    pub sid: i32,
+    pub fname: String,
+    pub psargs: String,
+}
+
+impl PrPsInfo {
+    pub fn parse(desc: &[u8]) -> Result<Self> {
+        let mut r = Reader::new(desc);
+        let state = r.u8()?;
+        let sname = r.u8()? as char;
+        let zomb = r.u8()?;
+        let nice = r.i8()?;
+        r.skip(4)?;
+
+        Ok(PrPsInfo {
+            state, sname, zomb, nice,
+            flag: r.u64()?,
+            uid: r.u32()?,
+            gid: r.u32()?,
+            pid: r.i32()?,
+            ppid: r.i32()?,
+            pgrp: r.i32()?,
+            sid: r.i32()?,
+            fname: fixed_string(r.bytes(PRPSINFO_FNAME_LEN)?),
+            psargs: fixed_string(r.bytes(PRPSINFO_PSARGS_LEN)?),
+        })
+    }
+}
+
+fn fixed_string(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// NT_SIGINFO: the siginfo_t of the signal that caused the dump.
+#[derive(Debug, Clone)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    /// Faulting address, for SIGSEGV, SIGBUS, SIGILL, SIGFPE and SIGTRAP.
+    pub addr: Option<u64>,
+    /// Sender, for signals sent by kill(2) and friends.
+    pub pid: Option<i32>,
+    pub uid: Option<u32>,
+}
+
+impl SigInfo {
+    pub fn parse(desc: &[u8]) -> Result<Self> {
+        let mut r = Reader::new(desc);
+        let signo = r.i32()?;
+        let errno = r.i32()?;
+        let code = r.i32()?;
+        r.skip(4)?;
+
+        let mut info = SigInfo { signo, errno, code, addr: None, pid: None, uid: None };
+
+        /* si_code <= 0 means the signal came from userspace */
+        if code <= 0 {
+            info.pid = Some(r.i32()?);
+            info.uid = Some(r.u32()?);
+        } else if matches!(signo, SIGILL | SIGTRAP | SIGBUS | SIGFPE | SIGSEGV) {
+            info.addr = Some(r.u64()?);
+        }
+
+        Ok(info)
+    }
+}
+
+/// An entry of the NT_FILE table.
+#[derive(Debug, Clone)]
+pub struct MappedFile {
+    pub start: u64,
+    pub end: u64,
+    /// Offset into the file in bytes.
+    pub offset: u64,
+    pub path: String,
+}
+
+impl MappedFile {
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end
+    }
+}
+
+impl fmt::Display for MappedFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#014x}-{:#014x} {:#010x} {}", self.start, self.end, self.offset, self.path)
+    }
+}
+
+pub fn parse_file_note(desc: &[u8]) -> Result<Vec<MappedFile>> {
+    let mut r = Reader::new(desc);
+    let count = r.u64()? as usize;
+    let page_size = r.u64()?;
+
+    if count > desc.len() / 24 {
+        return Err(Error::other(format!("NT_FILE count {} too large", count)));
+    }
+
+    let mut ranges = Vec::with_capacity(count);
+    for _ in 0..count {
+        ranges.push((r.u64()?, r.u64()?, r.u64()?));
+    }
+
+    let mut files = Vec::with_capacity(count);
+    for (start, end, pgoff) in ranges {
+        files.push(MappedFile {
+            start,
+            end,
+            offset: pgoff.wrapping_mul(page_size),
+            path: r.c_string()?,
+        });
+    }
+
+    Ok(files)
+}
+
+/// NT_FPREGSET: the floating point and vector registers.
+#[derive(Debug, Clone)]
+pub enum FpRegisters {
+    /// struct user_fpregs_struct (the FXSAVE area).
+    X86_64 {
+        cwd: u16,
+        swd: u16,
+        ftw: u16,
+        fop: u16,
+        rip: u64,
+        rdp: u64,
+        mxcsr: u32,
+        mxcsr_mask: u32,
+        st: [u128; 8],
+        xmm: [u128; 16],
+    },
+    /// struct user_fpsimd_state.
+    Aarch64 {
+        vregs: [u128; 32],
+        fpsr: u32,
+        fpcr: u32,
+    },
+}
+
+impl FpRegisters {
+    pub fn parse(desc: &[u8], arch: Arch) -> Result<Self> {
+        let mut r = Reader::new(desc);
+
+        match arch {
+            Arch::X86_64 => {
+                let cwd = r.u16()?;
+                let swd = r.u16()?;
+                let ftw = r.u16()?;
+                let fop = r.u16()?;
+                let rip = r.u64()?;
+                let rdp = r.u64()?;
+                let mxcsr = r.u32()?;
+                let mxcsr_mask = r.u32()?;
+
+                let mut st = [0; 8];
+                for reg in st.iter_mut() {
+                    *reg = read_u128(&mut r)?;
+                }
+                let mut xmm = [0; 16];
+                for reg in xmm.iter_mut() {
+                    *reg = read_u128(&mut r)?;
+                }
+
+                Ok(FpRegisters::X86_64 { cwd, swd, ftw, fop, rip, rdp, mxcsr, mxcsr_mask, st, xmm })
+            },
+            Arch::Aarch64 => {
+                let mut vregs = [0; 32];
+                for reg in vregs.iter_mut() {
+                    *reg = read_u128(&mut r)?;
+                }
+
+                Ok(FpRegisters::Aarch64 { vregs, fpsr: r.u32()?, fpcr: r.u32()? })
+            },
+        }
+    }
+}
+
+fn read_u128(r: &mut Reader) -> Result<u128> {
+    let lo = r.u64()? as u128;
+    let hi = r.u64()? as u128;
+    Ok(hi << 64 | lo)
+}
+
+/// A thread of the dumped process. The kernel writes NT_PRSTATUS first for
+/// each thread, followed by the rest of its per-thread notes.
+#[derive(Debug, Clone)]
+pub struct Thread {
+    pub status: PrStatus,
+    pub fpregs: Option<FpRegisters>,
+    pub siginfo: Option<SigInfo>,
+}
+
+impl Thread {
+    pub fn tid(&self) -> u32 {
+        self.status.pid as u32
+    }
+
+    pub fn registers(&self) -> Registers {
+        self.status.regs.to_registers()
+    }
+}
+
+#[derive(Debug)]
+pub struct Core<'a> {
+    elf: &'a Elf,
+    pub arch: Arch,
+    /// The first thread is the one that received the fatal signal.
+    pub threads: Vec<Thread>,
+    pub process: Option<PrPsInfo>,
+    pub auxv: Auxv,
+    pub files: Vec<MappedFile>,
+}
+
+impl<'a> Core<'a> {
+    pub fn load(elf: &'a Elf) -> Result<Self> {
+        if elf.header().e_type != ElfType::Core as u16 {
+            return Err(Error::other("not a core file"));
+        }
+
+        let arch = Arch::from_machine(elf.header().e_machine)
+            .ok_or_else(|| Error::other(
+                    format!("unsupported machine {}", elf.header().e_machine)))?;
+
+        let mut core = Core {
+            elf,
+            arch,
+            threads: Vec::new(),
+            process: None,
+            auxv: Auxv { arch: Some(arch), entries: Vec::new() },
+            files: Vec::new(),
+        };
+
+        for note in elf.get_notes()? {
+            core.add_note(&note)?;
+        }
+
+        Ok(core)
+    }
+
+    fn add_note(&mut self, note: &Note) -> Result<()> {
+        /* The "LINUX" notes (xstate etc.) reuse small type numbers */
+        if note.name != "CORE" {
+            return Ok(());
+        }
+
+        match note.typ {
+            NT_PRSTATUS => self.threads.push(Thread {
+                status: PrStatus::parse(note.desc, self.arch)?,
+                fpregs: None,
+                siginfo: None,
+            }),
+            NT_FPREGSET => if let Some(thread) = self.threads.last_mut() {
+                thread.fpregs = Some(FpRegisters::parse(note.desc, self.arch)?);
+            },
+            NT_SIGINFO => if let Some(thread) = self.threads.last_mut() {
+                thread.siginfo = Some(SigInfo::parse(note.desc)?);
+            },
+            NT_PRPSINFO => self.process = Some(PrPsInfo::parse(note.desc)?),
+            NT_AUXV => self.auxv = Auxv::parse(note.desc, Some(self.arch))?,
+            NT_FILE => self.files = parse_file_note(note.desc)?,
+            _ => {},
+        }
+
+        Ok(())
+    }
+
+    pub fn elf(&self) -> &'a Elf {
+        self.elf
+    }
+
+    /// Returns the thread that received the signal that caused the dump.
+    pub fn signal_thread(&self) -> Option<&Thread> {
+        self.threads.first()
+    }
+
+    /// Returns the signal that caused the dump.
+    pub fn signal(&self) -> Option<i32> {
+        let thread = self.signal_thread()?;
+        match &thread.siginfo {
+            Some(info) => Some(info.signo),
+            None => Some(thread.status.cursig as i32),
+        }
+    }
+
+    pub fn find_file(&self, addr: u64) -> Option<&MappedFile> {
+        self.files.iter().find(|f| f.contains(addr))
+    }
+
+    fn find_load(&self, vaddr: u64) -> Option<&'a Segment> {
+        self.elf.get_segments_by_type(SegmentType::Load)
+            .find(|seg| seg.contains_vaddr(vaddr as usize))
+    }
+
+    /// Returns the bytes dumped for the mapping containing `vaddr`, up to
+    /// `len` bytes or the end of the mapping.
+    pub fn memory_at(&self, vaddr: u64, len: usize) -> Result<&'a [u8]> {
+        let seg = self.find_load(vaddr)
+            .ok_or_else(|| Error::other(format!("{:#x} is not mapped in the core", vaddr)))?;
+
+        let rel = vaddr as usize - seg.vaddr;
+        if rel >= seg.filesz {
+            return Err(Error::other(format!("{:#x} was not dumped to the core", vaddr)));
+        }
+
+        let data = self.elf.as_bytes();
+        let start = seg.offset + rel;
+        let end = (start + len.min(seg.filesz - rel)).min(data.len());
+        if start >= end {
+            return Err(Error::other(format!("{:#x} lies past the end of the core file", vaddr)));
+        }
+
+        Ok(&data[start..end])
+    }
+
+    /// Reads process memory at `vaddr`, the read may span several mappings.
+    pub fn read_memory(&self, vaddr: u64, buf: &mut [u8]) -> Result<()> {
+        let mut done = 0;
+
+        while done < buf.len() {
+            let bytes = self.memory_at(vaddr + done as u64, buf.len() - done)?;
+            buf[done..done + bytes.len()].copy_from_slice(bytes);
+            done += bytes.len();
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryProvider for Core<'_> {
+    fn read_memory(&self, addr: u64, buf: &mut [u8]) -> Result<()> {
+        Core::read_memory(self, addr, buf)
+    }
+}
+
+impl ThreadProvider for Core<'_> {
+    fn threads(&self) -> Result<Vec<u32>> {
+        Ok(self.threads.iter().map(|t| t.tid()).collect())
+    }
+
+    fn registers(&self, tid: u32) -> Result<Registers> {
+        self.threads.iter()
+            .find(|t| t.tid() == tid)
+            .map(|t| t.registers())
+            .ok_or_else(|| Error::other(format!("no thread {} in core", tid)))
+    }
+}
+
+impl Elf {
+    pub fn core(&self) -> Result<Core<'_>> {
+        Core::load(self)
+    }
+}
@@ -0,0 +1,1399 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::*,
+    ops::Range,
+};
+
+use crate::elf::Elf;
+use crate::section::SectionFlag;
+use crate::utils::*;
+
+pub const DW_TAG_LEXICAL_BLOCK: u64 =       0x0b;
+pub const DW_TAG_COMPILE_UNIT: u64 =        0x11;
+pub const DW_TAG_INLINED_SUBROUTINE: u64 =  0x1d;
+pub const DW_TAG_SUBPROGRAM: u64 =          0x2e;
+pub const DW_TAG_NAMESPACE: u64 =           0x39;
+pub const DW_TAG_PARTIAL_UNIT: u64 =        0x3c;
+pub const DW_TAG_SKELETON_UNIT: u64 =       0x4a;
+
+pub const DW_AT_NAME: u64 =                 0x03;
+pub const DW_AT_STMT_LIST: u64 =            0x10;
+pub const DW_AT_LOW_PC: u64 =               0x11;
+pub const DW_AT_HIGH_PC: u64 =              0x12;
+pub const DW_AT_LANGUAGE: u64 =             0x13;
+pub const DW_AT_COMP_DIR: u64 =             0x1b;
+pub const DW_AT_PRODUCER: u64 =             0x25;
+pub const DW_AT_ABSTRACT_ORIGIN: u64 =      0x31;
+pub const DW_AT_DECL_FILE: u64 =            0x3a;
+pub const DW_AT_DECL_LINE: u64 =            0x3b;
+pub const DW_AT_SPECIFICATION: u64 =        0x47;
+pub const DW_AT_ENTRY_PC: u64 =             0x52;
+pub const DW_AT_RANGES: u64 =               0x55;
+pub const DW_AT_CALL_COLUMN: u64 =          0x57;
+pub const DW_AT_CALL_FILE: u64 =            0x58;
+pub const DW_AT_CALL_LINE: u64 =            0x59;
+pub const DW_AT_LINKAGE_NAME: u64 =         0x6e;
+pub const DW_AT_STR_OFFSETS_BASE: u64 =     0x72;
+pub const DW_AT_ADDR_BASE: u64 =            0x73;
+pub const DW_AT_RNGLISTS_BASE: u64 =        0x74;
+pub const DW_AT_MIPS_LINKAGE_NAME: u64 =    0x2007;
+pub const DW_AT_GNU_RANGES_BASE: u64 =      0x2132;
+pub const DW_AT_GNU_ADDR_BASE: u64 =        0x2133;
+
+const DW_FORM_ADDR: u64 =           0x01;
+const DW_FORM_BLOCK2: u64 =         0x03;
+const DW_FORM_BLOCK4: u64 =         0x04;
+const DW_FORM_DATA2: u64 =          0x05;
+const DW_FORM_DATA4: u64 =          0x06;
+const DW_FORM_DATA8: u64 =          0x07;
+const DW_FORM_STRING: u64 =         0x08;
+const DW_FORM_BLOCK: u64 =          0x09;
+const DW_FORM_BLOCK1: u64 =         0x0a;
+const DW_FORM_DATA1: u64 =          0x0b;
+const DW_FORM_FLAG: u64 =           0x0c;
+const DW_FORM_SDATA: u64 =          0x0d;
+const DW_FORM_STRP: u64 =           0x0e;
+const DW_FORM_UDATA: u64 =          0x0f;
+const DW_FORM_REF_ADDR: u64 =       0x10;
+const DW_FORM_REF1: u64 =           0x11;
+const DW_FORM_REF2: u64 =           0x12;
+const DW_FORM_REF4: u64 =           0x13;
+const DW_FORM_REF8: u64 =           0x14;
+const DW_FORM_REF_UDATA: u64 =      0x15;
+const DW_FORM_INDIRECT: u64 =       0x16;
+const DW_FORM_SEC_OFFSET: u64 =     0x17;
+const DW_FORM_EXPRLOC: u64 =        0x18;
+const DW_FORM_FLAG_PRESENT: u64 =   0x19;
+const DW_FORM_STRX: u64 =           0x1a;
+const DW_FORM_ADDRX: u64 =          0x1b;
+const DW_FORM_REF_SUP4: u64 =       0x1c;
+const DW_FORM_STRP_SUP: u64 =       0x1d;
+const DW_FORM_DATA16: u64 =         0x1e;
+const DW_FORM_LINE_STRP: u64 =      0x1f;
+const DW_FORM_REF_SIG8: u64 =       0x20;
+const DW_FORM_IMPLICIT_CONST: u64 = 0x21;
+const DW_FORM_LOCLISTX: u64 =       0x22;
+const DW_FORM_RNGLISTX: u64 =       0x23;
+const DW_FORM_REF_SUP8: u64 =       0x24;
+const DW_FORM_STRX1: u64 =          0x25;
+const DW_FORM_STRX2: u64 =          0x26;
+const DW_FORM_STRX3: u64 =          0x27;
+const DW_FORM_STRX4: u64 =          0x28;
+const DW_FORM_ADDRX1: u64 =         0x29;
+const DW_FORM_ADDRX2: u64 =         0x2a;
+const DW_FORM_ADDRX3: u64 =         0x2b;
+const DW_FORM_ADDRX4: u64 =         0x2c;
+const DW_FORM_GNU_ADDR_INDEX: u64 = 0x1f01;
+const DW_FORM_GNU_STR_INDEX: u64 =  0x1f02;
+const DW_FORM_GNU_REF_ALT: u64 =    0x1f20;
+const DW_FORM_GNU_STRP_ALT: u64 =   0x1f21;
+
+const DW_UT_COMPILE: u8 =           0x01;
+const DW_UT_TYPE: u8 =              0x02;
+const DW_UT_SKELETON: u8 =          0x04;
+const DW_UT_SPLIT_COMPILE: u8 =     0x05;
+const DW_UT_SPLIT_TYPE: u8 =        0x06;
+
+const DW_RLE_END_OF_LIST: u8 =      0x00;
+const DW_RLE_BASE_ADDRESSX: u8 =    0x01;
+const DW_RLE_STARTX_ENDX: u8 =      0x02;
+const DW_RLE_STARTX_LENGTH: u8 =    0x03;
+const DW_RLE_OFFSET_PAIR: u8 =      0x04;
+const DW_RLE_BASE_ADDRESS: u8 =     0x05;
+const DW_RLE_START_END: u8 =        0x06;
+const DW_RLE_START_LENGTH: u8 =     0x07;
+
+const DW_LNCT_PATH: u64 =           0x1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 0x2;
+
+const DW_LNS_COPY: u8 =             0x01;
+const DW_LNS_ADVANCE_PC: u8 =       0x02;
+const DW_LNS_ADVANCE_LINE: u8 =     0x03;
+const DW_LNS_SET_FILE: u8 =         0x04;
+const DW_LNS_SET_COLUMN: u8 =       0x05;
+const DW_LNS_NEGATE_STMT: u8 =      0x06;
+const DW_LNS_CONST_ADD_PC: u8 =     0x08;
+const DW_LNS_FIXED_ADVANCE_PC: u8 = 0x09;
+
+const DW_LNE_END_SEQUENCE: u8 =     0x01;
+const DW_LNE_SET_ADDRESS: u8 =      0x02;
+
+/// The value of a single DIE attribute, with section offsets already
+/// resolved where that can be done without the owning unit.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrValue<'a> {
+    Addr(u64),
+    AddrIndex(u64),
+    Udata(u64),
+    Sdata(i64),
+    Flag(bool),
+    Block(&'a [u8]),
+    Exprloc(&'a [u8]),
+    Str(&'a [u8]),
+    StrIndex(u64),
+    /// Absolute offset of the referenced DIE within `.debug_info`.
+    Ref(usize),
+    SecOffset(u64),
+    RangeListIndex(u64),
+    LocListIndex(u64),
+    TypeSignature(u64),
+    /// References into a supplementary object file, which are not followed.
+    Supplementary(u64),
+}
+
+impl AttrValue<'_> {
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            AttrValue::Udata(v) => Some(v),
+            AttrValue::Sdata(v) => Some(v as u64),
+            AttrValue::Addr(v) => Some(v),
+            AttrValue::SecOffset(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            AttrValue::Udata(v) => Some(v as i64),
+            AttrValue::Sdata(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AttrSpec {
+    name: u64,
+    form: u64,
+    implicit_const: i64,
+}
+
+#[derive(Debug)]
+struct Abbrev {
+    tag: u64,
+    has_children: bool,
+    attrs: Vec<AttrSpec>,
+}
+
+/// A debugging information entry.
+#[derive(Debug)]
+pub struct Die<'a> {
+    pub offset: usize,
+    pub tag: u64,
+    pub attrs: Vec<(u64, AttrValue<'a>)>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+impl<'a> Die<'a> {
+    pub fn attr(&self, name: u64) -> Option<&AttrValue<'a>> {
+        self.attrs.iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v)
+    }
+}
+
+/// A unit from `.debug_info` along with its flattened DIE tree. `dies[0]` is
+/// the unit DIE, children are referenced by index into `dies`.
+#[derive(Debug)]
+pub struct Unit<'a> {
+    pub offset: usize,
+    pub version: u16,
+    pub unit_type: u8,
+    pub address_size: u8,
+    pub offset_size: u8,
+    pub dies: Vec<Die<'a>>,
+    base_address: u64,
+    str_offsets_base: u64,
+    addr_base: u64,
+    rnglists_base: u64,
+}
+
+impl<'a> Unit<'a> {
+    pub fn root(&self) -> Option<&Die<'a>> {
+        self.dies.first()
+    }
+}
+
+/// Header of a line number program, as referenced by `DW_AT_stmt_list`.
+#[derive(Debug)]
+pub struct LineProgramHeader {
+    pub offset: usize,
+    pub version: u16,
+    pub address_size: u8,
+    pub min_inst_length: u8,
+    pub max_ops_per_inst: u8,
+    pub default_is_stmt: bool,
+    pub line_base: i8,
+    pub line_range: u8,
+    pub opcode_base: u8,
+    pub standard_opcode_lengths: Vec<u8>,
+    pub include_directories: Vec<String>,
+    pub file_names: Vec<String>,
+    /// Offset of the first opcode of the program within `.debug_line`.
+    pub program_offset: usize,
+    /// Offset one past the end of the program within `.debug_line`.
+    pub end_offset: usize,
+}
+
+impl LineProgramHeader {
+    /// Resolves a file index as used by `DW_AT_decl_file`/`DW_AT_call_file`.
+    /// Prior to DWARF 5 file indices are one based.
+    pub fn file_name(&self, index: u64) -> Option<&str> {
+        let index = if self.version >= 5 {
+            index as usize
+        } else {
+            (index as usize).checked_sub(1)?
+        };
+
+        self.file_names.get(index).map(|s| s.as_str())
+    }
+}
+
+/// A row of the line number matrix.
+#[derive(Debug, Clone)]
+pub struct LineRow {
+    pub address: u64,
+    pub file: u64,
+    pub line: u64,
+    pub column: u64,
+    pub is_stmt: bool,
+    /// Marks the first address past the end of a sequence.
+    pub end_sequence: bool,
+}
+
+/// A source location, as found by `Dwarf::find_location`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: String,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        if self.column != 0 {
+            write!(f, ":{}", self.column)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct CompileUnit {
+    pub offset: usize,
+    pub version: u16,
+    pub producer: Option<String>,
+    pub language: Option<u64>,
+    pub name: Option<String>,
+    pub comp_dir: Option<String>,
+    pub ranges: Vec<Range<u64>>,
+    pub functions: Vec<Function>,
+}
+
+impl CompileUnit {
+    pub fn language_name(&self) -> Option<&'static str> {
+        self.language.map(language_name)
+    }
+}
+
+/// A concrete (out of line) function.
+#[derive(Debug)]
+pub struct Function {
+    pub offset: usize,
+    pub name: Option<String>,
+    pub linkage_name: Option<String>,
+    pub ranges: Vec<Range<u64>>,
+    pub inlined: Vec<InlinedCall>,
+}
+
+/// A `DW_TAG_inlined_subroutine` instance. `call_*` describe the location
+/// in the caller where the function was inlined.
+#[derive(Debug)]
+pub struct InlinedCall {
+    pub offset: usize,
+    pub name: Option<String>,
+    pub ranges: Vec<Range<u64>>,
+    pub call_file: Option<String>,
+    pub call_line: u64,
+    pub call_column: u64,
+    pub inlined: Vec<InlinedCall>,
+}
+
+/// One logical frame at an address, innermost first when returned by
+/// `Dwarf::find_frames`. For inlined frames, `call_*` locate the call site
+/// within the next (outer) frame.
+#[derive(Debug)]
+pub struct Frame {
+    pub function: Option<String>,
+    pub linkage_name: Option<String>,
+    pub inlined: bool,
+    pub call_file: Option<String>,
+    pub call_line: u64,
+    pub call_column: u64,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.function.as_deref().unwrap_or("??");
+        if self.inlined {
+            write!(f, "{name} (inlined)")?;
+        } else {
+            write!(f, "{name}")?;
+        }
+
+        if let Some(file) = &self.call_file {
+            write!(f, " called at {}:{}", file, self.call_line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The DWARF sections of an `Elf`, parsed into units and DIE trees.
+#[derive(Debug)]
+pub struct Dwarf<'a> {
+    info: &'a [u8],
+    abbrev: &'a [u8],
+    str_: &'a [u8],
+    line_str: &'a [u8],
+    str_offsets: &'a [u8],
+    addr: &'a [u8],
+    ranges: &'a [u8],
+    rnglists: &'a [u8],
+    line: &'a [u8],
+    units: Vec<Unit<'a>>,
+    die_index: HashMap<usize, (usize, usize)>,
+}
+
+impl<'a> Dwarf<'a> {
+    pub fn load(elf: &'a Elf) -> Result<Self> {
+        let mut dwarf = Dwarf {
+            info: debug_section(elf, ".debug_info")?,
+            abbrev: debug_section(elf, ".debug_abbrev")?,
+            str_: debug_section(elf, ".debug_str")?,
+            line_str: debug_section(elf, ".debug_line_str")?,
+            str_offsets: debug_section(elf, ".debug_str_offsets")?,
+            addr: debug_section(elf, ".debug_addr")?,
+            ranges: debug_section(elf, ".debug_ranges")?,
+            rnglists: debug_section(elf, ".debug_rnglists")?,
+            line: debug_section(elf, ".debug_line")?,
+            units: Vec::new(),
+            die_index: HashMap::new(),
+        };
+
+        if dwarf.info.is_empty() {
+            return Err(Error::other("No .debug_info section present"));
+        }
+
+        dwarf.parse_units()?;
+        Ok(dwarf)
+    }
+
+    pub fn units(&self) -> &[Unit<'a>] {
+        &self.units
+    }
+
+    /// Looks up a DIE by its absolute `.debug_info` offset.
+    pub fn die_at(&self, offset: usize) -> Option<(&Unit<'a>, &Die<'a>)> {
+        let &(u, d) = self.die_index.get(&offset)?;
+        let unit = &self.units[u];
+        Some((unit, &unit.dies[d]))
+    }
+
+    fn parse_units(&mut self) -> Result<()> {
+        let mut abbrev_cache: HashMap<u64, HashMap<u64, Abbrev>> = HashMap::new();
+        let mut r = Reader::new(self.info);
+
+        while !r.is_empty() {
+            let offset = r.pos();
+            let (length, offset_size) = read_initial_length(&mut r)?;
+            let end = checked(r.pos().checked_add(length as usize))?;
+            let version = r.u16()?;
+
+            let (unit_type, address_size, abbrev_offset) = if version >= 5 {
+                let unit_type = r.u8()?;
+                let address_size = r.u8()?;
+                let abbrev_offset = r.uint(offset_size as usize)?;
+                match unit_type {
+                    DW_UT_SKELETON | DW_UT_SPLIT_COMPILE => r.skip(8)?,
+                    DW_UT_TYPE | DW_UT_SPLIT_TYPE => r.skip(8 + offset_size as usize)?,
+                    _ => (),
+                }
+                (unit_type, address_size, abbrev_offset)
+            } else if (2..=4).contains(&version) {
+                let abbrev_offset = r.uint(offset_size as usize)?;
+                let address_size = r.u8()?;
+                (DW_UT_COMPILE, address_size, abbrev_offset)
+            } else {
+                return Err(Error::other(
+                        format!("Unsupported DWARF version {} in unit at {:#x}", version, offset)));
+            };
+
+            if let std::collections::hash_map::Entry::Vacant(e) = abbrev_cache.entry(abbrev_offset) {
+                e.insert(parse_abbrevs(self.abbrev, abbrev_offset as usize)?);
+            }
+            let abbrevs = &abbrev_cache[&abbrev_offset];
+
+            let mut unit = Unit {
+                offset,
+                version,
+                unit_type,
+                address_size,
+                offset_size,
+                dies: Vec::new(),
+                base_address: 0,
+                str_offsets_base: 0,
+                addr_base: 0,
+                rnglists_base: 0,
+            };
+
+            self.parse_dies(&mut r, end, &mut unit, abbrevs)?;
+            unit.resolve_bases(self);
+
+            let index = self.units.len();
+            for (i, die) in unit.dies.iter().enumerate() {
+                self.die_index.insert(die.offset, (index, i));
+            }
+            self.units.push(unit);
+
+            r.seek(end);
+        }
+
+        Ok(())
+    }
+
+    fn parse_dies(&self, r: &mut Reader<'a>, end: usize, unit: &mut Unit<'a>,
+                  abbrevs: &HashMap<u64, Abbrev>) -> Result<()> {
+        let mut parents: Vec<usize> = Vec::new();
+
+        while r.pos() < end {
+            let offset = r.pos();
+            let code = r.uleb128()?;
+
+            if code == 0 {
+                parents.pop();
+                continue;
+            }
+
+            let abbrev = match abbrevs.get(&code) {
+                Some(a) => a,
+                None => return Err(Error::other(
+                        format!("Unknown abbreviation code {} at {:#x}", code, offset))),
+            };
+
+            let mut attrs = Vec::with_capacity(abbrev.attrs.len());
+            for spec in &abbrev.attrs {
+                let value = self.read_attr(r, unit, spec.form, spec.implicit_const)?;
+                attrs.push((spec.name, value));
+            }
+
+            let index = unit.dies.len();
+            let parent = parents.last().copied();
+            if let Some(p) = parent {
+                unit.dies[p].children.push(index);
+            }
+
+            unit.dies.push(Die {
+                offset,
+                tag: abbrev.tag,
+                attrs,
+                parent,
+                children: Vec::new(),
+            });
+
+            if abbrev.has_children {
+                parents.push(index);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_attr(&self, r: &mut Reader<'a>, unit: &Unit, form: u64, implicit_const: i64) -> Result<AttrValue<'a>> {
+        let offset_size = unit.offset_size as usize;
+        let value = match form {
+            DW_FORM_ADDR => AttrValue::Addr(r.uint(unit.address_size as usize)?),
+            DW_FORM_BLOCK1 => {
+                let len = r.u8()? as usize;
+                AttrValue::Block(r.bytes(len)?)
+            },
+            DW_FORM_BLOCK2 => {
+                let len = r.u16()? as usize;
+                AttrValue::Block(r.bytes(len)?)
+            },
+            DW_FORM_BLOCK4 => {
+                let len = r.u32()? as usize;
+                AttrValue::Block(r.bytes(len)?)
+            },
+            DW_FORM_BLOCK => {
+                let len = r.uleb128()? as usize;
+                AttrValue::Block(r.bytes(len)?)
+            },
+            DW_FORM_EXPRLOC => {
+                let len = r.uleb128()? as usize;
+                AttrValue::Exprloc(r.bytes(len)?)
+            },
+            DW_FORM_DATA1 => AttrValue::Udata(r.u8()? as u64),
+            DW_FORM_DATA2 => AttrValue::Udata(r.u16()? as u64),
+            DW_FORM_DATA4 => AttrValue::Udata(r.u32()? as u64),
+            DW_FORM_DATA8 => AttrValue::Udata(r.u64()?),
+            DW_FORM_DATA16 => AttrValue::Block(r.bytes(16)?),
+            DW_FORM_SDATA => AttrValue::Sdata(r.sleb128()?),
+            DW_FORM_UDATA => AttrValue::Udata(r.uleb128()?),
+            DW_FORM_IMPLICIT_CONST => AttrValue::Sdata(implicit_const),
+            DW_FORM_FLAG => AttrValue::Flag(r.u8()? != 0),
+            DW_FORM_FLAG_PRESENT => AttrValue::Flag(true),
+            DW_FORM_STRING => AttrValue::Str(r.c_str()?),
+            DW_FORM_STRP => {
+                let off = r.uint(offset_size)? as usize;
+                AttrValue::Str(str_at(self.str_, off)?)
+            },
+            DW_FORM_LINE_STRP => {
+                let off = r.uint(offset_size)? as usize;
+                AttrValue::Str(str_at(self.line_str, off)?)
+            },
+            DW_FORM_STRX | DW_FORM_GNU_STR_INDEX => AttrValue::StrIndex(r.uleb128()?),
+            DW_FORM_STRX1 => AttrValue::StrIndex(r.u8()? as u64),
+            DW_FORM_STRX2 => AttrValue::StrIndex(r.u16()? as u64),
+            DW_FORM_STRX3 => AttrValue::StrIndex(read_u24(r)?),
+            DW_FORM_STRX4 => AttrValue::StrIndex(r.u32()? as u64),
+            DW_FORM_ADDRX | DW_FORM_GNU_ADDR_INDEX => AttrValue::AddrIndex(r.uleb128()?),
+            DW_FORM_ADDRX1 => AttrValue::AddrIndex(r.u8()? as u64),
+            DW_FORM_ADDRX2 => AttrValue::AddrIndex(r.u16()? as u64),
+            DW_FORM_ADDRX3 => AttrValue::AddrIndex(read_u24(r)?),
+            DW_FORM_ADDRX4 => AttrValue::AddrIndex(r.u32()? as u64),
+            DW_FORM_REF1 => AttrValue::Ref(unit_ref(unit, r.u8()? as u64)?),
+            DW_FORM_REF2 => AttrValue::Ref(unit_ref(unit, r.u16()? as u64)?),
+            DW_FORM_REF4 => AttrValue::Ref(unit_ref(unit, r.u32()? as u64)?),
+            DW_FORM_REF8 => AttrValue::Ref(unit_ref(unit, r.u64()?)?),
+            DW_FORM_REF_UDATA => AttrValue::Ref(unit_ref(unit, r.uleb128()?)?),
+            DW_FORM_REF_ADDR => {
+                // DWARF 2 encoded this as an address rather than an offset
+                let size = if unit.version == 2 { unit.address_size as usize } else { offset_size };
+                AttrValue::Ref(r.uint(size)? as usize)
+            },
+            DW_FORM_REF_SIG8 => AttrValue::TypeSignature(r.u64()?),
+            DW_FORM_SEC_OFFSET => AttrValue::SecOffset(r.uint(offset_size)?),
+            DW_FORM_LOCLISTX => AttrValue::LocListIndex(r.uleb128()?),
+            DW_FORM_RNGLISTX => AttrValue::RangeListIndex(r.uleb128()?),
+            DW_FORM_REF_SUP4 => AttrValue::Supplementary(r.u32()? as u64),
+            DW_FORM_REF_SUP8 => AttrValue::Supplementary(r.u64()?),
+            DW_FORM_STRP_SUP | DW_FORM_GNU_REF_ALT | DW_FORM_GNU_STRP_ALT =>
+                AttrValue::Supplementary(r.uint(offset_size)?),
+            DW_FORM_INDIRECT => {
+                let form = r.uleb128()?;
+                let implicit_const = if form == DW_FORM_IMPLICIT_CONST { r.sleb128()? } else { 0 };
+                return self.read_attr(r, unit, form, implicit_const);
+            },
+            _ => return Err(Error::other(
+                    format!("Unsupported attribute form {:#x} at {:#x}", form, r.pos()))),
+        };
+
+        Ok(value)
+    }
+
+    /// Resolves string valued attributes, including those referencing
+    /// `.debug_str_offsets`.
+    pub fn attr_string(&self, unit: &Unit, value: &AttrValue) -> Option<String> {
+        match *value {
+            AttrValue::Str(s) => Some(String::from_utf8_lossy(s).into_owned()),
+            AttrValue::StrIndex(i) => {
+                let size = unit.offset_size as usize;
+                let pos = (i as usize).checked_mul(size)?.checked_add(unit.str_offsets_base as usize)?;
+                let off = Reader::at(self.str_offsets, pos).uint(size).ok()?;
+                let s = str_at(self.str_, off as usize).ok()?;
+                Some(String::from_utf8_lossy(s).into_owned())
+            },
+            _ => None,
+        }
+    }
+
+    /// Resolves address valued attributes, including those referencing
+    /// `.debug_addr`.
+    pub fn attr_address(&self, unit: &Unit, value: &AttrValue) -> Option<u64> {
+        match *value {
+            AttrValue::Addr(a) => Some(a),
+            AttrValue::AddrIndex(i) => self.indexed_address(unit, i).ok(),
+            _ => None,
+        }
+    }
+
+    fn indexed_address(&self, unit: &Unit, index: u64) -> Result<u64> {
+        let size = unit.address_size as usize;
+        let pos = checked((index as usize).checked_mul(size)
+            .and_then(|p| p.checked_add(unit.addr_base as usize)))?;
+        Reader::at(self.addr, pos).uint(size)
+    }
+
+    /// Returns the name of `die`, following `DW_AT_abstract_origin` and
+    /// `DW_AT_specification` as needed.
+    pub fn die_name(&self, unit: &Unit, die: &Die) -> Option<String> {
+        self.find_attr_string(unit, die, DW_AT_NAME, 0)
+    }
+
+    /// Returns the mangled name of `die`, following `DW_AT_abstract_origin`
+    /// and `DW_AT_specification` as needed.
+    pub fn die_linkage_name(&self, unit: &Unit, die: &Die) -> Option<String> {
+        self.find_attr_string(unit, die, DW_AT_LINKAGE_NAME, 0)
+            .or_else(|| self.find_attr_string(unit, die, DW_AT_MIPS_LINKAGE_NAME, 0))
+    }
+
+    fn find_attr_string(&self, unit: &Unit, die: &Die, name: u64, depth: usize) -> Option<String> {
+        if let Some(v) = die.attr(name) {
+            return self.attr_string(unit, v);
+        }
+
+        // Guard against reference cycles in malformed input
+        if depth > 16 {
+            return None;
+        }
+
+        for origin in [DW_AT_ABSTRACT_ORIGIN, DW_AT_SPECIFICATION] {
+            if let Some(&AttrValue::Ref(off)) = die.attr(origin) {
+                if let Some((u, d)) = self.die_at(off) {
+                    if let Some(s) = self.find_attr_string(u, d, name, depth + 1) {
+                        return Some(s);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns the address ranges covered by `die`, from either
+    /// `DW_AT_low_pc`/`DW_AT_high_pc` or `DW_AT_ranges`.
+    pub fn die_ranges(&self, unit: &Unit, die: &Die) -> Result<Vec<Range<u64>>> {
+        if let Some(v) = die.attr(DW_AT_RANGES) {
+            return self.range_list(unit, die, v);
+        }
+
+        let low = match die.attr(DW_AT_LOW_PC).and_then(|v| self.attr_address(unit, v)) {
+            Some(l) => l,
+            None => return Ok(Vec::new()),
+        };
+
+        let high = match die.attr(DW_AT_HIGH_PC) {
+            Some(v @ (AttrValue::Addr(_) | AttrValue::AddrIndex(_))) =>
+                self.attr_address(unit, v).unwrap_or(low),
+            Some(v) => checked(low.checked_add(v.as_u64().unwrap_or(0)))?,
+            None => checked(low.checked_add(1))?,
+        };
+
+        if high <= low {
+            return Ok(Vec::new());
+        }
+
+        Ok(vec![Range { start: low, end: high }])
+    }
+
+    fn range_list(&self, unit: &Unit, die: &Die, value: &AttrValue) -> Result<Vec<Range<u64>>> {
+        let offset = match *value {
+            AttrValue::RangeListIndex(i) => {
+                let size = unit.offset_size as usize;
+                let pos = checked((i as usize).checked_mul(size)
+                    .and_then(|p| p.checked_add(unit.rnglists_base as usize)))?;
+                checked(unit.rnglists_base.checked_add(Reader::at(self.rnglists, pos).uint(size)?))?
+            },
+            AttrValue::SecOffset(o) | AttrValue::Udata(o) => {
+                // DW_AT_GNU_ranges_base is only applied to non-unit DIEs of split units
+                if unit.version < 5 && die.tag != DW_TAG_COMPILE_UNIT && die.tag != DW_TAG_SKELETON_UNIT {
+                    checked(o.checked_add(unit.rnglists_base))?
+                } else {
+                    o
+                }
+            },
+            _ => return Err(Error::other(
+                    format!("Unsupported DW_AT_ranges value for DIE at {:#x}", die.offset))),
+        };
+
+        if unit.version >= 5 {
+            self.parse_rnglist(unit, offset as usize)
+        } else {
+            self.parse_ranges(unit, offset as usize)
+        }
+    }
+
+    fn parse_ranges(&self, unit: &Unit, offset: usize) -> Result<Vec<Range<u64>>> {
+        let size = unit.address_size as usize;
+        let max = if size == 4 { u32::MAX as u64 } else { u64::MAX };
+        let mut base = unit.base_address;
+        let mut ranges = Vec::new();
+        let mut r = Reader::at(self.ranges, offset);
+
+        loop {
+            let begin = r.uint(size)?;
+            let end = r.uint(size)?;
+
+            if begin == 0 && end == 0 {
+                break;
+            }
+
+            if begin == max {
+                base = end;
+                continue;
+            }
+
+            if begin < end {
+                ranges.push(checked(base.checked_add(begin))?..checked(base.checked_add(end))?);
+            }
+        }
+
+        Ok(ranges)
+    }
+
+    fn parse_rnglist(&self, unit: &Unit, offset: usize) -> Result<Vec<Range<u64>>> {
+        let size = unit.address_size as usize;
+        let mut base = unit.base_address;
+        let mut ranges = Vec::new();
+        let mut r = Reader::at(self.rnglists, offset);
+
+        loop {
+            let range = match r.u8()? {
+                DW_RLE_END_OF_LIST => break,
+                DW_RLE_BASE_ADDRESSX => {
+                    base = self.indexed_address(unit, r.uleb128()?)?;
+                    continue;
+                },
+                DW_RLE_BASE_ADDRESS => {
+                    base = r.uint(size)?;
+                    continue;
+                },
+                DW_RLE_STARTX_ENDX => {
+                    let start = self.indexed_address(unit, r.uleb128()?)?;
+                    let end = self.indexed_address(unit, r.uleb128()?)?;
+                    start..end
+                },
+                DW_RLE_STARTX_LENGTH => {
+                    let start = self.indexed_address(unit, r.uleb128()?)?;
+                    start..checked(start.checked_add(r.uleb128()?))?
+                },
+                DW_RLE_OFFSET_PAIR => {
+                    let start = checked(base.checked_add(r.uleb128()?))?;
+                    start..checked(base.checked_add(r.uleb128()?))?
+                },
+                DW_RLE_START_END => {
+                    let start = r.uint(size)?;
+                    start..r.uint(size)?
+                },
+                DW_RLE_START_LENGTH => {
+                    let start = r.uint(size)?;
+                    start..checked(start.checked_add(r.uleb128()?))?
+                },
+                kind => return Err(Error::other(
+                        format!("Invalid range list entry kind {:#x}", kind))),
+            };
+
+            if range.start < range.end {
+                ranges.push(range);
+            }
+        }
+
+        Ok(ranges)
+    }
+
+    /// Parses the header of the line number program belonging to `unit`.
+    pub fn line_program_header(&self, unit: &Unit) -> Result<LineProgramHeader> {
+        let offset = match unit.root().and_then(|d| d.attr(DW_AT_STMT_LIST)).and_then(|v| v.as_u64()) {
+            Some(o) => o as usize,
+            None => return Err(Error::other(
+                    format!("Unit at {:#x} has no line number program", unit.offset))),
+        };
+
+        let mut r = Reader::at(self.line, offset);
+        let (length, offset_size) = read_initial_length(&mut r)?;
+        let end_offset = checked(r.pos().checked_add(length as usize))?;
+        let version = r.u16()?;
+
+        let mut address_size = unit.address_size;
+        if version >= 5 {
+            address_size = r.u8()?;
+            let _segment_selector_size = r.u8()?;
+        }
+
+        let header_length = r.uint(offset_size as usize)? as usize;
+        let program_offset = checked(r.pos().checked_add(header_length))?;
+
+        let min_inst_length = r.u8()?;
+        let max_ops_per_inst = if version >= 4 { r.u8()? } else { 1 };
+        let default_is_stmt = r.u8()? != 0;
+        let line_base = r.i8()?;
+        let line_range = r.u8()?;
+        let opcode_base = r.u8()?;
+        let standard_opcode_lengths = r.bytes(opcode_base.saturating_sub(1) as usize)?.to_vec();
+
+        let comp_dir = unit.root()
+            .and_then(|d| d.attr(DW_AT_COMP_DIR))
+            .and_then(|v| self.attr_string(unit, v));
+
+        let mut include_directories = Vec::new();
+        let mut file_names = Vec::new();
+
+        if version >= 5 {
+            for (path, _) in self.read_entry_table(&mut r, unit, offset_size)? {
+                include_directories.push(path);
+            }
+
+            for (path, dir) in self.read_entry_table(&mut r, unit, offset_size)? {
+                let dir = include_directories.get(dir as usize).map(|s| s.as_str());
+                file_names.push(join_path(dir, &path));
+            }
+        } else {
+            if let Some(dir) = &comp_dir {
+                include_directories.push(dir.clone());
+            }
+
+            loop {
+                let dir = r.c_string()?;
+                if dir.is_empty() {
+                    break;
+                }
+                include_directories.push(join_path(comp_dir.as_deref(), &dir));
+            }
+
+            loop {
+                let name = r.c_string()?;
+                if name.is_empty() {
+                    break;
+                }
+                let dir = r.uleb128()?;
+                let _mtime = r.uleb128()?;
+                let _length = r.uleb128()?;
+
+                let dir = include_directories.get(dir as usize).map(|s| s.as_str());
+                file_names.push(join_path(dir, &name));
+            }
+        }
+
+        Ok(LineProgramHeader {
+            offset,
+            version,
+            address_size,
+            min_inst_length,
+            max_ops_per_inst,
+            default_is_stmt,
+            line_base,
+            line_range,
+            opcode_base,
+            standard_opcode_lengths,
+            include_directories,
+            file_names,
+            program_offset,
+            end_offset,
+        })
+    }
+
+    /// Runs the line number program described by `header`, returning every
+    /// row of the resulting matrix in program order.
+    pub fn line_rows(&self, header: &LineProgramHeader) -> Result<Vec<LineRow>> {
+        let end = header.end_offset.min(self.line.len());
+        let mut r = Reader::new(&self.line[..end]);
+        r.seek(header.program_offset);
+
+        let initial = LineRow {
+            address: 0,
+            file: 1,
+            line: 1,
+            column: 0,
+            is_stmt: header.default_is_stmt,
+            end_sequence: false,
+        };
+        let min_inst_length = header.min_inst_length as u64;
+        let line_range = header.line_range.max(1);
+
+        let mut rows = Vec::new();
+        let mut row = initial.clone();
+
+        while !r.is_empty() {
+            let opcode = r.u8()?;
+
+            if opcode >= header.opcode_base {
+                let adjusted = opcode - header.opcode_base;
+                row.address = advance(row.address, (adjusted / line_range) as u64, min_inst_length)?;
+                row.line = row.line.wrapping_add_signed(
+                    header.line_base as i64 + (adjusted % line_range) as i64);
+                rows.push(row.clone());
+                continue;
+            }
+
+            match opcode {
+                0 => {
+                    let len = r.uleb128()? as usize;
+                    if len == 0 {
+                        return Err(Error::other("empty extended line number opcode"));
+                    }
+                    let start = r.pos();
+                    let end = checked(start.checked_add(len))?;
+                    match r.u8()? {
+                        DW_LNE_END_SEQUENCE => {
+                            row.end_sequence = true;
+                            rows.push(row);
+                            row = initial.clone();
+                        },
+                        DW_LNE_SET_ADDRESS => row.address = r.uint(len - 1)?,
+                        _ => (),
+                    }
+                    r.seek(end);
+                },
+                DW_LNS_COPY => rows.push(row.clone()),
+                DW_LNS_ADVANCE_PC => row.address = advance(row.address, r.uleb128()?, min_inst_length)?,
+                DW_LNS_ADVANCE_LINE => row.line = row.line.wrapping_add_signed(r.sleb128()?),
+                DW_LNS_SET_FILE => row.file = r.uleb128()?,
+                DW_LNS_SET_COLUMN => row.column = r.uleb128()?,
+                DW_LNS_NEGATE_STMT => row.is_stmt = !row.is_stmt,
+                DW_LNS_CONST_ADD_PC => row.address = advance(row.address,
+                    ((255 - header.opcode_base) / line_range) as u64, min_inst_length)?,
+                DW_LNS_FIXED_ADVANCE_PC => row.address = advance(row.address, r.u16()? as u64, 1)?,
+                _ => {
+                    // Opcodes that do not affect the address or line, and
+                    // unknown ones, are skipped using their operand count.
+                    let count = header.standard_opcode_lengths
+                        .get(opcode as usize - 1)
+                        .copied()
+                        .unwrap_or(0);
+                    for _ in 0..count {
+                        r.uleb128()?;
+                    }
+                },
+            }
+        }
+
+        Ok(rows)
+    }
+
+    /// Finds the source file and line of the instruction at `addr`.
+    pub fn find_location(&self, addr: u64) -> Result<Option<Location>> {
+        for unit in &self.units {
+            let root = match unit.root() {
+                Some(r) if is_unit_tag(r.tag) => r,
+                _ => continue,
+            };
+
+            let cu_ranges = self.die_ranges(unit, root)?;
+            if !cu_ranges.is_empty() && !cu_ranges.iter().any(|r| r.contains(&addr)) {
+                continue;
+            }
+
+            let header = match self.line_program_header(unit) {
+                Ok(h) => h,
+                Err(_) => continue,
+            };
+
+            let rows = self.line_rows(&header)?;
+            let found = rows.windows(2)
+                .find(|w| !w[0].end_sequence && w[0].address <= addr && addr < w[1].address);
+
+            if let Some(w) = found {
+                return Ok(Some(Location {
+                    file: header.file_name(w[0].file).unwrap_or("??").to_string(),
+                    line: w[0].line,
+                    column: w[0].column,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads a DWARF 5 directory or file name table, returning the path and
+    /// directory index of each entry.
+    fn read_entry_table(&self, r: &mut Reader<'a>, unit: &Unit, offset_size: u8) -> Result<Vec<(String, u64)>> {
+        let format_count = r.u8()?;
+        let mut formats = Vec::with_capacity(format_count as usize);
+        for _ in 0..format_count {
+            formats.push((r.uleb128()?, r.uleb128()?));
+        }
+
+        // The forms here are interpreted relative to the line table, not the unit
+        let ctx = Unit {
+            offset: 0,
+            version: 5,
+            unit_type: DW_UT_COMPILE,
+            address_size: unit.address_size,
+            offset_size,
+            dies: Vec::new(),
+            base_address: 0,
+            str_offsets_base: unit.str_offsets_base,
+            addr_base: unit.addr_base,
+            rnglists_base: 0,
+        };
+
+        let count = r.uleb128()?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let mut path = String::new();
+            let mut dir = 0;
+
+            for &(content, form) in &formats {
+                let value = self.read_attr(r, &ctx, form, 0)?;
+                match content {
+                    DW_LNCT_PATH => path = self.attr_string(&ctx, &value).unwrap_or_default(),
+                    DW_LNCT_DIRECTORY_INDEX => dir = value.as_u64().unwrap_or(0),
+                    _ => (),
+                }
+            }
+
+            entries.push((path, dir));
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns a summary of every compile unit, including the functions
+    /// defined within it and their inlined call trees.
+    pub fn compile_units(&self) -> Result<Vec<CompileUnit>> {
+        let mut cus = Vec::new();
+
+        for unit in &self.units {
+            let root = match unit.root() {
+                Some(r) if is_unit_tag(r.tag) => r,
+                _ => continue,
+            };
+
+            // A malformed unit is skipped, the others are still usable.
+            let ranges = match self.die_ranges(unit, root) {
+                Ok(r) => r,
+                Err(_) => continue,
+            };
+            let files = self.line_program_header(unit).ok();
+            let mut functions = Vec::new();
+            if self.collect_functions(unit, 0, files.as_ref(), &mut functions).is_err() {
+                continue;
+            }
+
+            cus.push(CompileUnit {
+                offset: unit.offset,
+                version: unit.version,
+                producer: root.attr(DW_AT_PRODUCER).and_then(|v| self.attr_string(unit, v)),
+                language: root.attr(DW_AT_LANGUAGE).and_then(|v| v.as_u64()),
+                name: root.attr(DW_AT_NAME).and_then(|v| self.attr_string(unit, v)),
+                comp_dir: root.attr(DW_AT_COMP_DIR).and_then(|v| self.attr_string(unit, v)),
+                ranges,
+                functions,
+            });
+        }
+
+        Ok(cus)
+    }
+
+    fn collect_functions(&self, unit: &Unit, index: usize, files: Option<&LineProgramHeader>,
+                         out: &mut Vec<Function>) -> Result<()> {
+        for &child in &unit.dies[index].children {
+            let die = &unit.dies[child];
+            if die.tag == DW_TAG_SUBPROGRAM {
+                let ranges = self.die_ranges(unit, die)?;
+                if !ranges.is_empty() {
+                    out.push(Function {
+                        offset: die.offset,
+                        name: self.die_name(unit, die),
+                        linkage_name: self.die_linkage_name(unit, die),
+                        ranges,
+                        inlined: self.collect_inlined(unit, child, files)?,
+                    });
+                }
+            }
+
+            // Nested functions, C++ namespaces and member functions
+            self.collect_functions(unit, child, files, out)?;
+        }
+
+        Ok(())
+    }
+
+    fn collect_inlined(&self, unit: &Unit, index: usize, files: Option<&LineProgramHeader>) -> Result<Vec<InlinedCall>> {
+        let mut calls = Vec::new();
+
+        for &child in &unit.dies[index].children {
+            let die = &unit.dies[child];
+            match die.tag {
+                DW_TAG_INLINED_SUBROUTINE => {
+                    let (call_file, call_line, call_column) = self.call_site(die, files);
+                    calls.push(InlinedCall {
+                        offset: die.offset,
+                        name: self.die_name(unit, die),
+                        ranges: self.die_ranges(unit, die)?,
+                        call_file,
+                        call_line,
+                        call_column,
+                        inlined: self.collect_inlined(unit, child, files)?,
+                    });
+                },
+                DW_TAG_SUBPROGRAM => (),
+                _ => calls.extend(self.collect_inlined(unit, child, files)?),
+            }
+        }
+
+        Ok(calls)
+    }
+
+    fn call_site(&self, die: &Die, files: Option<&LineProgramHeader>) -> (Option<String>, u64, u64) {
+        let file = die.attr(DW_AT_CALL_FILE)
+            .and_then(|v| v.as_u64())
+            .and_then(|i| files.and_then(|f| f.file_name(i)))
+            .map(|s| s.to_string());
+        let line = die.attr(DW_AT_CALL_LINE).and_then(|v| v.as_u64()).unwrap_or(0);
+        let column = die.attr(DW_AT_CALL_COLUMN).and_then(|v| v.as_u64()).unwrap_or(0);
+
+        (file, line, column)
+    }
+
+    /// Expands `addr` into its logical call stack, innermost inlined frame
+    /// first and the containing concrete function last.
+    pub fn find_frames(&self, addr: u64) -> Result<Vec<Frame>> {
+        for unit in &self.units {
+            let root = match unit.root() {
+                Some(r) if is_unit_tag(r.tag) => r,
+                _ => continue,
+            };
+
+            let cu_ranges = self.die_ranges(unit, root)?;
+            if !cu_ranges.is_empty() && !cu_ranges.iter().any(|r| r.contains(&addr)) {
+                continue;
+            }
+
+            let mut stack = Vec::new();
+            self.find_scopes(unit, 0, addr, &mut stack)?;
+            if stack.is_empty() {
+                continue;
+            }
+
+            let files = self.line_program_header(unit).ok();
+            let mut frames = Vec::with_capacity(stack.len());
+
+            for &i in stack.iter().rev() {
+                let die = &unit.dies[i];
+                let inlined = die.tag == DW_TAG_INLINED_SUBROUTINE;
+                let (call_file, call_line, call_column) = if inlined {
+                    self.call_site(die, files.as_ref())
+                } else {
+                    (None, 0, 0)
+                };
+
+                frames.push(Frame {
+                    function: self.die_name(unit, die),
+                    linkage_name: self.die_linkage_name(unit, die),
+                    inlined,
+                    call_file,
+                    call_line,
+                    call_column,
+                });
+            }
+
+            return Ok(frames);
+        }
+
+        Ok(Vec::new())
+    }
+
+    fn find_scopes(&self, unit: &Unit, index: usize, addr: u64, stack: &mut Vec<usize>) -> Result<bool> {
+        for &child in &unit.dies[index].children {
+            let die = &unit.dies[child];
+            match die.tag {
+                DW_TAG_SUBPROGRAM | DW_TAG_INLINED_SUBROUTINE => {
+                    let ranges = self.die_ranges(unit, die)?;
+                    if ranges.iter().any(|r| r.contains(&addr)) {
+                        stack.push(child);
+                        self.find_scopes(unit, child, addr, stack)?;
+                        return Ok(true);
+                    }
+                },
+                DW_TAG_LEXICAL_BLOCK => {
+                    let ranges = self.die_ranges(unit, die)?;
+                    if (ranges.is_empty() || ranges.iter().any(|r| r.contains(&addr)))
+                        && self.find_scopes(unit, child, addr, stack)? {
+                        return Ok(true);
+                    }
+                },
+                _ if !die.children.is_empty() && stack.is_empty()
+                    && self.find_scopes(unit, child, addr, stack)? => return Ok(true),
+                _ => (),
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+impl Unit<'_> {
+    /// Records the per-unit bases used to resolve indexed forms. These live on
+    /// the unit DIE itself, so can only be resolved once it has been read.
+    fn resolve_bases(&mut self, dwarf: &Dwarf) {
+        let offset_size = self.offset_size as u64;
+        let root = match self.dies.first() {
+            Some(r) => r,
+            None => return,
+        };
+
+        let base = |names: &[u64]| names.iter()
+            .find_map(|&n| root.attr(n))
+            .and_then(|v| v.as_u64());
+
+        let str_offsets_base = base(&[DW_AT_STR_OFFSETS_BASE]).unwrap_or(2 * offset_size);
+        let addr_base = base(&[DW_AT_ADDR_BASE, DW_AT_GNU_ADDR_BASE]).unwrap_or(0);
+        let rnglists_base = base(&[DW_AT_RNGLISTS_BASE, DW_AT_GNU_RANGES_BASE]).unwrap_or(0);
+        let low_pc = root.attr(DW_AT_LOW_PC).cloned();
+
+        self.str_offsets_base = str_offsets_base;
+        self.addr_base = addr_base;
+        self.rnglists_base = rnglists_base;
+        self.base_address = match low_pc {
+            Some(v) => dwarf.attr_address(self, &v).unwrap_or(0),
+            None => 0,
+        };
+    }
+}
+
+fn debug_section<'a>(elf: &'a Elf, name: &str) -> Result<&'a [u8]> {
+    let section = match elf.get_section_by_name(name) {
+        Some(s) => s,
+        None => return Ok(&[]),
+    };
+
+    if section.has_flag(SectionFlag::Compressed) {
+        return Err(Error::other(
+                format!("Compressed debug section {name} is not supported")));
+    }
+
+    elf.get_section_contents(section)
+}
+
+fn parse_abbrevs(data: &[u8], offset: usize) -> Result<HashMap<u64, Abbrev>> {
+    let mut abbrevs = HashMap::new();
+    let mut r = Reader::at(data, offset);
+
+    loop {
+        let code = r.uleb128()?;
+        if code == 0 {
+            break;
+        }
+
+        let tag = r.uleb128()?;
+        let has_children = r.u8()? != 0;
+        let mut attrs = Vec::new();
+
+        loop {
+            let name = r.uleb128()?;
+            let form = r.uleb128()?;
+            if name == 0 && form == 0 {
+                break;
+            }
+
+            let implicit_const = if form == DW_FORM_IMPLICIT_CONST { r.sleb128()? } else { 0 };
+            attrs.push(AttrSpec { name, form, implicit_const });
+        }
+
+        abbrevs.insert(code, Abbrev { tag, has_children, attrs });
+    }
+
+    Ok(abbrevs)
+}
+
+/// Reads a unit length, returning the length and the offset size (4 or 8)
+/// used by the rest of the unit.
+pub(crate) fn read_initial_length(r: &mut Reader) -> Result<(u64, u8)> {
+    let length = r.u32()?;
+    if length == 0xffffffff {
+        Ok((r.u64()?, 8))
+    } else {
+        Ok((length as u64, 4))
+    }
+}
+
+/// Turns an overflowing offset or length computation into an error.
+fn checked<T>(value: Option<T>) -> Result<T> {
+    value.ok_or_else(|| Error::other("offset or length out of range"))
+}
+
+/// Resolves a reference relative to the start of `unit`.
+fn unit_ref(unit: &Unit, offset: u64) -> Result<usize> {
+    checked(unit.offset.checked_add(offset as usize))
+}
+
+/// Moves a line table address forward by `operation_advance` instructions.
+fn advance(address: u64, operation_advance: u64, min_inst_length: u64) -> Result<u64> {
+    checked(operation_advance.checked_mul(min_inst_length)
+        .and_then(|delta| address.checked_add(delta)))
+}
+
+fn read_u24(r: &mut Reader) -> Result<u64> {
+    let b = r.bytes(3)?;
+    Ok(b[0] as u64 | (b[1] as u64) << 8 | (b[2] as u64) << 16)
+}
+
+fn str_at(data: &[u8], offset: usize) -> Result<&[u8]> {
+    Reader::at(data, offset).c_str()
+}
+
+fn is_unit_tag(tag: u64) -> bool {
+    matches!(tag, DW_TAG_COMPILE_UNIT | DW_TAG_PARTIAL_UNIT | DW_TAG_SKELETON_UNIT)
+}
+
+fn join_path(dir: Option<&str>, name: &str) -> String {
+    match dir {
+        Some(d) if !name.starts_with('/') && !d.is_empty() => format!("{d}/{name}"),
+        _ => name.to_string(),
+    }
+}
+
+/// Returns a human readable name for a `DW_AT_language` value.
+pub fn language_name(lang: u64) -> &'static str {
+    match lang {
+        0x01 => "C89",
+        0x02 => "C",
+        0x03 => "Ada83",
+        0x04 => "C++",
+        0x05 => "Cobol74",
+        0x06 => "Cobol85",
+        0x07 => "Fortran77",
+        0x08 => "Fortran90",
+        0x09 => "Pascal83",
+        0x0a => "Modula2",
+        0x0b => "Java",
+        0x0c => "C99",
+        0x0d => "Ada95",
+        0x0e => "Fortran95",
+        0x0f => "PLI",
+        0x10 => "ObjC",
+        0x11 => "ObjC++",
+        0x12 => "UPC",
+        0x13 => "D",
+        0x14 => "Python",
+        0x15 => "OpenCL",
+        0x16 => "Go",
+        0x17 => "Modula3",
+        0x18 => "Haskell",
+        0x19 => "C++03",
+        0x1a => "C++11",
+        0x1b => "OCaml",
+        0x1c => "Rust",
+        0x1d => "C11",
+        0x1e => "Swift",
+        0x1f => "Julia",
+        0x20 => "Dylan",
+        0x21 => "C++14",
+        0x22 => "Fortran03",
+        0x23 => "Fortran08",
+        0x24 => "RenderScript",
+        0x25 => "BLISS",
+        0x26 => "Kotlin",
+        0x27 => "Zig",
+        0x28 => "Crystal",
+        0x2a => "C++17",
+        0x2b => "C++20",
+        0x2c => "C17",
+        0x2d => "Fortran18",
+        0x2e => "Ada2005",
+        0x2f => "Ada2012",
+        0x30 => "HIP",
+        0x31 => "Assembly",
+        0x32 => "C#",
+        0x33 => "Mojo",
+        0x8001 => "Mips Assembler",
+        0x8e57 => "Google RenderScript",
+        0xb000 => "Borland Delphi",
+        _ => "unknown",
+    }
+}
@@ -29,7 +29,7 @@ impl Dynamic {
     }
 }
 
-#[derive(Debug, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, FromPrimitive)]
 pub enum EntryType {
     Null,
     Needed,
@@ -67,6 +67,7 @@ pub enum EntryType {
     PreInitArraySize,
     SymtabShndx,
     RelRSize,
+    Relr,
     RelREnt,
     Num,
     LoOS = 0x6000000d,
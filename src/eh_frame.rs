@@ -0,0 +1,762 @@
+use std::{
+    collections::HashMap,
+    collections::hash_map::Entry,
+    fmt,
+    io::*,
+    ops::Range,
+};
+
+use crate::dwarf::read_initial_length;
+use crate::elf::Elf;
+use crate::segment::SegmentType;
+use crate::utils::*;
+
+pub const DW_EH_PE_ABSPTR: u8 =     0x00;
+pub const DW_EH_PE_ULEB128: u8 =    0x01;
+pub const DW_EH_PE_UDATA2: u8 =     0x02;
+pub const DW_EH_PE_UDATA4: u8 =     0x03;
+pub const DW_EH_PE_UDATA8: u8 =     0x04;
+pub const DW_EH_PE_SLEB128: u8 =    0x09;
+pub const DW_EH_PE_SDATA2: u8 =     0x0a;
+pub const DW_EH_PE_SDATA4: u8 =     0x0b;
+pub const DW_EH_PE_SDATA8: u8 =     0x0c;
+pub const DW_EH_PE_PCREL: u8 =      0x10;
+pub const DW_EH_PE_TEXTREL: u8 =    0x20;
+pub const DW_EH_PE_DATAREL: u8 =    0x30;
+pub const DW_EH_PE_FUNCREL: u8 =    0x40;
+pub const DW_EH_PE_ALIGNED: u8 =    0x50;
+pub const DW_EH_PE_INDIRECT: u8 =   0x80;
+pub const DW_EH_PE_OMIT: u8 =       0xff;
+
+const DW_CFA_ADVANCE_LOC: u8 =          0x40;
+const DW_CFA_OFFSET: u8 =               0x80;
+const DW_CFA_RESTORE: u8 =              0xc0;
+const DW_CFA_NOP: u8 =                  0x00;
+const DW_CFA_SET_LOC: u8 =              0x01;
+const DW_CFA_ADVANCE_LOC1: u8 =         0x02;
+const DW_CFA_ADVANCE_LOC2: u8 =         0x03;
+const DW_CFA_ADVANCE_LOC4: u8 =         0x04;
+const DW_CFA_OFFSET_EXTENDED: u8 =      0x05;
+const DW_CFA_RESTORE_EXTENDED: u8 =     0x06;
+const DW_CFA_UNDEFINED: u8 =            0x07;
+const DW_CFA_SAME_VALUE: u8 =           0x08;
+const DW_CFA_REGISTER: u8 =             0x09;
+const DW_CFA_REMEMBER_STATE: u8 =       0x0a;
+const DW_CFA_RESTORE_STATE: u8 =        0x0b;
+const DW_CFA_DEF_CFA: u8 =              0x0c;
+const DW_CFA_DEF_CFA_REGISTER: u8 =     0x0d;
+const DW_CFA_DEF_CFA_OFFSET: u8 =       0x0e;
+const DW_CFA_DEF_CFA_EXPRESSION: u8 =   0x0f;
+const DW_CFA_EXPRESSION: u8 =           0x10;
+const DW_CFA_OFFSET_EXTENDED_SF: u8 =   0x11;
+const DW_CFA_DEF_CFA_SF: u8 =           0x12;
+const DW_CFA_DEF_CFA_OFFSET_SF: u8 =    0x13;
+const DW_CFA_VAL_OFFSET: u8 =           0x14;
+const DW_CFA_VAL_OFFSET_SF: u8 =        0x15;
+const DW_CFA_VAL_EXPRESSION: u8 =       0x16;
+const DW_CFA_AARCH64_NEGATE_RA_STATE: u8 = 0x2d;
+const DW_CFA_GNU_ARGS_SIZE: u8 =        0x2e;
+const DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED: u8 = 0x2f;
+
+/// Base addresses used to resolve pointer encodings.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PointerBases {
+    /// Virtual address of the first byte of the data being read.
+    pub section: u64,
+    pub text: u64,
+    pub data: u64,
+    pub func: u64,
+}
+
+/// Reads a pointer encoded as described by a `DW_EH_PE_*` value. Returns
+/// `None` for `DW_EH_PE_omit`. Indirect pointers are returned as the address
+/// of the location holding the pointer, as there is no memory to read from.
+pub fn read_encoded_pointer(r: &mut Reader, encoding: u8, bases: &PointerBases) -> Result<Option<u64>> {
+    if encoding == DW_EH_PE_OMIT {
+        return Ok(None);
+    }
+
+    if encoding & 0x70 == DW_EH_PE_ALIGNED {
+        let addr = checked_cfi(bases.section.checked_add(r.pos() as u64))?;
+        let aligned = checked_cfi(addr.checked_add(7))? & !7;
+        r.skip((aligned - addr) as usize)?;
+    }
+
+    let field = bases.section.wrapping_add(r.pos() as u64);
+    let value = match encoding & 0x0f {
+        DW_EH_PE_ABSPTR => r.u64()?,
+        DW_EH_PE_ULEB128 => r.uleb128()?,
+        DW_EH_PE_UDATA2 => r.u16()? as u64,
+        DW_EH_PE_UDATA4 => r.u32()? as u64,
+        DW_EH_PE_UDATA8 => r.u64()?,
+        DW_EH_PE_SLEB128 => r.sleb128()? as u64,
+        DW_EH_PE_SDATA2 => r.i16()? as i64 as u64,
+        DW_EH_PE_SDATA4 => r.i32()? as i64 as u64,
+        DW_EH_PE_SDATA8 => r.i64()? as u64,
+        fmt => return Err(Error::other(
+                format!("Unsupported pointer encoding format {:#x}", fmt))),
+    };
+
+    let base = match encoding & 0x70 {
+        DW_EH_PE_PCREL => field,
+        DW_EH_PE_TEXTREL => bases.text,
+        DW_EH_PE_DATAREL => bases.data,
+        DW_EH_PE_FUNCREL => bases.func,
+        _ => 0,
+    };
+
+    Ok(Some(base.wrapping_add(value)))
+}
+
+/// Common Information Entry.
+#[derive(Debug, Clone)]
+pub struct Cie<'a> {
+    pub offset: usize,
+    pub version: u8,
+    pub augmentation: String,
+    pub code_alignment: u64,
+    pub data_alignment: i64,
+    pub return_address_register: u16,
+    pub fde_encoding: u8,
+    pub lsda_encoding: u8,
+    pub personality: Option<u64>,
+    pub signal_frame: bool,
+    pub initial_instructions: &'a [u8],
+}
+
+/// Frame Description Entry.
+#[derive(Debug, Clone)]
+pub struct Fde<'a> {
+    pub offset: usize,
+    pub cie_offset: usize,
+    pub pc_begin: u64,
+    pub pc_range: u64,
+    pub lsda: Option<u64>,
+    pub instructions: &'a [u8],
+}
+
+impl Fde<'_> {
+    pub fn pc_end(&self) -> u64 {
+        self.pc_begin.wrapping_add(self.pc_range)
+    }
+
+    pub fn contains(&self, pc: u64) -> bool {
+        pc >= self.pc_begin && pc < self.pc_end()
+    }
+}
+
+/// Rule to compute the Canonical Frame Address.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CfaRule {
+    RegisterOffset { register: u16, offset: i64 },
+    Expression(Vec<u8>),
+}
+
+/// Rule to recover the value a register had in the caller's frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegisterRule {
+    Undefined,
+    SameValue,
+    /// Saved at CFA + n.
+    Offset(i64),
+    /// The value is CFA + n.
+    ValOffset(i64),
+    /// Saved in another register.
+    Register(u16),
+    /// Saved at the address computed by the expression.
+    Expression(Vec<u8>),
+    /// The value is computed by the expression.
+    ValExpression(Vec<u8>),
+}
+
+/// A row of the unwind table, valid for `start..end`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnwindRow {
+    pub start: u64,
+    pub end: u64,
+    pub cfa: CfaRule,
+    pub registers: Vec<(u16, RegisterRule)>,
+    pub return_address_register: u16,
+    pub signal_frame: bool,
+}
+
+impl UnwindRow {
+    pub fn register(&self, register: u16) -> Option<&RegisterRule> {
+        self.registers.iter()
+            .find(|(r, _)| *r == register)
+            .map(|(_, rule)| rule)
+    }
+}
+
+impl fmt::Display for UnwindRow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}..{:#x} ", self.start, self.end)?;
+        match &self.cfa {
+            CfaRule::RegisterOffset { register, offset } => write!(f, "cfa=r{}{:+}", register, offset)?,
+            CfaRule::Expression(_) => write!(f, "cfa=exp")?,
+        }
+
+        for (reg, rule) in &self.registers {
+            match rule {
+                RegisterRule::Undefined => write!(f, " r{}=u", reg)?,
+                RegisterRule::SameValue => write!(f, " r{}=s", reg)?,
+                RegisterRule::Offset(n) => write!(f, " r{}=c{:+}", reg, n)?,
+                RegisterRule::ValOffset(n) => write!(f, " r{}=v{:+}", reg, n)?,
+                RegisterRule::Register(r) => write!(f, " r{}=r{}", reg, r)?,
+                RegisterRule::Expression(_) => write!(f, " r{}=exp", reg)?,
+                RegisterRule::ValExpression(_) => write!(f, " r{}=vexp", reg)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RowState {
+    cfa: CfaRule,
+    registers: Vec<(u16, RegisterRule)>,
+}
+
+impl RowState {
+    fn set(&mut self, register: u16, rule: RegisterRule) {
+        match self.registers.iter_mut().find(|(r, _)| *r == register) {
+            Some(entry) => entry.1 = rule,
+            None => self.registers.push((register, rule)),
+        }
+    }
+
+    fn restore(&mut self, register: u16, initial: &RowState) {
+        self.registers.retain(|(r, _)| *r != register);
+        if let Some((_, rule)) = initial.registers.iter().find(|(r, _)| *r == register) {
+            self.registers.push((register, rule.clone()));
+        }
+    }
+}
+
+/// The binary search table from `.eh_frame_hdr` / `PT_GNU_EH_FRAME`.
+#[derive(Debug, Clone)]
+pub struct EhFrameHdr {
+    pub vaddr: u64,
+    pub version: u8,
+    pub eh_frame_ptr: u64,
+    /// `(initial_location, fde_address)` pairs, sorted by location.
+    pub table: Vec<(u64, u64)>,
+}
+
+impl EhFrameHdr {
+    pub fn parse(data: &[u8], vaddr: u64) -> Result<Self> {
+        let mut r = Reader::new(data);
+        let bases = PointerBases { section: vaddr, data: vaddr, ..Default::default() };
+
+        let version = r.u8()?;
+        if version != 1 {
+            return Err(Error::other(
+                    format!("Unsupported .eh_frame_hdr version {}", version)));
+        }
+
+        let eh_frame_ptr_enc = r.u8()?;
+        let fde_count_enc = r.u8()?;
+        let table_enc = r.u8()?;
+
+        let eh_frame_ptr = match read_encoded_pointer(&mut r, eh_frame_ptr_enc, &bases)? {
+            Some(p) => p,
+            None => return Err(Error::other(".eh_frame_hdr has no .eh_frame pointer")),
+        };
+
+        let mut table = Vec::new();
+        if let Some(count) = read_encoded_pointer(&mut r, fde_count_enc, &bases)? {
+            if table_enc != DW_EH_PE_OMIT {
+                table.reserve(count.min(r.remaining() as u64) as usize);
+                for _ in 0..count {
+                    let loc = read_encoded_pointer(&mut r, table_enc, &bases)?.unwrap_or(0);
+                    let fde = read_encoded_pointer(&mut r, table_enc, &bases)?.unwrap_or(0);
+                    table.push((loc, fde));
+                }
+            }
+        }
+
+        Ok(EhFrameHdr { vaddr, version, eh_frame_ptr, table })
+    }
+
+    /// Returns the address of the FDE which may cover `pc`.
+    pub fn lookup(&self, pc: u64) -> Option<u64> {
+        let idx = self.table.partition_point(|&(loc, _)| loc <= pc);
+        idx.checked_sub(1).map(|i| self.table[i].1)
+    }
+}
+
+/// Call frame information parsed from `.eh_frame`.
+#[derive(Debug)]
+pub struct EhFrame<'a> {
+    pub vaddr: u64,
+    pub hdr: Option<EhFrameHdr>,
+    cies: HashMap<usize, Cie<'a>>,
+    /// Sorted by `pc_begin`.
+    fdes: Vec<Fde<'a>>,
+    /// Maps FDE offsets to indices into `fdes`.
+    fde_offsets: HashMap<usize, usize>,
+}
+
+impl<'a> EhFrame<'a> {
+    /// Locates and parses the call frame information of `elf`, using the
+    /// section headers if present and `PT_GNU_EH_FRAME` otherwise.
+    pub fn load(elf: &'a Elf) -> Result<Self> {
+        let hdr = match elf.get_section_by_name(".eh_frame_hdr") {
+            Some(s) => Some(EhFrameHdr::parse(elf.get_section_contents(s)?, s.addr as u64)?),
+            None => match elf.get_segments_by_type(SegmentType::GnuEhFrame).next() {
+                Some(seg) => Some(EhFrameHdr::parse(
+                        elf.read_vaddr(seg.vaddr, seg.filesz)?, seg.vaddr as u64)?),
+                None => None,
+            },
+        };
+
+        let (data, vaddr) = match elf.get_section_by_name(".eh_frame") {
+            Some(s) => (elf.get_section_contents(s)?, s.addr as u64),
+            None => match &hdr {
+                // Without section headers the size is unknown, the data is
+                // parsed up to the zero terminator or the end of the segment
+                Some(h) => (elf.read_vaddr(h.eh_frame_ptr as usize, usize::MAX)?, h.eh_frame_ptr),
+                None => return Err(Error::other("No .eh_frame or PT_GNU_EH_FRAME present")),
+            },
+        };
+
+        let data_base = elf.get_section_by_name(".got")
+            .map(|s| s.addr as u64)
+            .unwrap_or(0);
+
+        Self::parse(data, vaddr, data_base, hdr)
+    }
+
+    /// Parses `.eh_frame` contents mapped at `vaddr`.
+    pub fn parse(data: &'a [u8], vaddr: u64, data_base: u64, hdr: Option<EhFrameHdr>) -> Result<Self> {
+        let bases = PointerBases { section: vaddr, data: data_base, ..Default::default() };
+        let mut cies = HashMap::new();
+        let mut fdes = Vec::new();
+        let mut r = Reader::new(data);
+
+        while r.remaining() >= 4 {
+            let offset = r.pos();
+            let (length, _) = read_initial_length(&mut r)?;
+            if length == 0 {
+                break;
+            }
+
+            let start = r.pos();
+            let end = match start.checked_add(length as usize) {
+                Some(e) if e <= data.len() => e,
+                _ => return Err(Error::other(
+                        format!("Truncated CFI entry at .eh_frame+{:#x}", offset))),
+            };
+
+            let id = r.u32()?;
+            let mut entry = Reader::at(&data[..end], r.pos());
+
+            if id == 0 {
+                let cie = parse_cie(&mut entry, offset, &bases)?;
+                cies.insert(offset, cie);
+            } else {
+                let cie_offset = match start.checked_sub(id as usize) {
+                    Some(o) => o,
+                    None => return Err(Error::other(
+                            format!("Invalid CIE pointer in FDE at .eh_frame+{:#x}", offset))),
+                };
+
+                // CIEs normally precede their FDEs, but nothing requires it
+                if let Entry::Vacant(e) = cies.entry(cie_offset) {
+                    let mut cr = Reader::at(data, cie_offset);
+                    let (cie_len, _) = read_initial_length(&mut cr)?;
+                    let cie_end = cr.pos().saturating_add(cie_len as usize).min(data.len());
+                    cr.skip(4)?;
+                    let mut cr = Reader::at(&data[..cie_end], cr.pos());
+                    e.insert(parse_cie(&mut cr, cie_offset, &bases)?);
+                }
+
+                let fde = parse_fde(&mut entry, offset, &cies[&cie_offset], &bases)?;
+                fdes.push(fde);
+            }
+
+            r.seek(end);
+        }
+
+        fdes.sort_by_key(|f| f.pc_begin);
+        let fde_offsets = fdes.iter()
+            .enumerate()
+            .map(|(i, f)| (f.offset, i))
+            .collect();
+
+        Ok(EhFrame { vaddr, hdr, cies, fdes, fde_offsets })
+    }
+
+    pub fn cie(&self, offset: usize) -> Option<&Cie<'a>> {
+        self.cies.get(&offset)
+    }
+
+    pub fn iter_cies(&self) -> impl Iterator<Item = &Cie<'a>> {
+        self.cies.values()
+    }
+
+    pub fn iter_fdes(&self) -> impl Iterator<Item = &Fde<'a>> {
+        self.fdes.iter()
+    }
+
+    /// Finds the FDE covering `pc`, using the `.eh_frame_hdr` search table
+    /// when available.
+    pub fn find_fde(&self, pc: u64) -> Option<&Fde<'a>> {
+        if let Some(hdr) = &self.hdr {
+            if let Some(addr) = hdr.lookup(pc) {
+                let offset = addr.wrapping_sub(self.vaddr) as usize;
+                if let Some(&i) = self.fde_offsets.get(&offset) {
+                    let fde = &self.fdes[i];
+                    if fde.contains(pc) {
+                        return Some(fde);
+                    }
+                }
+            }
+        }
+
+        let idx = self.fdes.partition_point(|f| f.pc_begin <= pc);
+        let fde = &self.fdes[idx.checked_sub(1)?];
+        if fde.contains(pc) { Some(fde) } else { None }
+    }
+
+    /// Returns the address range of every function described by an FDE,
+    /// which is reliable even when the symbol table has been stripped.
+    pub fn function_ranges(&self) -> Vec<Range<u64>> {
+        self.fdes.iter()
+            .filter(|f| f.pc_range != 0)
+            .map(|f| f.pc_begin..f.pc_end())
+            .collect()
+    }
+
+    /// Evaluates the CFA program of `fde`, returning the complete unwind table.
+    pub fn unwind_rows(&self, fde: &Fde) -> Result<Vec<UnwindRow>> {
+        let cie = match self.cies.get(&fde.cie_offset) {
+            Some(c) => c,
+            None => return Err(Error::other(
+                    format!("Missing CIE for FDE at .eh_frame+{:#x}", fde.offset))),
+        };
+
+        let bases = PointerBases { section: self.vaddr, ..Default::default() };
+        let mut initial = RowState {
+            cfa: CfaRule::RegisterOffset { register: 0, offset: 0 },
+            registers: Vec::new(),
+        };
+        let mut loc = fde.pc_begin;
+
+        // The initial instructions may not advance the location
+        let mut unused = Vec::new();
+        let mut state = initial.clone();
+        execute_cfa(cie.initial_instructions, cie, &bases, &mut state, None, &mut loc, &mut unused)?;
+        initial = state.clone();
+
+        let mut rows = Vec::new();
+        let mut row_start = fde.pc_begin;
+        execute_cfa(fde.instructions, cie, &bases, &mut state, Some(&initial), &mut loc, &mut rows)?;
+
+        if let Some(last) = rows.last() {
+            row_start = last.end;
+        }
+
+        if row_start < fde.pc_end() {
+            rows.push(make_row(&state, row_start, fde.pc_end(), cie));
+        }
+
+        Ok(rows)
+    }
+
+    /// Evaluates the CFA rules in effect at `pc`.
+    pub fn unwind_row(&self, pc: u64) -> Result<Option<UnwindRow>> {
+        let fde = match self.find_fde(pc) {
+            Some(f) => f,
+            None => return Ok(None),
+        };
+
+        let row = self.unwind_rows(fde)?
+            .into_iter()
+            .find(|r| pc >= r.start && pc < r.end);
+
+        Ok(row)
+    }
+}
+
+fn make_row(state: &RowState, start: u64, end: u64, cie: &Cie) -> UnwindRow {
+    UnwindRow {
+        start,
+        end,
+        cfa: state.cfa.clone(),
+        registers: state.registers.clone(),
+        return_address_register: cie.return_address_register,
+        signal_frame: cie.signal_frame,
+    }
+}
+
+/// Runs CFA instructions, pushing a row each time the location advances.
+fn execute_cfa(instructions: &[u8], cie: &Cie, bases: &PointerBases, state: &mut RowState,
+               initial: Option<&RowState>, loc: &mut u64, rows: &mut Vec<UnwindRow>) -> Result<()> {
+    let mut r = Reader::new(instructions);
+    let mut stack: Vec<RowState> = Vec::new();
+    let code_align = cie.code_alignment;
+    let data_align = cie.data_alignment;
+
+    let mut advance = |state: &RowState, loc: &mut u64, new_loc: u64| {
+        if new_loc > *loc {
+            rows.push(make_row(state, *loc, new_loc, cie));
+        }
+        *loc = new_loc;
+    };
+
+    while !r.is_empty() {
+        let op = r.u8()?;
+        let low = op & 0x3f;
+
+        match op & 0xc0 {
+            DW_CFA_ADVANCE_LOC => {
+                advance(state, loc, advance_loc(*loc, low as u64, code_align)?);
+                continue;
+            },
+            DW_CFA_OFFSET => {
+                let offset = factored(r.uleb128()?, data_align)?;
+                state.set(low as u16, RegisterRule::Offset(offset));
+                continue;
+            },
+            DW_CFA_RESTORE => {
+                if let Some(init) = initial {
+                    state.restore(low as u16, init);
+                }
+                continue;
+            },
+            _ => (),
+        }
+
+        match op {
+            DW_CFA_NOP => (),
+            DW_CFA_SET_LOC => {
+                let new_loc = read_encoded_pointer(&mut r, cie.fde_encoding, bases)?.unwrap_or(*loc);
+                advance(state, loc, new_loc);
+            },
+            DW_CFA_ADVANCE_LOC1 => {
+                let delta = r.u8()? as u64;
+                advance(state, loc, advance_loc(*loc, delta, code_align)?);
+            },
+            DW_CFA_ADVANCE_LOC2 => {
+                let delta = r.u16()? as u64;
+                advance(state, loc, advance_loc(*loc, delta, code_align)?);
+            },
+            DW_CFA_ADVANCE_LOC4 => {
+                let delta = r.u32()? as u64;
+                advance(state, loc, advance_loc(*loc, delta, code_align)?);
+            },
+            DW_CFA_OFFSET_EXTENDED => {
+                let reg = r.uleb128()? as u16;
+                let offset = factored(r.uleb128()?, data_align)?;
+                state.set(reg, RegisterRule::Offset(offset));
+            },
+            DW_CFA_RESTORE_EXTENDED => {
+                let reg = r.uleb128()? as u16;
+                if let Some(init) = initial {
+                    state.restore(reg, init);
+                }
+            },
+            DW_CFA_UNDEFINED => {
+                let reg = r.uleb128()? as u16;
+                state.set(reg, RegisterRule::Undefined);
+            },
+            DW_CFA_SAME_VALUE => {
+                let reg = r.uleb128()? as u16;
+                state.set(reg, RegisterRule::SameValue);
+            },
+            DW_CFA_REGISTER => {
+                let reg = r.uleb128()? as u16;
+                let other = r.uleb128()? as u16;
+                state.set(reg, RegisterRule::Register(other));
+            },
+            DW_CFA_REMEMBER_STATE => stack.push(state.clone()),
+            DW_CFA_RESTORE_STATE => {
+                if let Some(saved) = stack.pop() {
+                    *state = saved;
+                }
+            },
+            DW_CFA_DEF_CFA => {
+                let register = r.uleb128()? as u16;
+                let offset = r.uleb128()? as i64;
+                state.cfa = CfaRule::RegisterOffset { register, offset };
+            },
+            DW_CFA_DEF_CFA_SF => {
+                let register = r.uleb128()? as u16;
+                let offset = factored_sf(r.sleb128()?, data_align)?;
+                state.cfa = CfaRule::RegisterOffset { register, offset };
+            },
+            DW_CFA_DEF_CFA_REGISTER => {
+                let reg = r.uleb128()? as u16;
+                if let CfaRule::RegisterOffset { register, .. } = &mut state.cfa {
+                    *register = reg;
+                } else {
+                    state.cfa = CfaRule::RegisterOffset { register: reg, offset: 0 };
+                }
+            },
+            DW_CFA_DEF_CFA_OFFSET | DW_CFA_DEF_CFA_OFFSET_SF => {
+                let off = if op == DW_CFA_DEF_CFA_OFFSET {
+                    r.uleb128()? as i64
+                } else {
+                    factored_sf(r.sleb128()?, data_align)?
+                };
+                if let CfaRule::RegisterOffset { offset, .. } = &mut state.cfa {
+                    *offset = off;
+                }
+            },
+            DW_CFA_DEF_CFA_EXPRESSION => {
+                let len = r.uleb128()? as usize;
+                state.cfa = CfaRule::Expression(r.bytes(len)?.to_vec());
+            },
+            DW_CFA_EXPRESSION | DW_CFA_VAL_EXPRESSION => {
+                let reg = r.uleb128()? as u16;
+                let len = r.uleb128()? as usize;
+                let expr = r.bytes(len)?.to_vec();
+                let rule = if op == DW_CFA_EXPRESSION {
+                    RegisterRule::Expression(expr)
+                } else {
+                    RegisterRule::ValExpression(expr)
+                };
+                state.set(reg, rule);
+            },
+            DW_CFA_OFFSET_EXTENDED_SF => {
+                let reg = r.uleb128()? as u16;
+                let offset = factored_sf(r.sleb128()?, data_align)?;
+                state.set(reg, RegisterRule::Offset(offset));
+            },
+            DW_CFA_VAL_OFFSET => {
+                let reg = r.uleb128()? as u16;
+                let offset = factored(r.uleb128()?, data_align)?;
+                state.set(reg, RegisterRule::ValOffset(offset));
+            },
+            DW_CFA_VAL_OFFSET_SF => {
+                let reg = r.uleb128()? as u16;
+                let offset = factored_sf(r.sleb128()?, data_align)?;
+                state.set(reg, RegisterRule::ValOffset(offset));
+            },
+            DW_CFA_GNU_ARGS_SIZE => {
+                r.uleb128()?;
+            },
+            DW_CFA_GNU_NEGATIVE_OFFSET_EXTENDED => {
+                let reg = r.uleb128()? as u16;
+                let offset = checked_cfi(factored(r.uleb128()?, data_align)?.checked_neg())?;
+                state.set(reg, RegisterRule::Offset(offset));
+            },
+            // Return address signing state on aarch64, does not affect unwinding
+            DW_CFA_AARCH64_NEGATE_RA_STATE => (),
+            _ => return Err(Error::other(
+                    format!("Unsupported CFA instruction {:#x}", op))),
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the code alignment factor to a location advance.
+fn advance_loc(loc: u64, delta: u64, code_align: u64) -> Result<u64> {
+    checked_cfi(delta.checked_mul(code_align).and_then(|d| loc.checked_add(d)))
+}
+
+/// Applies the data alignment factor to an unsigned offset.
+fn factored(offset: u64, data_align: i64) -> Result<i64> {
+    checked_cfi(i64::try_from(offset).ok().and_then(|o| o.checked_mul(data_align)))
+}
+
+/// Applies the data alignment factor to a signed offset.
+fn factored_sf(offset: i64, data_align: i64) -> Result<i64> {
+    checked_cfi(offset.checked_mul(data_align))
+}
+
+fn checked_cfi<T>(value: Option<T>) -> Result<T> {
+    value.ok_or_else(|| Error::other("Malformed CFI: value out of range"))
+}
+
+fn parse_cie<'a>(r: &mut Reader<'a>, offset: usize, bases: &PointerBases) -> Result<Cie<'a>> {
+    let bases = PointerBases { section: bases.section, data: bases.data, ..Default::default() };
+    let version = r.u8()?;
+    if version != 1 && version != 3 && version != 4 {
+        return Err(Error::other(
+                format!("Unsupported CIE version {} at .eh_frame+{:#x}", version, offset)));
+    }
+
+    let augmentation = String::from_utf8_lossy(r.c_str()?).into_owned();
+    if augmentation.contains("eh") {
+        r.skip(8)?;
+    }
+
+    if version == 4 {
+        // address_size and segment_selector_size
+        r.skip(2)?;
+    }
+
+    let code_alignment = r.uleb128()?;
+    let data_alignment = r.sleb128()?;
+    let return_address_register = if version == 1 { r.u8()? as u16 } else { r.uleb128()? as u16 };
+
+    let mut cie = Cie {
+        offset,
+        version,
+        augmentation: augmentation.clone(),
+        code_alignment,
+        data_alignment,
+        return_address_register,
+        fde_encoding: DW_EH_PE_ABSPTR,
+        lsda_encoding: DW_EH_PE_OMIT,
+        personality: None,
+        signal_frame: false,
+        initial_instructions: &[],
+    };
+
+    if augmentation.starts_with('z') {
+        let len = r.uleb128()? as usize;
+        let aug_end = r.pos().checked_add(len)
+            .ok_or_else(|| Error::other("augmentation data length out of range"))?;
+
+        for c in augmentation.chars().skip(1) {
+            match c {
+                'L' => cie.lsda_encoding = r.u8()?,
+                'R' => cie.fde_encoding = r.u8()?,
+                'P' => {
+                    let enc = r.u8()?;
+                    cie.personality = read_encoded_pointer(r, enc, &bases)?;
+                },
+                'S' => cie.signal_frame = true,
+                // 'B' (aarch64 BTI) and 'G' (MTE tagged frames) carry no data
+                _ => (),
+            }
+        }
+
+        r.seek(aug_end);
+    }
+
+    cie.initial_instructions = r.bytes(r.remaining())?;
+    Ok(cie)
+}
+
+fn parse_fde<'a>(r: &mut Reader<'a>, offset: usize, cie: &Cie, bases: &PointerBases) -> Result<Fde<'a>> {
+    let pc_begin = read_encoded_pointer(r, cie.fde_encoding, bases)?.unwrap_or(0);
+    // The range only uses the value format of the encoding
+    let pc_range = read_encoded_pointer(r, cie.fde_encoding & 0x0f, bases)?.unwrap_or(0);
+
+    let mut lsda = None;
+    if cie.augmentation.starts_with('z') {
+        let len = r.uleb128()? as usize;
+        let aug_end = r.pos().checked_add(len)
+            .ok_or_else(|| Error::other("augmentation data length out of range"))?;
+        if len > 0 && cie.lsda_encoding != DW_EH_PE_OMIT {
+            let bases = PointerBases { func: pc_begin, ..*bases };
+            lsda = read_encoded_pointer(r, cie.lsda_encoding, &bases)?;
+        }
+        r.seek(aug_end);
+    }
+
+    Ok(Fde {
+        offset,
+        cie_offset: cie.offset,
+        pc_begin,
+        pc_range,
+        lsda,
+        instructions: r.bytes(r.remaining())?,
+    })
+}
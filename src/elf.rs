@@ -2,7 +2,7 @@ use std::{
     ptr,
     io::Result,
     io::*,
-    //mem,
+    mem,
     collections::HashMap,
     slice,
 };
@@ -12,10 +12,12 @@ use crate::section::*;
 use crate::segment::*;
 use crate::symbols::*;
 use crate::dynamic::*;
+use crate::dwarf::Dwarf;
+use crate::eh_frame::EhFrame;
+use crate::hash::{GnuHash, SysvHash};
 use crate::utils::*;
 
 //const EI_NIDENT: usize = 16;
-const ELF_MAGIC: u32 = 0x464c457f;
 
 #[derive(Debug, Default)]
 pub struct Elf {
@@ -25,23 +27,27 @@ pub struct Elf {
     //section_map: HashMap<String, &'a Section>,
     segments: Vec<Segment>,
     symbols: HashMap<String, Symbol>,
+    dynamic_symbols: Vec<Symbol>,
+    bias: usize,
     pub dynamic: Vec<Dynamic>,
 }
 
 impl Elf {
     pub fn open(path: &str) -> Result<Self> {
-        let mut elf = Elf::default();
-        elf.data = read_to_vec(path)?;
-        let data = elf.get_raw_ptr();
+        let data = read_to_vec(path)?;
 
-        if !is_ptr_to_elf(data as *const u32) {
+        if !is_elf(&data) {
             return Err(Error::other(
                     format!("'{}' not a valid elf file", path))
                 );
         }
 
-        elf.header = unsafe { ptr::read(data as *const _) };
-        elf.parse_segments();
+        Elf::from_bytes(data)
+    }
+
+    /// Parses an ELF file that has already been read into memory.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        let mut elf = Elf::parse_headers(data)?;
 
         // If the section headers have been stripped, there is no point in continuing.
         // Return early.
@@ -51,11 +57,54 @@ impl Elf {
         }
 
         elf.parse_sections()?;
-        elf.parse_symtab()?;
+        if elf.get_section_by_name(".symtab").is_some() {
+            elf.parse_symtab()?;
+        }
 
         // TODO: 
         if elf.header.e_type == ElfType::Dynamic as u16 {
-            elf.parse_dynamic_section()?;
+            match elf.get_section_by_name(".dynamic") {
+                Some(_) => elf.parse_dynamic_section()?,
+                // The section headers were rebuilt without it, e.g. by with_symbols.
+                None => elf.parse_dynamic_segment()?,
+            }
+        }
+        elf.parse_dynsym()?;
+
+        Ok(elf)
+    }
+
+    /// Parses a file image reconstructed from the PT_LOAD segments of a
+    /// running process. Section headers are not part of any segment, so they
+    /// are ignored and the dynamic section is located through PT_DYNAMIC.
+    /// `bias` is the load bias of the image, used to undo the relocation of
+    /// dynamic entries done by the loader.
+    pub fn from_memory_image(data: Vec<u8>, bias: usize) -> Result<Self> {
+        let mut elf = Elf::parse_headers(data)?;
+        elf.bias = bias;
+        elf.parse_dynamic_segment()?;
+        elf.parse_dynsym()?;
+
+        Ok(elf)
+    }
+
+    fn parse_headers(data: Vec<u8>) -> Result<Self> {
+        if !is_elf(&data) {
+            return Err(Error::other("not a valid elf file"));
+        }
+
+        let mut elf = Elf {
+            data,
+            ..Default::default()
+        };
+        elf.header = unsafe { ptr::read_unaligned(elf.get_raw_ptr() as *const _) };
+
+        let phdrs_end = (elf.header.e_phnum as usize)
+            .checked_mul(mem::size_of::<Phdr>())
+            .and_then(|size| size.checked_add(elf.header.e_phoff));
+        match phdrs_end {
+            Some(end) if end <= elf.data.len() => elf.parse_segments(),
+            _ => return Err(Error::other("program headers extend past the end of the file")),
         }
 
         Ok(elf)
@@ -137,6 +186,102 @@ impl Elf {
         Ok(())
     }
 
+    pub(crate) fn parse_dynamic_segment(&mut self) -> Result<()> {
+        let seg = match self.get_segments_by_type(SegmentType::Dynamic).next() {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        let data = match seg.offset.checked_add(seg.filesz) {
+            Some(end) if end <= self.data.len() => &self.data[seg.offset..end],
+            _ => return Err(Error::other("PT_DYNAMIC extends past the end of the image")),
+        };
+
+        let mut r = Reader::new(data);
+        let mut dynamic: Vec<Dynamic> = Vec::new();
+
+        while r.remaining() >= mem::size_of::<Dyn>() {
+            let entry = Dyn {
+                d_tag: r.u64()? as usize,
+                d_val: r.u64()? as usize,
+            };
+            dynamic.push(Dynamic::from_dyn(&entry)?);
+            if entry.d_tag == EntryType::Null as usize {
+                break;
+            }
+        }
+
+        self.dynamic = dynamic;
+
+        Ok(())
+    }
+
+    /// Parses the dynamic symbol table, from .dynsym if there are section
+    /// headers and through DT_SYMTAB otherwise.
+    pub(crate) fn parse_dynsym(&mut self) -> Result<()> {
+        let (symtab, strtab) = match self.get_section_by_name(".dynsym") {
+            Some(dynsym) => {
+                let strtab = match self.sections.get(dynsym.link as usize) {
+                    Some(s) => s,
+                    None => return Err(Error::other(".dynsym has no string table")),
+                };
+                (self.get_section_contents(dynsym)?, self.get_section_contents(strtab)?)
+            },
+            None => {
+                let (symtab, strtab) = match (self.get_dynamic_vaddr(EntryType::Symtab),
+                    self.get_dynamic_vaddr(EntryType::Strtab)) {
+                    (Some(symtab), Some(strtab)) => (symtab, strtab),
+                    _ => return Ok(()),
+                };
+                let strsz = self.get_dynamic(EntryType::StrSize).unwrap_or(0);
+                let count = self.dynamic_symbol_count()?;
+
+                (self.read_vaddr(symtab, count * mem::size_of::<ElfSym>())?,
+                    self.read_vaddr(strtab, strsz)?)
+            },
+        };
+
+        let mut dynamic_symbols = Vec::with_capacity(symtab.len() / mem::size_of::<ElfSym>());
+        let mut r = Reader::new(symtab);
+
+        while r.remaining() >= mem::size_of::<ElfSym>() {
+            let sym = ElfSym {
+                st_name: r.u32()?,
+                st_info: r.u8()?,
+                st_other: r.u8()?,
+                st_shndx: r.u16()?,
+                st_value: r.u64()? as usize,
+                st_size: r.u64()? as usize,
+            };
+
+            let mut symbol = Symbol::from_elfsym(&sym)?;
+            symbol.name = c_str_at(strtab, sym.st_name as usize)?;
+            dynamic_symbols.push(symbol);
+        }
+
+        self.dynamic_symbols = dynamic_symbols;
+
+        Ok(())
+    }
+
+    /// The number of dynamic symbols as given by the hash tables, which is
+    /// all there is to go on without section headers.
+    fn dynamic_symbol_count(&self) -> Result<usize> {
+        if let Some(hash) = self.gnu_hash_table()? {
+            return Ok(hash.symbol_count());
+        }
+        if let Some(hash) = self.sysv_hash_table()? {
+            return Ok(hash.symbol_count());
+        }
+
+        // The string table customarily follows the symbol table.
+        match (self.get_dynamic_vaddr(EntryType::Symtab), self.get_dynamic_vaddr(EntryType::Strtab)) {
+            (Some(symtab), Some(strtab)) if strtab > symtab =>
+                Ok((strtab - symtab) / mem::size_of::<ElfSym>()),
+            _ => Err(Error::other("unable to determine the size of the dynamic symbol table")),
+        }
+    }
+
     fn get_ptr_to_section(&self, section_name: &str) -> Result<*const u8> {
         let mut ptr = self.get_raw_ptr();
         let section = match self.get_section_by_name(section_name) {
@@ -173,6 +318,39 @@ impl Elf {
         Ok(slice)
     }
 
+    /// Returns the raw contents of the section named `section_name`.
+    pub fn get_section_bytes(&self, section_name: &str) -> Result<&[u8]> {
+        match self.get_section_by_name(section_name) {
+            Some(s) => self.get_section_contents(s),
+            None => Err(Error::other(
+                    format!("Failed to locate section: {section_name}"))),
+        }
+    }
+
+    /// Returns the raw contents of `section`. Sections without file backing
+    /// (SHT_NOBITS) yield an empty slice.
+    pub fn get_section_contents(&self, section: &Section) -> Result<&[u8]> {
+        if section.typ == SectionType::NoBits as u32 {
+            return Ok(&[]);
+        }
+
+        match section.offset.checked_add(section.size) {
+            Some(end) if end <= self.data.len() => Ok(&self.data[section.offset..end]),
+            _ => Err(Error::other(
+                    format!("Section {} extends past the end of the file", section.name))),
+        }
+    }
+
+    /// Parses the DWARF debugging information of this binary.
+    pub fn dwarf(&self) -> Result<Dwarf<'_>> {
+        Dwarf::load(self)
+    }
+
+    /// Parses the call frame information from `.eh_frame`.
+    pub fn eh_frame(&self) -> Result<EhFrame<'_>> {
+        EhFrame::load(self)
+    }
+
     pub fn get_section_by_name(&self, name: &str) -> Option<&Section> {
         for s in &self.sections {
             if s.name == *name {
@@ -203,6 +381,28 @@ impl Elf {
         self.symbols.get(name)
     }
 
+    /// Looks up `name` in `.symtab`, then in the dynamic symbol table.
+    pub fn find_symbol(&self, name: &str) -> Option<&Symbol> {
+        self.get_symbol(name)
+            .or_else(|| self.dynamic_symbols.iter().find(|s| s.name == name))
+    }
+
+    /// Looks up a symbol by its demangled name, e.g. "core::fmt::write".
+    pub fn get_demangled_symbol(&self, name: &str) -> Option<&Symbol> {
+        self.get_symbol(name).or_else(|| self.iter_symbols()
+            .find(|s| s.demangled().is_some_and(|d| d == name)))
+    }
+
+    /// Finds the function or object symbol covering `addr`, returning it along
+    /// with the offset of `addr` from its start.
+    pub fn symbolize(&self, addr: usize) -> Option<(&Symbol, usize)> {
+        self.iter_all_symbols()
+            .filter(|s| s.symbol_type == SymbolType::Function || s.symbol_type == SymbolType::Object)
+            .filter(|s| s.within_range(addr) || (s.size == 0 && s.value == addr))
+            .min_by_key(|s| addr - s.value)
+            .map(|s| (s, addr - s.value))
+    }
+
     pub fn iter_sections(&self) -> impl Iterator<Item = &Section> {
         self.sections.iter()
     }
@@ -211,10 +411,134 @@ impl Elf {
         self.segments.iter()
     }
 
+    pub fn get_segments_by_type(&self, typ: SegmentType) -> impl Iterator<Item = &Segment> {
+        let typ = typ as u32;
+        self.segments.iter()
+            .filter(move |s| s.typ == typ)
+    }
+
+    pub fn header(&self) -> &Ehdr {
+        &self.header
+    }
+
+    /// Returns the raw contents of the file.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Translates a virtual address into a file offset, using the PT_LOAD
+    /// segments or, if there are none, the allocated sections.
+    pub fn vaddr_to_offset(&self, vaddr: usize) -> Option<usize> {
+        let load = self.get_segments_by_type(SegmentType::Load)
+            .find(|s| vaddr >= s.vaddr && vaddr - s.vaddr < s.filesz);
+        if let Some(seg) = load {
+            return Some(seg.offset + (vaddr - seg.vaddr));
+        }
+
+        self.sections.iter()
+            .filter(|s| s.addr != 0 && s.typ != SectionType::NoBits as u32)
+            .find(|s| vaddr >= s.addr && vaddr - s.addr < s.size)
+            .map(|s| s.offset + (vaddr - s.addr))
+    }
+
+    /// Returns the file backed bytes from `vaddr` up to `len` bytes, stopping
+    /// early at the end of the containing segment.
+    pub fn read_vaddr(&self, vaddr: usize, len: usize) -> Result<&[u8]> {
+        let offset = match self.vaddr_to_offset(vaddr) {
+            Some(o) => o,
+            None => return Err(Error::other(
+                    format!("Address {:#x} is not backed by the file", vaddr))),
+        };
+
+        let limit = self.get_segments_by_type(SegmentType::Load)
+            .find(|s| vaddr >= s.vaddr && vaddr - s.vaddr < s.filesz)
+            .map(|s| s.offset + s.filesz)
+            .unwrap_or(self.data.len())
+            .min(self.data.len());
+
+        let end = offset.saturating_add(len).min(limit);
+        Ok(&self.data[offset.min(end)..end])
+    }
+
     pub fn iter_symbols(&self) -> impl Iterator<Item = &Symbol> {
         self.symbols.values()
     }
 
+    /// Iterates over `.symtab` followed by the named dynamic symbols it does
+    /// not have, which is all there is in stripped files and memory images.
+    pub fn iter_all_symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols.values().chain(self.dynamic_symbols.iter()
+            .filter(|s| !s.name.is_empty() && !self.symbols.contains_key(&s.name)))
+    }
+
+    /// Iterates over the dynamic symbol table, in table order.
+    pub fn iter_dynamic_symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.dynamic_symbols.iter()
+    }
+
+    pub fn get_dynamic_symbol(&self, index: usize) -> Option<&Symbol> {
+        self.dynamic_symbols.get(index)
+    }
+
+    /// Looks up a defined dynamic symbol through the hash tables, the way
+    /// the dynamic loader does.
+    pub fn lookup_dynamic_symbol(&self, name: &str) -> Result<Option<&Symbol>> {
+        Ok(self.dynamic_symbol_candidates(name)?
+            .into_iter()
+            .filter_map(|i| self.dynamic_symbols.get(i))
+            .find(|s| s.name == name && s.shndx != 0))
+    }
+
+    /// Returns the dynamic symbol indices the hash tables give for `name`.
+    pub fn dynamic_symbol_candidates(&self, name: &str) -> Result<Vec<usize>> {
+        let candidates = match self.gnu_hash_table()? {
+            Some(hash) => hash.candidates(name.as_bytes()),
+            None => match self.sysv_hash_table()? {
+                Some(hash) => hash.candidates(name.as_bytes()),
+                None => return Ok(Vec::new()),
+            },
+        };
+
+        Ok(candidates.into_iter().map(|i| i as usize).collect())
+    }
+
+    /// Returns the value of the first dynamic entry with tag `tag`.
+    pub fn get_dynamic(&self, tag: EntryType) -> Option<usize> {
+        self.dynamic.iter()
+            .find(|d| d.tag == tag)
+            .map(|d| d.val)
+    }
+
+    /// Returns the address held by the dynamic entry `tag` as a link time
+    /// address. In memory images the loader may have relocated it already.
+    pub fn get_dynamic_vaddr(&self, tag: EntryType) -> Option<usize> {
+        let val = self.get_dynamic(tag)?;
+        if self.bias != 0 && val >= self.bias {
+            Some(val - self.bias)
+        } else {
+            Some(val)
+        }
+    }
+
+    pub fn sysv_hash_table(&self) -> Result<Option<SysvHash>> {
+        match self.get_dynamic_vaddr(EntryType::Hash) {
+            Some(vaddr) => Ok(Some(SysvHash::parse(self.read_vaddr(vaddr, usize::MAX)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn gnu_hash_table(&self) -> Result<Option<GnuHash>> {
+        match self.get_dynamic_vaddr(EntryType::GnuHash) {
+            Some(vaddr) => Ok(Some(GnuHash::parse(self.read_vaddr(vaddr, usize::MAX)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The load bias of an image read from memory, 0 for files.
+    pub fn load_bias(&self) -> usize {
+        self.bias
+    }
+
     // TODO: does this always return a valid pointer?
     // https://doc.rust-lang.org/1.81.0/src/alloc/vec/mod.rs.html#1330 says never...?
     fn get_raw_ptr(&self) -> *mut u8 {
@@ -222,12 +546,6 @@ impl Elf {
     }
 }
 
-fn is_ptr_to_elf(p: *const u32) -> bool {
-    if p.is_null() {
-        return false
-    }
-
-    unsafe {
-        if *p == ELF_MAGIC { return true; } else {return false;}
-    }
+fn is_elf(data: &[u8]) -> bool {
+    data.len() >= mem::size_of::<Ehdr>() && data[..ELFMAG.len()] == ELFMAG
 }
@@ -0,0 +1,256 @@
+use std::{
+    fmt,
+    io::*,
+};
+
+use crate::elf::Elf;
+use crate::segment::{SegmentFlag, SegmentType};
+use crate::utils::Reader;
+
+const BUILDINFO_MAGIC: &[u8] = b"\xff Go buildinf:";
+const BUILDINFO_HEADER_SIZE: usize = 32;
+const BUILDINFO_ALIGN: usize = 16;
+
+/* Header flags */
+const FLAG_BIG_ENDIAN: u8 = 0x1;
+const FLAG_INLINE: u8 = 0x2;
+
+/// A module as recorded in the build info.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoModule {
+    pub path: String,
+    pub version: String,
+    /// The go.sum hash, e.g. "h1:...". Missing for the main module of a
+    /// build outside of version control.
+    pub sum: Option<String>,
+    /// The module that replaced this one through a replace directive.
+    pub replace: Option<Box<GoModule>>,
+}
+
+impl fmt::Display for GoModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t{}", self.path, self.version)?;
+        if let Some(sum) = &self.sum {
+            write!(f, "\t{}", sum)?;
+        }
+        if let Some(replace) = &self.replace {
+            write!(f, "\n\t=>\t{}", replace)?;
+        }
+        Ok(())
+    }
+}
+
+/// What `go version -m` reports about a binary.
+#[derive(Debug, Clone, Default)]
+pub struct GoBuildInfo {
+    /// The toolchain version, e.g. "go1.23.1".
+    pub go_version: String,
+    /// Package path of the main package.
+    pub path: String,
+    pub main: Option<GoModule>,
+    pub deps: Vec<GoModule>,
+    /// Build settings in order, e.g. ("GOOS", "linux") or ("-ldflags", "-s -w").
+    pub settings: Vec<(String, String)>,
+}
+
+impl GoBuildInfo {
+    /// Parses the module info string written by the linker (runtime/debug.modinfo).
+    pub fn parse_modinfo(go_version: &str, modinfo: &[u8]) -> Result<Self> {
+        let mut info = GoBuildInfo {
+            go_version: go_version.to_string(),
+            ..Default::default()
+        };
+
+        // The text is surrounded by 16 byte sentinels.
+        let modinfo = match modinfo.len() {
+            n if n >= 33 && modinfo[n - 17] == b'\n' => &modinfo[16..n - 16],
+            _ => modinfo,
+        };
+
+        for line in String::from_utf8_lossy(modinfo).lines() {
+            let (kind, rest) = match line.split_once('\t') {
+                Some(split) => split,
+                None => continue,
+            };
+
+            match kind {
+                "path" => info.path = rest.to_string(),
+                "mod" => info.main = Some(parse_module(rest)?),
+                "dep" => info.deps.push(parse_module(rest)?),
+                "=>" => {
+                    let replace = Box::new(parse_module(rest)?);
+                    match info.deps.last_mut().or(info.main.as_mut()) {
+                        Some(module) => module.replace = Some(replace),
+                        None => return Err(Error::other("replacement without a module")),
+                    }
+                },
+                "build" => {
+                    let (key, value) = rest.split_once('=')
+                        .ok_or_else(|| Error::other(format!("malformed build setting '{}'", rest)))?;
+                    info.settings.push((unquote(key), unquote(value)));
+                },
+                _ => (),
+            }
+        }
+
+        Ok(info)
+    }
+
+    pub fn setting(&self, key: &str) -> Option<&str> {
+        self.settings.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn goos(&self) -> Option<&str> {
+        self.setting("GOOS")
+    }
+
+    pub fn goarch(&self) -> Option<&str> {
+        self.setting("GOARCH")
+    }
+
+    pub fn cgo_enabled(&self) -> Option<bool> {
+        self.setting("CGO_ENABLED").map(|v| v == "1")
+    }
+
+    pub fn ldflags(&self) -> Option<&str> {
+        self.setting("-ldflags")
+    }
+}
+
+impl fmt::Display for GoBuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.go_version)?;
+        if !self.path.is_empty() {
+            writeln!(f, "\tpath\t{}", self.path)?;
+        }
+        if let Some(main) = &self.main {
+            writeln!(f, "\tmod\t{}", main)?;
+        }
+        for dep in &self.deps {
+            writeln!(f, "\tdep\t{}", dep)?;
+        }
+        for (key, value) in &self.settings {
+            writeln!(f, "\tbuild\t{}={}", quote(key), quote(value))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_module(text: &str) -> Result<GoModule> {
+    let mut fields = text.split('\t');
+    let path = fields.next().unwrap_or("");
+    if path.is_empty() {
+        return Err(Error::other(format!("malformed module line '{}'", text)));
+    }
+
+    Ok(GoModule {
+        path: path.to_string(),
+        version: fields.next().unwrap_or("").to_string(),
+        sum: fields.next().filter(|s| !s.is_empty()).map(|s| s.to_string()),
+        replace: None,
+    })
+}
+
+/// Quotes keys and values the way `go version -m` prints them.
+fn quote(s: &str) -> String {
+    if !s.contains([' ', '\t', '\r', '\n', '"', '`']) {
+        return s.to_string();
+    }
+    format!("{:?}", s)
+}
+
+/// Undoes the strconv.Quote applied to keys and values with spaces or
+/// special characters.
+fn unquote(s: &str) -> String {
+    let inner = match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        Some(inner) => inner,
+        None => return s.to_string(),
+    };
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => (),
+        }
+    }
+    out
+}
+
+impl Elf {
+    /// Reads the build info embedded by the Go linker, located through the
+    /// .go.buildinfo section or by scanning the data segments for its magic.
+    pub fn go_build_info(&self) -> Result<GoBuildInfo> {
+        let data = match self.get_section_by_name(".go.buildinfo") {
+            Some(s) => self.get_section_contents(s)?,
+            None => self.find_build_info()
+                .ok_or_else(|| Error::other("no Go build info found"))?,
+        };
+
+        if !data.starts_with(BUILDINFO_MAGIC) || data.len() < BUILDINFO_HEADER_SIZE {
+            return Err(Error::other("invalid Go build info header"));
+        }
+
+        let ptr_size = data[14] as usize;
+        let flags = data[15];
+
+        let (version, modinfo) = if flags & FLAG_INLINE != 0 {
+            // Go 1.18+: two varint length prefixed strings after the header.
+            let mut r = Reader::at(data, BUILDINFO_HEADER_SIZE);
+            let len = r.uleb128()? as usize;
+            let version = String::from_utf8_lossy(r.bytes(len)?).into_owned();
+            let len = r.uleb128()? as usize;
+            (version, r.bytes(len)?)
+        } else {
+            if flags & FLAG_BIG_ENDIAN != 0 {
+                return Err(Error::other("big endian Go build info is not supported"));
+            }
+            // Older releases store pointers to Go string headers.
+            let mut r = Reader::at(data, 16);
+            let version = r.uint(ptr_size)? as usize;
+            let modinfo = r.uint(ptr_size)? as usize;
+            let version = String::from_utf8_lossy(self.read_go_string(version, ptr_size)?).into_owned();
+            (version, self.read_go_string(modinfo, ptr_size)?)
+        };
+
+        GoBuildInfo::parse_modinfo(&version, modinfo)
+    }
+
+    /// Reads the Go string whose (data, len) header is at `vaddr`.
+    fn read_go_string(&self, vaddr: usize, ptr_size: usize) -> Result<&[u8]> {
+        let mut r = Reader::new(self.read_vaddr(vaddr, ptr_size * 2)?);
+        let ptr = r.uint(ptr_size)? as usize;
+        let len = r.uint(ptr_size)? as usize;
+
+        let bytes = self.read_vaddr(ptr, len)?;
+        if bytes.len() != len {
+            return Err(Error::other(format!("Go string at {:#x} is truncated", ptr)));
+        }
+        Ok(bytes)
+    }
+
+    fn find_build_info(&self) -> Option<&[u8]> {
+        let data = self.as_bytes();
+
+        self.get_segments_by_type(SegmentType::Load)
+            .filter(|seg| seg.has_flag(SegmentFlag::Write))
+            .find_map(|seg| {
+                let bytes = data.get(seg.offset..seg.offset.checked_add(seg.filesz)?)?;
+                // The header is 16 byte aligned in memory.
+                let skew = (BUILDINFO_ALIGN - seg.vaddr % BUILDINFO_ALIGN) % BUILDINFO_ALIGN;
+                (skew..bytes.len())
+                    .step_by(BUILDINFO_ALIGN)
+                    .find(|&i| bytes[i..].starts_with(BUILDINFO_MAGIC))
+                    .map(|i| &bytes[i..])
+            })
+    }
+}
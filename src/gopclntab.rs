@@ -0,0 +1,400 @@
+use std::{
+    fmt,
+    io::*,
+};
+
+use crate::elf::Elf;
+use crate::segment::{SegmentFlag, SegmentType};
+use crate::utils::*;
+
+const GO12_MAGIC: u32 = 0xfffffffb;
+const GO116_MAGIC: u32 = 0xfffffffa;
+const GO118_MAGIC: u32 = 0xfffffff0;
+const GO120_MAGIC: u32 = 0xfffffff1;
+
+/// The layout of a pclntab, named after the Go release that introduced it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PclntabVersion {
+    Go12,
+    Go116,
+    Go118,
+    Go120,
+}
+
+impl PclntabVersion {
+    pub fn from_magic(magic: u32) -> Option<Self> {
+        match magic {
+            GO12_MAGIC => Some(PclntabVersion::Go12),
+            GO116_MAGIC => Some(PclntabVersion::Go116),
+            GO118_MAGIC => Some(PclntabVersion::Go118),
+            GO120_MAGIC => Some(PclntabVersion::Go120),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PclntabVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PclntabVersion::Go12 => "go1.2",
+            PclntabVersion::Go116 => "go1.16",
+            PclntabVersion::Go118 => "go1.18",
+            PclntabVersion::Go120 => "go1.20",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A function described by the pclntab, a runtime._func.
+#[derive(Debug, Clone)]
+pub struct GoFunc {
+    pub name: String,
+    pub entry: u64,
+    /// Entry of the next function, the end of this one.
+    pub end: u64,
+    pub args: i32,
+    /* Offsets of the pc-value tables */
+    pub pcsp: u32,
+    pub pcfile: u32,
+    pub pcln: u32,
+    /// Index of the function's compilation unit in the cu table, Go 1.16+.
+    pub cu_offset: u32,
+}
+
+impl GoFunc {
+    pub fn size(&self) -> u64 {
+        // An unsorted functab can put the next entry first.
+        self.end.saturating_sub(self.entry)
+    }
+
+    pub fn contains(&self, pc: u64) -> bool {
+        pc >= self.entry && pc < self.end
+    }
+}
+
+impl fmt::Display for GoFunc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}-{:#x} {}", self.entry, self.end, self.name)
+    }
+}
+
+/// The Go runtime's symbol and line table (runtime.pcHeader and the tables
+/// following it), which Go binaries keep even when stripped.
+#[derive(Debug)]
+pub struct GoPclntab<'a> {
+    pub version: PclntabVersion,
+    /// Address of the table.
+    pub vaddr: u64,
+    /// Instruction size quantum, pc deltas are multiples of it.
+    pub quantum: u8,
+    pub ptr_size: u8,
+    pub nfunc: usize,
+    pub nfiles: usize,
+    /// Base of the functab entry offsets, Go 1.18+.
+    pub text_start: u64,
+    data: &'a [u8],
+    funcnametab: usize,
+    cutab: usize,
+    filetab: usize,
+    pctab: usize,
+    functab: usize,
+}
+
+impl<'a> GoPclntab<'a> {
+    /// Parses a pclntab from `data`, which starts with its header and is
+    /// mapped at `vaddr`.
+    pub fn parse(data: &'a [u8], vaddr: u64) -> Result<Self> {
+        let mut r = Reader::new(data);
+        let magic = r.u32()?;
+        let version = PclntabVersion::from_magic(magic)
+            .ok_or_else(|| Error::other(format!("unknown pclntab magic {:#x}", magic)))?;
+
+        let pad = r.u16()?;
+        let quantum = r.u8()?;
+        let ptr_size = r.u8()?;
+        if pad != 0 || !matches!(quantum, 1 | 2 | 4) || !matches!(ptr_size, 4 | 8) {
+            return Err(Error::other("invalid pclntab header"));
+        }
+
+        let mut word = || r.uint(ptr_size as usize).map(|w| w as usize);
+
+        let mut tab = GoPclntab {
+            version,
+            vaddr,
+            quantum,
+            ptr_size,
+            nfunc: word()?,
+            nfiles: 0,
+            text_start: 0,
+            data,
+            funcnametab: 0,
+            cutab: 0,
+            filetab: 0,
+            pctab: 0,
+            functab: 0,
+        };
+
+        match version {
+            PclntabVersion::Go12 => {
+                // The file table offset follows the functab and its end pc.
+                tab.functab = 8 + ptr_size as usize;
+                let filetab = checked(tab.nfunc.checked_mul(2)
+                    .and_then(|n| n.checked_add(1))
+                    .and_then(|n| n.checked_mul(ptr_size as usize))
+                    .and_then(|n| n.checked_add(tab.functab)))?;
+                tab.filetab = Reader::at(data, filetab).u32()? as usize;
+                tab.nfiles = Reader::at(data, tab.filetab).u32()? as usize;
+            },
+            _ => {
+                tab.nfiles = word()?;
+                if version >= PclntabVersion::Go118 {
+                    tab.text_start = word()? as u64;
+                }
+                tab.funcnametab = word()?;
+                tab.cutab = word()?;
+                tab.filetab = word()?;
+                tab.pctab = word()?;
+                tab.functab = word()?;
+            },
+        }
+
+        if tab.functab >= data.len() || tab.filetab >= data.len() {
+            return Err(Error::other("pclntab tables extend past the end of the data"));
+        }
+
+        Ok(tab)
+    }
+
+    /// Size of a functab field: a pointer before Go 1.18, a 32 bit offset
+    /// from `text_start` since.
+    fn functab_field_size(&self) -> usize {
+        match self.version {
+            PclntabVersion::Go12 | PclntabVersion::Go116 => self.ptr_size as usize,
+            _ => 4,
+        }
+    }
+
+    /// Returns the (entry, _func offset) pair at `index` of the functab. The
+    /// entry at `nfunc` is the end of the last function.
+    pub(crate) fn functab_entry(&self, index: usize) -> Result<(u64, usize)> {
+        let size = self.functab_field_size();
+        let offset = checked(index.checked_mul(2 * size)
+            .and_then(|n| n.checked_add(self.functab)))?;
+        let mut r = Reader::at(self.data, offset);
+        let entry = r.uint(size)?;
+        let funcoff = if index < self.nfunc { r.uint(size)? as usize } else { 0 };
+
+        Ok((checked(self.text_start.checked_add(entry))?, funcoff))
+    }
+
+    fn func_at(&self, index: usize) -> Result<GoFunc> {
+        let (entry, funcoff) = self.functab_entry(index)?;
+        let (end, _) = self.functab_entry(index + 1)?;
+
+        // _func offsets are relative to the functab from Go 1.16 on.
+        let base = match self.version {
+            PclntabVersion::Go12 => funcoff,
+            _ => checked(self.functab.checked_add(funcoff))?,
+        };
+
+        let mut r = Reader::at(self.data, base);
+        r.skip(self.functab_field_size())?;
+        let nameoff = r.i32()?;
+        let args = r.i32()?;
+        let _deferreturn = r.u32()?;
+        let pcsp = r.u32()?;
+        let pcfile = r.u32()?;
+        let pcln = r.u32()?;
+        let _npcdata = r.u32()?;
+        let cu_offset = match self.version {
+            PclntabVersion::Go12 => 0,
+            _ => r.u32()?,
+        };
+
+        let name = c_str_at(self.data, checked(self.funcnametab.checked_add_signed(nameoff as isize))?)?;
+
+        Ok(GoFunc { name, entry, end, args, pcsp, pcfile, pcln, cu_offset })
+    }
+
+    pub fn functions(&self) -> Result<Vec<GoFunc>> {
+        (0..self.nfunc).map(|i| self.func_at(i)).collect()
+    }
+
+    /// Finds the function containing `pc`.
+    pub fn find_func(&self, pc: u64) -> Result<Option<GoFunc>> {
+        let (mut lo, mut hi) = (0, self.nfunc);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.functab_entry(mid)?.0 <= pc {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        if lo == 0 {
+            return Ok(None);
+        }
+
+        let func = self.func_at(lo - 1)?;
+        Ok(func.contains(pc).then_some(func))
+    }
+
+    pub fn lookup_func(&self, name: &str) -> Result<Option<GoFunc>> {
+        for i in 0..self.nfunc {
+            let func = self.func_at(i)?;
+            if func.name == name {
+                return Ok(Some(func));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns every source file name in the file table.
+    pub fn files(&self) -> Result<Vec<String>> {
+        match self.version {
+            PclntabVersion::Go12 => (1..self.nfiles).map(|i| self.go12_file(i as i64)).collect(),
+            _ => {
+                let mut r = Reader::at(self.data, self.filetab);
+                let mut files = Vec::with_capacity(self.nfiles);
+                while files.len() < self.nfiles {
+                    files.push(r.c_string()?);
+                }
+                Ok(files)
+            },
+        }
+    }
+
+    fn go12_file(&self, index: i64) -> Result<String> {
+        let offset = usize::try_from(index).ok()
+            .and_then(|i| i.checked_mul(4))
+            .and_then(|i| i.checked_add(self.filetab));
+        let off = Reader::at(self.data, checked(offset)?).u32()?;
+        c_str_at(self.data, off as usize)
+    }
+
+    /// Evaluates the pc-value table at `offset` for `pc`, within `func`.
+    pub fn pcvalue(&self, func: &GoFunc, offset: u32, pc: u64) -> Result<Option<i64>> {
+        if offset == 0 {
+            return Ok(None);
+        }
+
+        let mut r = Reader::at(self.data, checked(self.pctab.checked_add(offset as usize))?);
+        let mut value: i64 = -1;
+        let mut cur = func.entry;
+        let mut first = true;
+
+        loop {
+            let uv = r.uleb128()?;
+            if uv == 0 && !first {
+                return Ok(None);
+            }
+            first = false;
+
+            // Value deltas are zigzag encoded.
+            let delta = match uv & 1 {
+                0 => (uv >> 1) as i64,
+                _ => -((uv >> 1) as i64) - 1,
+            };
+            value = checked(value.checked_add(delta))?;
+            cur = checked(r.uleb128()?.checked_mul(self.quantum as u64)
+                .and_then(|d| cur.checked_add(d)))?;
+
+            if pc < cur {
+                return Ok(Some(value));
+            }
+        }
+    }
+
+    /// Resolves `pc` to its function, source file and line.
+    pub fn pc_to_line(&self, pc: u64) -> Result<Option<(GoFunc, String, i64)>> {
+        let func = match self.find_func(pc)? {
+            Some(f) => f,
+            None => return Ok(None),
+        };
+
+        let (file, line) = match (self.pcvalue(&func, func.pcfile, pc)?, self.pcvalue(&func, func.pcln, pc)?) {
+            (Some(file), Some(line)) => (file, line),
+            _ => return Ok(None),
+        };
+
+        let file = match self.version {
+            PclntabVersion::Go12 => self.go12_file(file)?,
+            _ => {
+                let offset = usize::try_from(file).ok()
+                    .and_then(|f| f.checked_add(func.cu_offset as usize))
+                    .and_then(|i| i.checked_mul(4))
+                    .and_then(|i| i.checked_add(self.cutab));
+                let off = Reader::at(self.data, checked(offset)?).u32()?;
+                c_str_at(self.data, checked(self.filetab.checked_add(off as usize))?)?
+            },
+        };
+
+        Ok(Some((func, file, line)))
+    }
+}
+
+impl Elf {
+    /// Locates and parses the Go pclntab. Stripped binaries have no
+    /// .gopclntab section, the table is then found through the
+    /// runtime.pclntab symbol or the pointer to it in runtime.firstmoduledata.
+    pub fn go_pclntab(&self) -> Result<GoPclntab<'_>> {
+        let vaddr = match self.get_section_by_name(".gopclntab") {
+            Some(s) => s.addr,
+            None => match self.get_symbol("runtime.pclntab") {
+                Some(sym) => sym.value,
+                None => self.find_pclntab()
+                    .ok_or_else(|| Error::other("no Go pclntab found"))?,
+            },
+        };
+
+        // Relocatable objects have sections at address 0.
+        let data = match vaddr {
+            0 => self.get_section_bytes(".gopclntab")?,
+            _ => self.read_vaddr(vaddr, usize::MAX)?,
+        };
+
+        GoPclntab::parse(data, vaddr as u64)
+    }
+
+    /// Scans read-only segments for a pclntab header whose address is also
+    /// stored in a writable segment, as moduledata.pcHeader.
+    fn find_pclntab(&self) -> Option<usize> {
+        let data = self.as_bytes();
+        let mut candidates = Vec::new();
+
+        for seg in self.get_segments_by_type(SegmentType::Load) {
+            if seg.has_flag(SegmentFlag::Write) || seg.has_flag(SegmentFlag::Execute) {
+                continue;
+            }
+            let bytes = data.get(seg.offset..seg.offset.checked_add(seg.filesz)?)?;
+            for (i, chunk) in bytes.chunks_exact(4).enumerate() {
+                let magic = u32::from_le_bytes(chunk.try_into().ok()?);
+                if PclntabVersion::from_magic(magic).is_some()
+                    && GoPclntab::parse(&bytes[i * 4..], 0).is_ok() {
+                    candidates.push(seg.vaddr.checked_add(i * 4)?);
+                }
+            }
+        }
+
+        for seg in self.get_segments_by_type(SegmentType::Load) {
+            if !seg.has_flag(SegmentFlag::Write) {
+                continue;
+            }
+            let bytes = data.get(seg.offset..seg.offset.checked_add(seg.filesz)?)?;
+            for chunk in bytes.chunks_exact(8) {
+                let ptr = u64::from_le_bytes(chunk.try_into().ok()?) as usize;
+                if candidates.contains(&ptr) {
+                    return Some(ptr);
+                }
+            }
+        }
+
+        candidates.first().copied()
+    }
+}
+
+/// Turns an overflowing offset computation into an error.
+fn checked<T>(value: Option<T>) -> Result<T> {
+    value.ok_or_else(|| Error::other("pclntab offset out of range"))
+}
@@ -0,0 +1,616 @@
+use std::{
+    fmt,
+    io::*,
+};
+
+use crate::elf::Elf;
+use crate::gopclntab::PclntabVersion;
+use crate::segment::{SegmentFlag, SegmentType};
+use crate::utils::Reader;
+
+/* _type.tflag */
+const TFLAG_UNCOMMON: u8 = 0x1;
+const TFLAG_EXTRA_STAR: u8 = 0x2;
+const TFLAG_NAMED: u8 = 0x4;
+
+const KIND_MASK: u8 = 0x1f;
+
+/* name flags */
+const NAME_EMBEDDED: u8 = 0x8;
+
+const UNCOMMON_SIZE: u64 = 16;
+const METHOD_SIZE: u64 = 16;
+const IMETHOD_SIZE: u64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, FromPrimitive)]
+pub enum GoKind {
+    Invalid,
+    Bool,
+    Int,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Uint,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Uintptr,
+    Float32,
+    Float64,
+    Complex64,
+    Complex128,
+    Array,
+    Chan,
+    Func,
+    Interface,
+    Map,
+    Pointer,
+    Slice,
+    String,
+    Struct,
+    UnsafePointer,
+}
+
+impl fmt::Display for GoKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            GoKind::Invalid => "invalid",
+            GoKind::Bool => "bool",
+            GoKind::Int => "int",
+            GoKind::Int8 => "int8",
+            GoKind::Int16 => "int16",
+            GoKind::Int32 => "int32",
+            GoKind::Int64 => "int64",
+            GoKind::Uint => "uint",
+            GoKind::Uint8 => "uint8",
+            GoKind::Uint16 => "uint16",
+            GoKind::Uint32 => "uint32",
+            GoKind::Uint64 => "uint64",
+            GoKind::Uintptr => "uintptr",
+            GoKind::Float32 => "float32",
+            GoKind::Float64 => "float64",
+            GoKind::Complex64 => "complex64",
+            GoKind::Complex128 => "complex128",
+            GoKind::Array => "array",
+            GoKind::Chan => "chan",
+            GoKind::Func => "func",
+            GoKind::Interface => "interface",
+            GoKind::Map => "map",
+            GoKind::Pointer => "ptr",
+            GoKind::Slice => "slice",
+            GoKind::String => "string",
+            GoKind::Struct => "struct",
+            GoKind::UnsafePointer => "unsafe.Pointer",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A Go slice header: data pointer, length and capacity.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GoSlice {
+    pub ptr: u64,
+    pub len: u64,
+    pub cap: u64,
+}
+
+/// The parts of runtime.moduledata that describe the layout of the binary.
+/// Fields that do not exist in the binary's Go version are left at 0.
+#[derive(Debug, Clone, Default)]
+pub struct GoModuledata {
+    /// Address of the moduledata itself.
+    pub vaddr: u64,
+    pub pc_header: u64,
+    pub pclntable: GoSlice,
+    pub ftab: GoSlice,
+    pub minpc: u64,
+    pub maxpc: u64,
+    pub text: u64,
+    pub etext: u64,
+    pub noptrdata: u64,
+    pub enoptrdata: u64,
+    pub data: u64,
+    pub edata: u64,
+    pub bss: u64,
+    pub ebss: u64,
+    pub noptrbss: u64,
+    pub enoptrbss: u64,
+    pub end: u64,
+    pub types: u64,
+    pub etypes: u64,
+    pub rodata: u64,
+    pub gofunc: u64,
+    pub textsectmap: GoSlice,
+    pub typelinks: GoSlice,
+    pub itablinks: GoSlice,
+}
+
+impl GoModuledata {
+    /// Parses a moduledata as laid out by Go 1.`minor`.
+    pub fn parse(data: &[u8], vaddr: u64, minor: u32, ptr_size: usize) -> Result<Self> {
+        let mut r = Reader::new(data);
+        let mut md = GoModuledata { vaddr, ..Default::default() };
+
+        let word = |r: &mut Reader| r.uint(ptr_size);
+        let slice = |r: &mut Reader| -> Result<GoSlice> {
+            Ok(GoSlice { ptr: r.uint(ptr_size)?, len: r.uint(ptr_size)?, cap: r.uint(ptr_size)? })
+        };
+
+        if minor >= 16 {
+            md.pc_header = word(&mut r)?;
+            let _funcnametab = slice(&mut r)?;
+            let _cutab = slice(&mut r)?;
+            let _filetab = slice(&mut r)?;
+            let _pctab = slice(&mut r)?;
+            md.pclntable = slice(&mut r)?;
+            md.ftab = slice(&mut r)?;
+        } else {
+            md.pclntable = slice(&mut r)?;
+            md.ftab = slice(&mut r)?;
+            let _filetab = slice(&mut r)?;
+        }
+
+        let _findfunctab = word(&mut r)?;
+        md.minpc = word(&mut r)?;
+        md.maxpc = word(&mut r)?;
+        md.text = word(&mut r)?;
+        md.etext = word(&mut r)?;
+        md.noptrdata = word(&mut r)?;
+        md.enoptrdata = word(&mut r)?;
+        md.data = word(&mut r)?;
+        md.edata = word(&mut r)?;
+        md.bss = word(&mut r)?;
+        md.ebss = word(&mut r)?;
+        md.noptrbss = word(&mut r)?;
+        md.enoptrbss = word(&mut r)?;
+        if minor >= 20 {
+            let _covctrs = word(&mut r)?;
+            let _ecovctrs = word(&mut r)?;
+        }
+        md.end = word(&mut r)?;
+        let _gcdata = word(&mut r)?;
+        let _gcbss = word(&mut r)?;
+        md.types = word(&mut r)?;
+        md.etypes = word(&mut r)?;
+        if minor >= 18 {
+            md.rodata = word(&mut r)?;
+            md.gofunc = word(&mut r)?;
+        }
+        md.textsectmap = slice(&mut r)?;
+        md.typelinks = slice(&mut r)?;
+        md.itablinks = slice(&mut r)?;
+
+        Ok(md)
+    }
+
+    /// Sanity checks that catch a moduledata parsed with the wrong layout.
+    fn is_valid(&self) -> bool {
+        self.minpc <= self.maxpc && self.text <= self.etext
+            && self.types <= self.etypes && self.data <= self.edata
+            && self.typelinks.len <= self.typelinks.cap
+            && self.itablinks.len <= self.itablinks.cap
+    }
+}
+
+/// A struct field.
+#[derive(Debug, Clone)]
+pub struct GoField {
+    pub name: String,
+    /// Address of the field's type.
+    pub typ: u64,
+    pub offset: u64,
+    pub embedded: bool,
+}
+
+/// A method of a named type, or of an interface. Interface methods have no
+/// code addresses.
+#[derive(Debug, Clone)]
+pub struct GoMethod {
+    pub name: String,
+    /// Address of the method's func type, if the linker kept it.
+    pub typ: Option<u64>,
+    /// Code called through interfaces (pointer receiver).
+    pub ifn: Option<u64>,
+    /// Code called for the value receiver.
+    pub tfn: Option<u64>,
+}
+
+/// A runtime._type record and its kind specific data.
+#[derive(Debug, Clone)]
+pub struct GoType {
+    pub addr: u64,
+    pub name: String,
+    pub kind: GoKind,
+    pub size: u64,
+    pub tflag: u8,
+    /// Package path of a named type.
+    pub pkg_path: Option<String>,
+    /// Element type of arrays, channels, maps, pointers and slices.
+    pub elem: Option<u64>,
+    /// Key type of maps.
+    pub key: Option<u64>,
+    /// Length of arrays.
+    pub len: Option<u64>,
+    pub fields: Vec<GoField>,
+    /* Func types */
+    pub params: Vec<u64>,
+    pub results: Vec<u64>,
+    pub variadic: bool,
+    /// Methods required by an interface type.
+    pub interface_methods: Vec<GoMethod>,
+    /// The method set of a named type.
+    pub methods: Vec<GoMethod>,
+}
+
+impl GoType {
+    pub fn is_named(&self) -> bool {
+        self.tflag & TFLAG_NAMED != 0
+    }
+}
+
+impl fmt::Display for GoType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x} {} {} size={}", self.addr, self.kind, self.name, self.size)
+    }
+}
+
+/// An itab, pairing an interface type with a concrete type implementing it.
+#[derive(Debug, Clone, Copy)]
+pub struct GoItab {
+    pub addr: u64,
+    pub interface: u64,
+    pub typ: u64,
+}
+
+/// Reads Go runtime type information through the moduledata.
+#[derive(Debug)]
+pub struct GoTypes<'a> {
+    elf: &'a Elf,
+    pub moduledata: GoModuledata,
+    /// The Go minor version the layouts are chosen by, e.g. 23 for go1.23.
+    pub minor: u32,
+    pub ptr_size: usize,
+}
+
+impl<'a> GoTypes<'a> {
+    fn read(&self, addr: u64, len: usize) -> Result<&'a [u8]> {
+        let data = self.elf.read_vaddr(addr as usize, len)?;
+        if data.len() != len {
+            return Err(Error::other(format!("read of {} bytes at {:#x} is truncated", len, addr)));
+        }
+        Ok(data)
+    }
+
+    fn reader(&self, addr: u64) -> Result<Reader<'a>> {
+        Ok(Reader::new(self.elf.read_vaddr(addr as usize, usize::MAX)?))
+    }
+
+    fn ptr(&self, addr: u64) -> Result<u64> {
+        Reader::new(self.read(addr, self.ptr_size)?).uint(self.ptr_size)
+    }
+
+    /// Size of the common _type header.
+    fn type_size(&self) -> u64 {
+        4 * self.ptr_size as u64 + 16
+    }
+
+    /// Reads a runtime name, the encoding of which changed in Go 1.17.
+    /// Returns the name and its flags.
+    fn name_at(&self, addr: u64) -> Result<(String, u8)> {
+        let mut r = self.reader(addr)?;
+        let flags = r.u8()?;
+        let len = match self.minor >= 17 {
+            true => r.uleb128()? as usize,
+            false => u16::from_be_bytes([r.u8()?, r.u8()?]) as usize,
+        };
+
+        Ok((String::from_utf8_lossy(r.bytes(len)?).into_owned(), flags))
+    }
+
+    /// Resolves a nameOff, relative to the types section.
+    fn name_off(&self, off: i32) -> Result<String> {
+        Ok(self.name_at(self.moduledata.types.wrapping_add_signed(off as i64))?.0)
+    }
+
+    fn type_off(&self, off: i32) -> Option<u64> {
+        (off != -1 && off != 0).then(|| self.moduledata.types.wrapping_add_signed(off as i64))
+    }
+
+    fn text_off(&self, off: i32) -> Option<u64> {
+        (off != -1).then(|| self.moduledata.text.wrapping_add(off as u32 as u64))
+    }
+
+    /// Returns the name of the type at `addr` without decoding the rest.
+    pub fn type_name(&self, addr: u64) -> Result<String> {
+        let p = self.ptr_size as u64;
+        let mut r = Reader::new(self.read(addr, self.type_size() as usize)?);
+        r.skip(2 * p as usize + 4)?;
+        let tflag = r.u8()?;
+        r.skip(3 + 2 * p as usize)?;
+        let str_off = r.i32()?;
+
+        let name = self.name_off(str_off)?;
+        Ok(match tflag & TFLAG_EXTRA_STAR {
+            0 => name,
+            _ => name.get(1..).unwrap_or("").to_string(),
+        })
+    }
+
+    /// Decodes the runtime._type at `addr`.
+    pub fn type_at(&self, addr: u64) -> Result<GoType> {
+        let p = self.ptr_size as u64;
+        let mut r = Reader::new(self.read(addr, self.type_size() as usize)?);
+        let size = r.uint(p as usize)?;
+        let _ptrdata = r.uint(p as usize)?;
+        let _hash = r.u32()?;
+        let tflag = r.u8()?;
+        let _align = r.u8()?;
+        let _field_align = r.u8()?;
+        let kind = r.u8()? & KIND_MASK;
+        r.skip(2 * p as usize)?;
+
+        let kind = num_traits::FromPrimitive::from_u8(kind)
+            .ok_or_else(|| Error::other(format!("invalid kind {} for type at {:#x}", kind, addr)))?;
+
+        let mut typ = GoType {
+            addr,
+            name: self.type_name(addr)?,
+            kind,
+            size,
+            tflag,
+            pkg_path: None,
+            elem: None,
+            key: None,
+            len: None,
+            fields: Vec::new(),
+            params: Vec::new(),
+            results: Vec::new(),
+            variadic: false,
+            interface_methods: Vec::new(),
+            methods: Vec::new(),
+        };
+
+        // Kind specific data directly follows the _type.
+        let extra = checked(addr.checked_add(self.type_size()))?;
+        let extra_size = match kind {
+            GoKind::Array => {
+                typ.elem = Some(self.ptr(extra)?);
+                typ.len = Some(self.ptr(element(extra, 2, p)?)?);
+                3 * p
+            },
+            GoKind::Chan => {
+                typ.elem = Some(self.ptr(extra)?);
+                2 * p
+            },
+            GoKind::Pointer | GoKind::Slice => {
+                typ.elem = Some(self.ptr(extra)?);
+                p
+            },
+            GoKind::Map => {
+                typ.key = Some(self.ptr(extra)?);
+                typ.elem = Some(self.ptr(element(extra, 1, p)?)?);
+                // Swiss tables replaced the bucket based maps in Go 1.24.
+                match self.minor >= 24 {
+                    true => 8 * p,
+                    false => 4 * p + 8,
+                }
+            },
+            GoKind::Func => {
+                let mut r = Reader::new(self.read(extra, 4)?);
+                let in_count = r.u16()? as u64;
+                let out_count = r.u16()?;
+                typ.variadic = out_count & 0x8000 != 0;
+                let out_count = (out_count & 0x7fff) as u64;
+
+                // Parameter types follow the uncommon type, if any.
+                let mut params = element(extra, 1, p)?;
+                if tflag & TFLAG_UNCOMMON != 0 {
+                    params = element(params, 1, UNCOMMON_SIZE)?;
+                }
+                for i in 0..in_count + out_count {
+                    let param = self.ptr(element(params, i, p)?)?;
+                    match i < in_count {
+                        true => typ.params.push(param),
+                        false => typ.results.push(param),
+                    }
+                }
+                p
+            },
+            GoKind::Interface => {
+                let methods = self.read_slice(element(extra, 1, p)?)?;
+                for i in 0..methods.len {
+                    let mut r = Reader::new(self.read(element(methods.ptr, i, IMETHOD_SIZE)?, IMETHOD_SIZE as usize)?);
+                    let name = r.i32()?;
+                    let ityp = r.i32()?;
+                    typ.interface_methods.push(GoMethod {
+                        name: self.name_off(name)?,
+                        typ: self.type_off(ityp),
+                        ifn: None,
+                        tfn: None,
+                    });
+                }
+                4 * p
+            },
+            GoKind::Struct => {
+                let fields = self.read_slice(element(extra, 1, p)?)?;
+                for i in 0..fields.len {
+                    let field = element(fields.ptr, i, 3 * p)?;
+                    let (name, flags) = self.name_at(self.ptr(field)?)?;
+                    let offset = self.ptr(element(field, 2, p)?)?;
+
+                    // Before Go 1.19 the embedded flag was the offset's low bit.
+                    let (offset, embedded) = match self.minor >= 19 {
+                        true => (offset, flags & NAME_EMBEDDED != 0),
+                        false => (offset >> 1, offset & 1 != 0),
+                    };
+
+                    typ.fields.push(GoField { name, typ: self.ptr(element(field, 1, p)?)?, offset, embedded });
+                }
+                4 * p
+            },
+            _ => 0,
+        };
+
+        if tflag & TFLAG_UNCOMMON != 0 {
+            let uncommon = element(extra, 1, extra_size)?;
+            let mut r = Reader::new(self.read(uncommon, UNCOMMON_SIZE as usize)?);
+            let pkg_path = r.i32()?;
+            let mcount = r.u16()? as u64;
+            let _xcount = r.u16()?;
+            let moff = r.u32()? as u64;
+
+            if pkg_path != 0 {
+                typ.pkg_path = Some(self.name_off(pkg_path)?);
+            }
+
+            let methods = element(uncommon, 1, moff)?;
+            for i in 0..mcount {
+                let mut r = Reader::new(self.read(element(methods, i, METHOD_SIZE)?, METHOD_SIZE as usize)?);
+                let name = r.i32()?;
+                let mtyp = r.i32()?;
+                let ifn = r.i32()?;
+                let tfn = r.i32()?;
+                typ.methods.push(GoMethod {
+                    name: self.name_off(name)?,
+                    typ: self.type_off(mtyp),
+                    ifn: self.text_off(ifn),
+                    tfn: self.text_off(tfn),
+                });
+            }
+        }
+
+        Ok(typ)
+    }
+
+    fn read_slice(&self, addr: u64) -> Result<GoSlice> {
+        let p = self.ptr_size;
+        let mut r = Reader::new(self.read(addr, 3 * p)?);
+        Ok(GoSlice { ptr: r.uint(p)?, len: r.uint(p)?, cap: r.uint(p)? })
+    }
+
+    /// Returns the addresses of the types listed in moduledata.typelinks,
+    /// the types the runtime may need to look up by name.
+    pub fn typelinks(&self) -> Result<Vec<u64>> {
+        let links = self.moduledata.typelinks;
+        let len = checked(usize::try_from(links.len).ok().and_then(|n| n.checked_mul(4)))?;
+        let mut r = Reader::new(self.read(links.ptr, len)?);
+        (0..links.len)
+            .map(|_| Ok(self.moduledata.types.wrapping_add_signed(r.i32()? as i64)))
+            .collect()
+    }
+
+    pub fn types(&self) -> Result<Vec<GoType>> {
+        self.typelinks()?
+            .into_iter()
+            .map(|addr| self.type_at(addr))
+            .collect()
+    }
+
+    /// Returns the itabs listed in moduledata.itablinks.
+    pub fn itabs(&self) -> Result<Vec<GoItab>> {
+        let links = self.moduledata.itablinks;
+        let p = self.ptr_size as u64;
+
+        (0..links.len).map(|i| {
+            let addr = self.ptr(element(links.ptr, i, p)?)?;
+            Ok(GoItab {
+                addr,
+                interface: self.ptr(addr)?,
+                typ: self.ptr(element(addr, 1, p)?)?,
+            })
+        }).collect()
+    }
+}
+
+impl Elf {
+    /// Locates runtime.firstmoduledata by looking for a pointer to the
+    /// pclntab in the writable segments.
+    pub fn go_moduledata(&self) -> Result<GoModuledata> {
+        let (minor, ptr_size) = self.go_layout()?;
+        let pclntab = self.go_pclntab()?;
+        let first_func = match pclntab.nfunc {
+            0 => 0,
+            _ => pclntab.functab_entry(0)?.0,
+        };
+
+        if let Some(sym) = self.get_symbol("runtime.firstmoduledata") {
+            return GoModuledata::parse(self.read_vaddr(sym.value, usize::MAX)?,
+                sym.value as u64, minor, ptr_size);
+        }
+
+        let data = self.as_bytes();
+        for seg in self.get_segments_by_type(SegmentType::Load) {
+            if !seg.has_flag(SegmentFlag::Write) {
+                continue;
+            }
+            let bytes = match seg.offset.checked_add(seg.filesz).and_then(|end| data.get(seg.offset..end)) {
+                Some(b) => b,
+                None => continue,
+            };
+
+            // A partial word at the end can not hold the pointer.
+            let end = bytes.len() - bytes.len() % ptr_size;
+            for off in (0..end).step_by(ptr_size) {
+                let mut r = Reader::at(bytes, off);
+                // The pcHeader, or before Go 1.16 the pclntable data pointer,
+                // is the first field.
+                if r.uint(ptr_size)? != pclntab.vaddr {
+                    continue;
+                }
+
+                let md = match GoModuledata::parse(&bytes[off..], seg.vaddr.wrapping_add(off) as u64, minor, ptr_size) {
+                    Ok(md) => md,
+                    Err(_) => continue,
+                };
+                if md.is_valid() && md.minpc == first_func {
+                    return Ok(md);
+                }
+            }
+        }
+
+        Err(Error::other("runtime.moduledata not found"))
+    }
+
+    pub fn go_types(&self) -> Result<GoTypes<'_>> {
+        let (minor, ptr_size) = self.go_layout()?;
+        Ok(GoTypes {
+            elf: self,
+            moduledata: self.go_moduledata()?,
+            minor,
+            ptr_size,
+        })
+    }
+
+    /// Returns the Go minor version and pointer size. The version comes from
+    /// the build info, or is estimated from the pclntab layout.
+    fn go_layout(&self) -> Result<(u32, usize)> {
+        let pclntab = self.go_pclntab()?;
+
+        let minor = self.go_build_info().ok()
+            .and_then(|info| info.go_version.strip_prefix("go1.")
+                .and_then(|v| v.split(|c: char| !c.is_ascii_digit()).next())
+                .and_then(|v| v.parse().ok()));
+
+        let minor = minor.unwrap_or(match pclntab.version {
+            PclntabVersion::Go12 => 15,
+            PclntabVersion::Go116 => 16,
+            PclntabVersion::Go118 => 18,
+            PclntabVersion::Go120 => 20,
+        });
+
+        Ok((minor, pclntab.ptr_size as usize))
+    }
+}
+
+/// Returns the address of the `index`th `size` byte element at `base`,
+/// failing if a bogus count or pointer from the file makes it overflow.
+fn element(base: u64, index: u64, size: u64) -> Result<u64> {
+    checked(index.checked_mul(size).and_then(|off| base.checked_add(off)))
+}
+
+/// Turns an overflowing address computation into an error.
+fn checked<T>(value: Option<T>) -> Result<T> {
+    value.ok_or_else(|| Error::other("Go type data address out of range"))
+}
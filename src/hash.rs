@@ -0,0 +1,159 @@
+use std::io::*;
+
+use crate::utils::Reader;
+
+/// The hash function used by DT_HASH tables.
+pub fn sysv_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 0;
+    for &c in name {
+        h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf0000000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
+
+/// The hash function used by DT_GNU_HASH tables (djb2).
+pub fn gnu_hash(name: &[u8]) -> u32 {
+    name.iter()
+        .fold(5381u32, |h, &c| h.wrapping_mul(33).wrapping_add(c as u32))
+}
+
+/// A DT_HASH table.
+#[derive(Debug, Clone)]
+pub struct SysvHash {
+    pub buckets: Vec<u32>,
+    pub chains: Vec<u32>,
+}
+
+impl SysvHash {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = Reader::new(data);
+        let nbucket = r.u32()? as usize;
+        let nchain = r.u32()? as usize;
+
+        if nbucket.saturating_add(nchain) > r.remaining() / 4 {
+            return Err(Error::other("DT_HASH table extends past the end of the image"));
+        }
+
+        let buckets = (0..nbucket).map(|_| r.u32()).collect::<Result<_>>()?;
+        let chains = (0..nchain).map(|_| r.u32()).collect::<Result<_>>()?;
+
+        Ok(SysvHash { buckets, chains })
+    }
+
+    /// The number of entries in the dynamic symbol table.
+    pub fn symbol_count(&self) -> usize {
+        self.chains.len()
+    }
+
+    /// Returns the indices of the symbols that may be named `name`.
+    pub fn candidates(&self, name: &[u8]) -> Vec<u32> {
+        let mut found = Vec::new();
+        if self.buckets.is_empty() {
+            return found;
+        }
+
+        let hash = sysv_hash(name) as usize;
+        let mut index = self.buckets[hash % self.buckets.len()];
+
+        while index != 0 && (index as usize) < self.chains.len() && found.len() < self.chains.len() {
+            found.push(index);
+            index = self.chains[index as usize];
+        }
+
+        found
+    }
+}
+
+/// A DT_GNU_HASH table.
+#[derive(Debug, Clone)]
+pub struct GnuHash {
+    /// Index of the first symbol reachable through the table.
+    pub symoffset: u32,
+    pub bloom_shift: u32,
+    pub bloom: Vec<u64>,
+    pub buckets: Vec<u32>,
+    /// Hash values of the symbols from `symoffset` on, the low bit marks
+    /// the end of a chain.
+    pub chains: Vec<u32>,
+}
+
+impl GnuHash {
+    /// Parses the table. The number of chain entries is not recorded
+    /// anywhere, it is found by walking to the end of the last chain.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut r = Reader::new(data);
+        let nbuckets = r.u32()? as usize;
+        let symoffset = r.u32()?;
+        let bloom_size = r.u32()? as usize;
+        let bloom_shift = r.u32()?;
+
+        if bloom_size.saturating_mul(2).saturating_add(nbuckets) > r.remaining() / 4 {
+            return Err(Error::other("DT_GNU_HASH table extends past the end of the image"));
+        }
+
+        let bloom = (0..bloom_size).map(|_| r.u64()).collect::<Result<_>>()?;
+        let buckets: Vec<u32> = (0..nbuckets).map(|_| r.u32()).collect::<Result<_>>()?;
+
+        let mut chains = Vec::new();
+        if let Some(&last) = buckets.iter().max() {
+            if last >= symoffset {
+                for _ in symoffset..=last {
+                    chains.push(r.u32()?);
+                }
+                while chains.last().is_some_and(|h| h & 1 == 0) {
+                    chains.push(r.u32()?);
+                }
+            }
+        }
+
+        Ok(GnuHash { symoffset, bloom_shift, bloom, buckets, chains })
+    }
+
+    /// The number of entries in the dynamic symbol table.
+    pub fn symbol_count(&self) -> usize {
+        self.symoffset as usize + self.chains.len()
+    }
+
+    /// Checks the bloom filter, false means `hash` is definitely absent.
+    pub fn may_contain(&self, hash: u32) -> bool {
+        if self.bloom.is_empty() {
+            return false;
+        }
+
+        let word = self.bloom[(hash as usize / 64) % self.bloom.len()];
+        let bit1 = 1u64 << (hash % 64);
+        let bit2 = 1u64 << ((hash >> (self.bloom_shift % 32)) % 64);
+        word & bit1 != 0 && word & bit2 != 0
+    }
+
+    /// Returns the indices of the symbols that may be named `name`.
+    pub fn candidates(&self, name: &[u8]) -> Vec<u32> {
+        let mut found = Vec::new();
+        let hash = gnu_hash(name);
+        if self.buckets.is_empty() || !self.may_contain(hash) {
+            return found;
+        }
+
+        let mut index = self.buckets[hash as usize % self.buckets.len()];
+        if index < self.symoffset {
+            return found;
+        }
+
+        while let Some(&h) = self.chains.get((index - self.symoffset) as usize) {
+            if h | 1 == hash | 1 {
+                found.push(index);
+            }
+            if h & 1 != 0 {
+                break;
+            }
+            index += 1;
+        }
+
+        found
+    }
+}
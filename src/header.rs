@@ -1,7 +1,8 @@
 use std::fmt;
 
 const EI_NIDENT: usize = 16;
-const ELF_MAGIC: u32 = 0x464c457f;
+/// The first bytes of e_ident.
+pub(crate) const ELFMAG: [u8; 4] = [0x7f, b'E', b'L', b'F'];
 
 #[derive(Debug, Default)]
 #[repr(C)]
@@ -0,0 +1,269 @@
+use std::{
+    env,
+    ffi::CStr,
+    io::*,
+    mem,
+    ptr,
+    slice,
+};
+
+use crate::dynamic::{Dyn, Dynamic, EntryType};
+use crate::elf::Elf;
+use crate::header::{Ehdr, ELFMAG};
+use crate::segment::{first_load, header_vaddr, parse_phdrs, Phdr, Segment, SegmentFlag, SegmentType};
+
+/// An ELF object mapped into the current process, as seen by the dynamic
+/// loader. Headers are read straight from memory, nothing is read from disk.
+///
+/// The image is a snapshot: a shared object may be unloaded by dlclose(3)
+/// after the image was taken, which is why reading its contents is unsafe.
+#[derive(Debug)]
+pub struct ProcessImage {
+    name: String,
+    base: usize,
+    bias: usize,
+    header: Ehdr,
+    segments: Vec<Segment>,
+}
+
+impl ProcessImage {
+    /// Returns the image of the running executable, located through
+    /// getauxval(AT_PHDR).
+    pub fn this() -> Result<Self> {
+        let phdr = unsafe { libc::getauxval(libc::AT_PHDR) } as usize;
+        let phnum = unsafe { libc::getauxval(libc::AT_PHNUM) } as usize;
+
+        if phdr == 0 || phnum == 0 {
+            return ProcessImage::loaded()?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::other("failed to locate the executable"));
+        }
+
+        let segments = unsafe { read_phdrs(phdr as *const Phdr, phnum) };
+
+        // PT_PHDR gives the link time address of the program headers,
+        // without it assume they directly follow the ELF header.
+        let bias = match segments.iter().find(|s| s.is_type(SegmentType::Phdr)) {
+            Some(s) => phdr.wrapping_sub(s.vaddr),
+            None => {
+                let first = first_load(&segments)?;
+                phdr.wrapping_sub(first.vaddr + mem::size_of::<Ehdr>())
+            },
+        };
+
+        let name = env::current_exe()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        ProcessImage::new(name, bias, segments)
+    }
+
+    /// Returns the image of the dynamic loader, located through
+    /// getauxval(AT_BASE). Statically linked programs have none.
+    pub fn interpreter() -> Result<Option<Self>> {
+        let base = unsafe { libc::getauxval(libc::AT_BASE) } as usize;
+        if base == 0 {
+            return Ok(None);
+        }
+
+        Ok(ProcessImage::loaded()?
+            .into_iter()
+            .find(|image| image.base() == base))
+    }
+
+    /// Returns every object currently loaded into the process, in the order
+    /// reported by dl_iterate_phdr(3). The executable comes first.
+    pub fn loaded() -> Result<Vec<Self>> {
+        let mut found: Vec<(String, usize, Vec<Segment>)> = Vec::new();
+
+        unsafe extern "C" fn callback(info: *mut libc::dl_phdr_info, _size: libc::size_t,
+            data: *mut libc::c_void) -> libc::c_int {
+            let found = &mut *(data as *mut Vec<(String, usize, Vec<Segment>)>);
+            let info = &*info;
+
+            let name = if info.dlpi_name.is_null() {
+                String::new()
+            } else {
+                CStr::from_ptr(info.dlpi_name).to_string_lossy().into_owned()
+            };
+            let segments = read_phdrs(info.dlpi_phdr as *const Phdr, info.dlpi_phnum as usize);
+
+            found.push((name, info.dlpi_addr as usize, segments));
+            0
+        }
+
+        unsafe {
+            libc::dl_iterate_phdr(Some(callback), &mut found as *mut _ as *mut libc::c_void);
+        }
+
+        let mut images = Vec::with_capacity(found.len());
+        for (i, (mut name, bias, segments)) in found.into_iter().enumerate() {
+            if i == 0 && name.is_empty() {
+                name = env::current_exe()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+            }
+            images.push(ProcessImage::new(name, bias, segments)?);
+        }
+
+        Ok(images)
+    }
+
+    /// Returns the loaded object whose PT_LOAD segments contain `addr`.
+    pub fn containing(addr: usize) -> Result<Option<Self>> {
+        Ok(ProcessImage::loaded()?
+            .into_iter()
+            .find(|image| image.contains(addr)))
+    }
+
+    /// Builds the image of an object from its ELF header at `base`. The
+    /// bias follows from the first PT_LOAD, which maps the ELF header.
+    ///
+    /// # Safety
+    ///
+    /// `base` must point to the ELF header of an object mapped by the loader.
+    pub unsafe fn from_base(name: String, base: usize) -> Result<Self> {
+        let magic = slice::from_raw_parts(base as *const u8, ELFMAG.len());
+        if magic != ELFMAG {
+            return Err(Error::other(
+                    format!("no ELF header at {:#x} for '{}'", base, name)));
+        }
+
+        let header = ptr::read_unaligned(base as *const Ehdr);
+        let phdr = (base + header.e_phoff) as *const Phdr;
+        let segments = read_phdrs(phdr, header.e_phnum as usize);
+
+        let bias = base.wrapping_sub(header_vaddr(&segments)?);
+
+        Ok(ProcessImage { name, base, bias, header, segments })
+    }
+
+    fn new(name: String, bias: usize, segments: Vec<Segment>) -> Result<Self> {
+        let base = bias.wrapping_add(header_vaddr(&segments)?);
+
+        let magic = unsafe { slice::from_raw_parts(base as *const u8, ELFMAG.len()) };
+        if magic != ELFMAG {
+            return Err(Error::other(
+                    format!("no ELF header at {:#x} for '{}'", base, name)));
+        }
+        let header = unsafe { ptr::read_unaligned(base as *const Ehdr) };
+
+        Ok(ProcessImage { name, base, bias, header, segments })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Address of the ELF header in memory.
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Difference between the runtime and the link time addresses.
+    pub fn bias(&self) -> usize {
+        self.bias
+    }
+
+    pub fn header(&self) -> &Ehdr {
+        &self.header
+    }
+
+    pub fn iter_segments(&self) -> impl Iterator<Item = &Segment> {
+        self.segments.iter()
+    }
+
+    /// Returns true if `addr` lies within one of the PT_LOAD segments.
+    pub fn contains(&self, addr: usize) -> bool {
+        self.segments.iter()
+            .filter(|s| s.is_type(SegmentType::Load))
+            .any(|s| s.contains_vaddr(addr.wrapping_sub(self.bias)))
+    }
+
+    /// Returns the runtime address of the link time address `vaddr`.
+    pub fn runtime_addr(&self, vaddr: usize) -> usize {
+        vaddr.wrapping_add(self.bias)
+    }
+
+    /// Reads the dynamic section in place. Unlike the file contents, the
+    /// loader may have relocated the address entries.
+    ///
+    /// # Safety
+    ///
+    /// The object must still be loaded, i.e. not have been unloaded by
+    /// dlclose(3) since the image was taken.
+    pub unsafe fn dynamic(&self) -> Result<Vec<Dynamic>> {
+        let seg = match self.segments.iter().find(|s| s.is_type(SegmentType::Dynamic)) {
+            Some(s) => s,
+            None => return Ok(Vec::new()),
+        };
+
+        let entries = slice::from_raw_parts(self.runtime_addr(seg.vaddr) as *const Dyn,
+            seg.memsz / mem::size_of::<Dyn>());
+
+        let mut dynamic = Vec::new();
+        for entry in entries {
+            dynamic.push(Dynamic::from_dyn(entry)?);
+            if entry.d_tag == EntryType::Null as usize {
+                break;
+            }
+        }
+
+        Ok(dynamic)
+    }
+
+    /// Returns the runtime address of the dynamic section.
+    pub fn dynamic_addr(&self) -> Option<usize> {
+        self.segments.iter()
+            .find(|s| s.is_type(SegmentType::Dynamic))
+            .map(|s| self.runtime_addr(s.vaddr))
+    }
+
+    /// Reassembles the file backed parts of the image: every readable PT_LOAD
+    /// is copied from memory to its file offset. Writable segments hold their
+    /// current contents, relocations included.
+    ///
+    /// # Safety
+    ///
+    /// The object must still be loaded, as for `dynamic`.
+    pub unsafe fn read_image(&self) -> Vec<u8> {
+        let loads = || self.segments.iter()
+            .filter(|s| s.is_type(SegmentType::Load) && s.has_flag(SegmentFlag::Read));
+
+        let len = loads()
+            .map(|s| s.offset + s.filesz)
+            .max()
+            .unwrap_or(0);
+        let mut data = vec![0; len];
+
+        for seg in loads() {
+            let src = slice::from_raw_parts(self.runtime_addr(seg.vaddr) as *const u8, seg.filesz);
+            data[seg.offset..seg.offset + seg.filesz].copy_from_slice(src);
+        }
+
+        data
+    }
+
+    /// Parses the image as an `Elf`. Only what is mapped is available, i.e.
+    /// headers, segments and the dynamic section.
+    ///
+    /// # Safety
+    ///
+    /// The object must still be loaded, as for `dynamic`.
+    pub unsafe fn to_elf(&self) -> Result<Elf> {
+        Elf::from_memory_image(self.read_image(), self.bias)
+    }
+}
+
+unsafe fn read_phdrs(phdr: *const Phdr, phnum: usize) -> Vec<Segment> {
+    parse_phdrs(slice::from_raw_parts(phdr as *const u8, phnum * mem::size_of::<Phdr>()))
+}
+
+impl Elf {
+    /// Parses the running executable from its in-memory image.
+    pub fn current_executable() -> Result<Self> {
+        // The executable is never unloaded.
+        unsafe { ProcessImage::this()?.to_elf() }
+    }
+}
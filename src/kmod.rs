@@ -0,0 +1,538 @@
+use std::{
+    fmt,
+    io::*,
+};
+
+use crate::elf::Elf;
+use crate::header::ElfType;
+use crate::relocated::RelocatedImage;
+use crate::relocation::Relocation;
+use crate::section::SectionType;
+use crate::utils::{c_str_at, Reader};
+
+pub const MODULE_SIG_MAGIC: &[u8] = b"~Module signature appended~\n";
+
+/* struct module_signature */
+const MODULE_SIG_SIZE: usize = 12;
+const PKEY_ID_PKCS7: u8 = 2;
+
+/* struct modversion_info, 64 bit */
+const MODVERSION_SIZE: usize = 64;
+
+/* struct module: state, list, name[MODULE_NAME_LEN] */
+const MODULE_NAME_OFFSET: usize = 24;
+const MODULE_NAME_LEN: usize = 56;
+
+/* struct kernel_symbol with PREL32 relocations, as on x86_64 and arm64 */
+const KERNEL_SYMBOL_NS_OFFSET: usize = 8;
+const KSYMTAB_PREFIX: &str = "__ksymtab_";
+
+/* PKCS#7 */
+const OID_SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CONTEXT_0: u8 = 0xa0;
+const TAG_SUBJECT_KEY_ID: u8 = 0x80;
+
+/// A `parm=` entry of .modinfo, merged with its `parmtype=`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModuleParam {
+    pub name: String,
+    pub typ: Option<String>,
+    pub description: Option<String>,
+}
+
+/// The key=value strings of .modinfo, in file order.
+#[derive(Debug, Clone, Default)]
+pub struct ModInfo {
+    pub entries: Vec<(String, String)>,
+}
+
+impl ModInfo {
+    pub fn parse(data: &[u8]) -> Self {
+        let entries = data.split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .filter_map(|s| {
+                let s = String::from_utf8_lossy(s);
+                let (key, value) = s.split_once('=')?;
+                Some((key.to_string(), value.to_string()))
+            })
+            .collect();
+        ModInfo { entries }
+    }
+
+    /// Returns the first value of `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries.iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get("name")
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.get("license")
+    }
+
+    pub fn vermagic(&self) -> Option<&str> {
+        self.get("vermagic")
+    }
+
+    /// Modules that have to be loaded first.
+    pub fn depends(&self) -> Vec<&str> {
+        self.get("depends")
+            .map(|d| d.split(',').filter(|d| !d.is_empty()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn aliases(&self) -> Vec<&str> {
+        self.get_all("alias").collect()
+    }
+
+    pub fn parameters(&self) -> Vec<ModuleParam> {
+        let mut params: Vec<ModuleParam> = Vec::new();
+        for (key, value) in &self.entries {
+            if key != "parm" && key != "parmtype" {
+                continue;
+            }
+            let (name, value) = value.split_once(':').unwrap_or((value, ""));
+            let index = match params.iter().position(|p| p.name == name) {
+                Some(i) => i,
+                None => {
+                    params.push(ModuleParam { name: name.to_string(), ..Default::default() });
+                    params.len() - 1
+                },
+            };
+            match key.as_str() {
+                "parm" => params[index].description = Some(value.to_string()),
+                _ => params[index].typ = Some(value.to_string()),
+            }
+        }
+        params
+    }
+}
+
+/// A symbol the module imports, with the CRC it was built against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModVersion {
+    pub name: String,
+    pub crc: u32,
+}
+
+impl fmt::Display for ModVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#010x} {}", self.crc, self.name)
+    }
+}
+
+/// The parts of `.gnu.linkonce.this_module` that do not depend on the
+/// kernel configuration.
+#[derive(Debug, Clone, Default)]
+pub struct ThisModule {
+    pub name: String,
+    /// The functions init_module and cleanup_module alias.
+    pub init: Option<String>,
+    pub exit: Option<String>,
+}
+
+/// A symbol exported through __ksymtab or __ksymtab_gpl.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportedSymbol {
+    pub name: String,
+    pub gpl: bool,
+    pub namespace: Option<String>,
+}
+
+/// The signature appended by scripts/sign-file.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleSignature {
+    /// Length of the module without the signature.
+    pub module_len: usize,
+    pub id_type: u8,
+    pub sig_len: usize,
+    /// Issuer of the signing certificate, e.g. [("CN", "...")].
+    pub issuer: Vec<(String, String)>,
+    /// Certificate serial number, or subject key identifier.
+    pub key_id: Vec<u8>,
+    pub hash_algorithm: Option<String>,
+    pub signature_algorithm: Option<String>,
+}
+
+impl ModuleSignature {
+    /// Parses the trailer of a signed module file, None if it is unsigned.
+    pub fn parse(file: &[u8]) -> Result<Option<Self>> {
+        let end = match file.strip_suffix(MODULE_SIG_MAGIC) {
+            Some(data) => data.len(),
+            None => return Ok(None),
+        };
+        let info = end.checked_sub(MODULE_SIG_SIZE)
+            .ok_or_else(|| Error::other("truncated module signature"))?;
+
+        let mut r = Reader::at(file, info);
+        let _algo = r.u8()?;
+        let _hash = r.u8()?;
+        let id_type = r.u8()?;
+        let signer_len = r.u8()? as usize;
+        let key_id_len = r.u8()? as usize;
+        r.skip(3)?;
+        let sig_len = u32::from_be_bytes(r.bytes(4)?.try_into().unwrap()) as usize;
+
+        let module_len = info.checked_sub(sig_len + signer_len + key_id_len)
+            .ok_or_else(|| Error::other("module signature longer than the file"))?;
+        let mut sig = ModuleSignature { module_len, id_type, sig_len, ..Default::default() };
+
+        if id_type == PKEY_ID_PKCS7 {
+            sig.parse_pkcs7(&file[info - sig_len..info])?;
+        }
+        Ok(Some(sig))
+    }
+
+    /// Returns the common name of the issuer, which is what modinfo shows
+    /// as the signer.
+    pub fn signer(&self) -> Option<&str> {
+        self.issuer.iter()
+            .find(|(k, _)| k == "CN")
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn key_id_hex(&self) -> String {
+        self.key_id.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join(":")
+    }
+
+    /// Picks the first SignerInfo out of a ContentInfo holding SignedData.
+    fn parse_pkcs7(&mut self, data: &[u8]) -> Result<()> {
+        let mut content_info = Der::new(Der::new(data).expect(TAG_SEQUENCE)?);
+        if oid_to_string(content_info.expect(TAG_OID)?) != OID_SIGNED_DATA {
+            return Err(Error::other("module signature is not PKCS#7 signed data"));
+        }
+        let mut signed_data = Der::new(Der::new(content_info.expect(TAG_CONTEXT_0)?).expect(TAG_SEQUENCE)?);
+        signed_data.expect(TAG_INTEGER)?;
+        signed_data.expect(TAG_SET)?;
+        signed_data.expect(TAG_SEQUENCE)?;
+
+        // Skip the optional certificates and CRLs.
+        let signer_infos = loop {
+            match signed_data.next()? {
+                (TAG_SET, body) => break body,
+                _ => continue,
+            }
+        };
+        let mut signer_info = Der::new(Der::new(signer_infos).expect(TAG_SEQUENCE)?);
+        signer_info.expect(TAG_INTEGER)?;
+
+        match signer_info.next()? {
+            (TAG_SEQUENCE, body) => {
+                let mut issuer_and_serial = Der::new(body);
+                self.issuer = parse_name(issuer_and_serial.expect(TAG_SEQUENCE)?)?;
+                self.key_id = issuer_and_serial.expect(TAG_INTEGER)?.to_vec();
+            },
+            (TAG_SUBJECT_KEY_ID, body) => self.key_id = body.to_vec(),
+            (tag, _) => return Err(Error::other(format!("unexpected signer identifier tag {:#x}", tag))),
+        }
+
+        self.hash_algorithm = Some(algorithm_name(signer_info.expect(TAG_SEQUENCE)?)?);
+        let algorithm = match signer_info.next()? {
+            // Authenticated attributes.
+            (TAG_CONTEXT_0, _) => signer_info.expect(TAG_SEQUENCE)?,
+            (TAG_SEQUENCE, body) => body,
+            (tag, _) => return Err(Error::other(format!("unexpected SignerInfo tag {:#x}", tag))),
+        };
+        self.signature_algorithm = Some(algorithm_name(algorithm)?);
+        signer_info.expect(TAG_OCTET_STRING)?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ModuleSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "signer: {}, key: {}, hash: {}",
+            self.signer().unwrap_or("?"),
+            self.key_id_hex(),
+            self.hash_algorithm.as_deref().unwrap_or("?"))
+    }
+}
+
+/// Everything known about a loadable kernel module.
+#[derive(Debug, Clone, Default)]
+pub struct KernelModule {
+    pub modinfo: ModInfo,
+    pub versions: Vec<ModVersion>,
+    pub this_module: Option<ThisModule>,
+    pub exports: Vec<ExportedSymbol>,
+    pub signature: Option<ModuleSignature>,
+}
+
+impl KernelModule {
+    /// The module name, from .modinfo or else `this_module`.
+    pub fn name(&self) -> Option<&str> {
+        self.modinfo.name()
+            .or(self.this_module.as_ref().map(|m| m.name.as_str()))
+    }
+}
+
+impl Elf {
+    /// Inspects a kernel module, None if this is not a relocatable object
+    /// with a .modinfo or .gnu.linkonce.this_module section.
+    pub fn kernel_module(&self) -> Result<Option<KernelModule>> {
+        if self.header().e_type != ElfType::Reloc as u16 {
+            return Ok(None);
+        }
+        let modinfo = self.get_section_by_name(".modinfo");
+        if modinfo.is_none() && self.get_section_by_name(".gnu.linkonce.this_module").is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(KernelModule {
+            modinfo: match modinfo {
+                Some(s) => ModInfo::parse(self.get_section_contents(s)?),
+                None => ModInfo::default(),
+            },
+            versions: self.module_versions()?,
+            this_module: self.this_module()?,
+            exports: self.module_exports()?,
+            signature: self.module_signature()?,
+        }))
+    }
+
+    /// Returns the CRCs of imported symbols from __versions, or from the
+    /// __version_ext_crcs and __version_ext_names tables of newer kernels.
+    pub fn module_versions(&self) -> Result<Vec<ModVersion>> {
+        let mut versions = Vec::new();
+
+        if let Some(s) = self.get_section_by_name("__versions") {
+            let data = self.get_section_contents(s)?;
+            for entry in data.chunks_exact(MODVERSION_SIZE) {
+                let crc = Reader::new(entry).u64()? as u32;
+                let name = &entry[8..];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+                versions.push(ModVersion { name: String::from_utf8_lossy(name).into_owned(), crc });
+            }
+        }
+
+        let crcs = self.get_section_by_name("__version_ext_crcs");
+        let names = self.get_section_by_name("__version_ext_names");
+        if let (Some(crcs), Some(names)) = (crcs, names) {
+            let mut crcs = Reader::new(self.get_section_contents(crcs)?);
+            let names = self.get_section_contents(names)?;
+            for name in names.split(|&b| b == 0).filter(|n| !n.is_empty()) {
+                if crcs.remaining() < 4 {
+                    break;
+                }
+                versions.push(ModVersion { name: String::from_utf8_lossy(name).into_owned(), crc: crcs.u32()? });
+            }
+        }
+
+        Ok(versions)
+    }
+
+    /// Reads the module name from .gnu.linkonce.this_module and resolves the
+    /// init and exit functions from its relocations.
+    pub fn this_module(&self) -> Result<Option<ThisModule>> {
+        let section = match self.get_section_by_name(".gnu.linkonce.this_module") {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+        let data = self.get_section_contents(section)?;
+        let name = data.get(MODULE_NAME_OFFSET..)
+            .map(|n| &n[..n.len().min(MODULE_NAME_LEN)])
+            .ok_or_else(|| Error::other(".gnu.linkonce.this_module is too small"))?;
+        let mut module = ThisModule {
+            name: c_str_at(name, 0).unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let index = self.section_index(&section.name);
+        for rela in self.get_section_by_type(SectionType::Rela) {
+            if Some(rela.info as usize) != index {
+                continue;
+            }
+            let symbols = self.object_symbols(rela.link as usize)?;
+            for reloc in Relocation::parse_rela(self.get_section_contents(rela)?)? {
+                let sym = match symbols.get(reloc.symbol as usize) {
+                    Some(s) => s,
+                    None => continue,
+                };
+                // init_module and cleanup_module alias the real functions.
+                let target = symbols.iter()
+                    .find(|s| s.shndx == sym.shndx && s.value == sym.value && s.size == sym.size
+                        && s.name != sym.name && !s.name.is_empty())
+                    .unwrap_or(sym);
+                match sym.name.as_str() {
+                    "init_module" => module.init = Some(target.name.clone()),
+                    "cleanup_module" => module.exit = Some(target.name.clone()),
+                    _ => {},
+                }
+            }
+        }
+
+        Ok(Some(module))
+    }
+
+    /// Returns the symbols exported from __ksymtab and __ksymtab_gpl.
+    pub fn module_exports(&self) -> Result<Vec<ExportedSymbol>> {
+        let sections: Vec<_> = self.iter_sections().collect();
+        let is_ksymtab = |shndx: u16| sections.get(shndx as usize)
+            .is_some_and(|s| s.name == "__ksymtab" || s.name == "__ksymtab_gpl");
+
+        // The namespace is only reachable through the relocations.
+        let image = self.relocated_image(0).ok();
+        let mut exports: Vec<ExportedSymbol> = self.iter_symbols()
+            .filter(|s| is_ksymtab(s.shndx))
+            .filter_map(|s| Some((s, s.name.strip_prefix(KSYMTAB_PREFIX)?)))
+            .map(|(s, name)| ExportedSymbol {
+                name: name.to_string(),
+                gpl: sections[s.shndx as usize].name.ends_with("_gpl"),
+                namespace: image.as_ref().and_then(|i| self.export_namespace(i, s.shndx, s.value)),
+            })
+            .collect();
+        exports.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(exports)
+    }
+
+    /// Parses the appended module signature, None if there is none.
+    pub fn module_signature(&self) -> Result<Option<ModuleSignature>> {
+        ModuleSignature::parse(self.as_bytes())
+    }
+
+    /// Follows the namespace_offset field of a kernel_symbol entry into
+    /// __ksymtab_strings.
+    fn export_namespace(&self, image: &RelocatedImage, shndx: u16, value: usize) -> Option<String> {
+        let field = image.section_addrs.get(shndx as usize).copied().flatten()?
+            .checked_add(value)?
+            .checked_add(KERNEL_SYMBOL_NS_OFFSET)?;
+        let offset = i32::from_le_bytes(image.read(field, 4)?.try_into().ok()?);
+
+        let strings = self.section_index("__ksymtab_strings")?;
+        let start = image.section_addrs.get(strings).copied().flatten()?;
+        let size = self.iter_sections().nth(strings)?.size;
+        let addr = field.checked_add_signed(offset as isize)?;
+        if addr < start || addr - start >= size {
+            return None;
+        }
+
+        let namespace = c_str_at(image.read(start, size)?, addr - start).ok()?;
+        Some(namespace).filter(|n| !n.is_empty())
+    }
+}
+
+/// A cursor over DER encoded TLVs.
+struct Der<'a> {
+    r: Reader<'a>,
+}
+
+impl<'a> Der<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Der { r: Reader::new(data) }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.r.is_empty()
+    }
+
+    fn next(&mut self) -> Result<(u8, &'a [u8])> {
+        let tag = self.r.u8()?;
+        let len = match self.r.u8()? {
+            l if l & 0x80 == 0 => l as usize,
+            l => {
+                let n = (l & 0x7f) as usize;
+                if n == 0 || n > 4 {
+                    return Err(Error::other(format!("unsupported DER length of {} bytes", n)));
+                }
+                self.r.bytes(n)?.iter().fold(0, |len, &b| (len << 8) | b as usize)
+            },
+        };
+        Ok((tag, self.r.bytes(len)?))
+    }
+
+    fn expect(&mut self, tag: u8) -> Result<&'a [u8]> {
+        match self.next()? {
+            (t, body) if t == tag => Ok(body),
+            (t, _) => Err(Error::other(format!("expected DER tag {:#x}, found {:#x}", tag, t))),
+        }
+    }
+}
+
+/// Renders an X.501 Name as (attribute, value) pairs.
+fn parse_name(data: &[u8]) -> Result<Vec<(String, String)>> {
+    let mut name = Vec::new();
+    let mut rdns = Der::new(data);
+    while !rdns.is_empty() {
+        let mut rdn = Der::new(rdns.expect(TAG_SET)?);
+        while !rdn.is_empty() {
+            let mut attr = Der::new(rdn.expect(TAG_SEQUENCE)?);
+            let oid = oid_to_string(attr.expect(TAG_OID)?);
+            let (_, value) = attr.next()?;
+            let key = match oid.as_str() {
+                "2.5.4.3" => "CN",
+                "2.5.4.6" => "C",
+                "2.5.4.7" => "L",
+                "2.5.4.8" => "ST",
+                "2.5.4.10" => "O",
+                "2.5.4.11" => "OU",
+                "1.2.840.113549.1.9.1" => "emailAddress",
+                _ => &oid,
+            };
+            name.push((key.to_string(), String::from_utf8_lossy(value).into_owned()));
+        }
+    }
+    Ok(name)
+}
+
+/// Names the OID of an AlgorithmIdentifier.
+fn algorithm_name(data: &[u8]) -> Result<String> {
+    let oid = oid_to_string(Der::new(data).expect(TAG_OID)?);
+    let name = match oid.as_str() {
+        "1.3.14.3.2.26" => "sha1",
+        "2.16.840.1.101.3.4.2.1" => "sha256",
+        "2.16.840.1.101.3.4.2.2" => "sha384",
+        "2.16.840.1.101.3.4.2.3" => "sha512",
+        "2.16.840.1.101.3.4.2.4" => "sha224",
+        "2.16.840.1.101.3.4.2.8" => "sha3-256",
+        "2.16.840.1.101.3.4.2.9" => "sha3-384",
+        "2.16.840.1.101.3.4.2.10" => "sha3-512",
+        "1.2.840.113549.1.1.1" => "rsaEncryption",
+        "1.2.840.113549.1.1.5" => "sha1WithRSAEncryption",
+        "1.2.840.113549.1.1.11" => "sha256WithRSAEncryption",
+        "1.2.840.113549.1.1.12" => "sha384WithRSAEncryption",
+        "1.2.840.113549.1.1.13" => "sha512WithRSAEncryption",
+        "1.2.840.10045.4.3.2" => "ecdsa-with-SHA256",
+        "1.2.840.10045.4.3.3" => "ecdsa-with-SHA384",
+        "1.2.840.10045.4.3.4" => "ecdsa-with-SHA512",
+        "2.16.840.1.101.3.4.3.17" => "ML-DSA-44",
+        "2.16.840.1.101.3.4.3.18" => "ML-DSA-65",
+        "2.16.840.1.101.3.4.3.19" => "ML-DSA-87",
+        _ => return Ok(oid),
+    };
+    Ok(name.to_string())
+}
+
+fn oid_to_string(data: &[u8]) -> String {
+    let mut arcs: Vec<u64> = Vec::new();
+    let mut value: u64 = 0;
+    for &b in data {
+        value = (value << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (value / 40).min(2);
+                arcs.push(first);
+                arcs.push(value - first * 40);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        }
+    }
+    arcs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(".")
+}
@@ -0,0 +1,560 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    fmt::Write as _,
+    io::*,
+};
+
+use crate::dwarf::*;
+use crate::utils::Reader;
+
+pub const DW_TAG_ARRAY_TYPE: u64 =          0x01;
+pub const DW_TAG_CLASS_TYPE: u64 =          0x02;
+pub const DW_TAG_ENUMERATION_TYPE: u64 =    0x04;
+pub const DW_TAG_MEMBER: u64 =              0x0d;
+pub const DW_TAG_POINTER_TYPE: u64 =        0x0f;
+pub const DW_TAG_REFERENCE_TYPE: u64 =      0x10;
+pub const DW_TAG_STRUCTURE_TYPE: u64 =      0x13;
+pub const DW_TAG_SUBROUTINE_TYPE: u64 =     0x15;
+pub const DW_TAG_TYPEDEF: u64 =             0x16;
+pub const DW_TAG_UNION_TYPE: u64 =          0x17;
+pub const DW_TAG_INHERITANCE: u64 =         0x1c;
+pub const DW_TAG_PTR_TO_MEMBER_TYPE: u64 =  0x1f;
+pub const DW_TAG_SUBRANGE_TYPE: u64 =       0x21;
+pub const DW_TAG_BASE_TYPE: u64 =           0x24;
+pub const DW_TAG_CONST_TYPE: u64 =          0x26;
+pub const DW_TAG_VOLATILE_TYPE: u64 =       0x35;
+pub const DW_TAG_RESTRICT_TYPE: u64 =       0x37;
+pub const DW_TAG_UNSPECIFIED_TYPE: u64 =    0x3b;
+pub const DW_TAG_RVALUE_REFERENCE_TYPE: u64 = 0x42;
+pub const DW_TAG_ATOMIC_TYPE: u64 =         0x47;
+
+pub const DW_AT_BYTE_SIZE: u64 =            0x0b;
+pub const DW_AT_BIT_OFFSET: u64 =           0x0c;
+pub const DW_AT_BIT_SIZE: u64 =             0x0d;
+pub const DW_AT_LOWER_BOUND: u64 =          0x22;
+pub const DW_AT_UPPER_BOUND: u64 =          0x2f;
+pub const DW_AT_COUNT: u64 =                0x37;
+pub const DW_AT_DATA_MEMBER_LOCATION: u64 = 0x38;
+pub const DW_AT_DECLARATION: u64 =          0x3c;
+pub const DW_AT_TYPE: u64 =                 0x49;
+pub const DW_AT_DATA_BIT_OFFSET: u64 =      0x6b;
+
+const DW_OP_PLUS_UCONST: u8 = 0x23;
+
+pub const DEFAULT_CACHELINE_SIZE: usize = 64;
+
+/// A single member of a `StructLayout`. Offsets and sizes are in bytes,
+/// except for bitfields where `bit_offset`/`bit_size` are relative to the
+/// storage unit starting at `offset`.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    pub type_name: String,
+    pub offset: usize,
+    pub size: usize,
+    pub bit_offset: Option<usize>,
+    pub bit_size: Option<usize>,
+    /// Set for base classes (`DW_TAG_inheritance`).
+    pub inherited: bool,
+}
+
+impl Member {
+    pub fn is_bitfield(&self) -> bool {
+        self.bit_size.is_some()
+    }
+
+    // Saturating, the offsets and sizes come straight from the DWARF.
+    fn start_bits(&self) -> usize {
+        self.offset.saturating_mul(8).saturating_add(self.bit_offset.unwrap_or(0))
+    }
+
+    fn end_bits(&self) -> usize {
+        match self.bit_size {
+            Some(bits) => self.start_bits().saturating_add(bits),
+            None => self.offset.saturating_add(self.size).saturating_mul(8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StructKind {
+    Struct,
+    Class,
+    Union,
+}
+
+impl StructKind {
+    fn from_tag(tag: u64) -> Self {
+        match tag {
+            DW_TAG_CLASS_TYPE => StructKind::Class,
+            DW_TAG_UNION_TYPE => StructKind::Union,
+            _ => StructKind::Struct,
+        }
+    }
+}
+
+impl fmt::Display for StructKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            StructKind::Struct => "struct",
+            StructKind::Class => "class",
+            StructKind::Union => "union",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The memory layout of a structure, class or union.
+#[derive(Debug, Clone)]
+pub struct StructLayout {
+    pub kind: StructKind,
+    pub name: String,
+    pub size: usize,
+    pub members: Vec<Member>,
+}
+
+/// A gap between two members or the end of the structure.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hole {
+    /// Index of the member following the hole, `members.len()` for trailing padding.
+    pub before: usize,
+    pub bytes: usize,
+    pub bits: usize,
+}
+
+impl StructLayout {
+    pub fn is_union(&self) -> bool {
+        self.kind == StructKind::Union
+    }
+
+    /// Returns the holes between members. Trailing padding is reported
+    /// separately by `padding()`.
+    pub fn holes(&self) -> Vec<Hole> {
+        let mut holes = Vec::new();
+        if self.is_union() {
+            return holes;
+        }
+
+        let mut end = 0;
+        for (i, m) in self.members.iter().enumerate() {
+            let start = m.start_bits();
+            if start > end {
+                let gap = start - end;
+                holes.push(Hole { before: i, bytes: gap / 8, bits: gap % 8 });
+            }
+            end = end.max(m.end_bits());
+        }
+
+        holes
+    }
+
+    /// Returns the number of unused bytes at the end of the structure.
+    pub fn padding(&self) -> usize {
+        let end = self.members.iter()
+            .map(|m| m.end_bits())
+            .max()
+            .unwrap_or(0);
+
+        self.size.saturating_sub(end.div_ceil(8))
+    }
+
+    /// Sum of the sizes of all non-bitfield members plus the bytes covered
+    /// by bitfields.
+    pub fn sum_members(&self) -> usize {
+        let bytes: usize = self.members.iter()
+            .filter(|m| !m.is_bitfield())
+            .map(|m| m.size)
+            .sum();
+        let bits: usize = self.members.iter()
+            .filter_map(|m| m.bit_size)
+            .sum();
+
+        bytes + bits / 8
+    }
+
+    /// Renders the layout in the style of pahole, marking cache line
+    /// boundaries every `cacheline` bytes.
+    pub fn render(&self, cacheline: usize) -> String {
+        let cacheline = cacheline.max(1);
+        let mut out = String::new();
+        let holes = self.holes();
+        let mut next_boundary = cacheline;
+
+        let _ = writeln!(out, "{} {} {{", self.kind, self.name);
+
+        for (i, m) in self.members.iter().enumerate() {
+            if let Some(h) = holes.iter().find(|h| h.before == i) {
+                if h.bytes > 0 {
+                    let _ = writeln!(out, "\n\t/* XXX {} byte{} hole, try to pack */\n",
+                        h.bytes, if h.bytes == 1 { "" } else { "s" });
+                }
+                if h.bits > 0 {
+                    let _ = writeln!(out, "\n\t/* XXX {} bit{} hole, try to pack */\n",
+                        h.bits, if h.bits == 1 { "" } else { "s" });
+                }
+            }
+
+            while !self.is_union() && m.offset >= next_boundary {
+                let _ = writeln!(out, "\t/* --- cacheline {} boundary ({} bytes) --- */",
+                    next_boundary / cacheline, next_boundary);
+                next_boundary += cacheline;
+            }
+
+            let decl = if m.inherited {
+                format!("{} <ancestor>;", m.type_name)
+            } else {
+                match m.bit_size {
+                    Some(bits) => format!("{} {}:{};", m.type_name, m.name, bits),
+                    None => format!("{} {};", m.type_name, m.name),
+                }
+            };
+
+            let location = match m.bit_offset {
+                Some(bo) => format!("/* {:5}:{:2} {:4} */", m.offset, bo, m.size),
+                None => format!("/* {:5} {:5} */", m.offset, m.size),
+            };
+
+            let _ = writeln!(out, "\t{:<48} {}", decl, location);
+        }
+
+        let padding = self.padding();
+        let cachelines = self.size.div_ceil(cacheline);
+        let sum_holes: usize = holes.iter().map(|h| h.bytes).sum();
+        let byte_holes = holes.iter().filter(|h| h.bytes > 0).count();
+        let bit_holes: Vec<&Hole> = holes.iter().filter(|h| h.bits > 0).collect();
+
+        let _ = writeln!(out, "\n\t/* size: {}, cachelines: {}, members: {} */",
+            self.size, cachelines, self.members.len());
+        if byte_holes > 0 {
+            let _ = writeln!(out, "\t/* sum members: {}, holes: {}, sum holes: {} */",
+                self.sum_members(), byte_holes, sum_holes);
+        }
+        if !bit_holes.is_empty() {
+            let _ = writeln!(out, "\t/* bit holes: {}, sum bit holes: {} bits */",
+                bit_holes.len(), bit_holes.iter().map(|h| h.bits).sum::<usize>());
+        }
+        if padding > 0 {
+            let _ = writeln!(out, "\t/* padding: {} */", padding);
+        }
+        let last = self.size % cacheline;
+        if last > 0 && cachelines > 1 {
+            let _ = writeln!(out, "\t/* last cacheline: {} bytes */", last);
+        }
+        out.push_str("};\n");
+
+        out
+    }
+}
+
+impl fmt::Display for StructLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(DEFAULT_CACHELINE_SIZE))
+    }
+}
+
+impl<'a> Dwarf<'a> {
+    /// Returns the layout of every named, complete structure, class and union
+    /// type. Types defined identically in several units are reported once.
+    pub fn struct_layouts(&self) -> Result<Vec<StructLayout>> {
+        let mut seen = HashSet::new();
+        let mut layouts = Vec::new();
+
+        for unit in self.units() {
+            for die in &unit.dies {
+                if !is_struct_tag(die.tag) || die.attr(DW_AT_DECLARATION).is_some() {
+                    continue;
+                }
+
+                let layout = match self.struct_layout(unit, die)? {
+                    Some(l) => l,
+                    None => continue,
+                };
+
+                if seen.insert((layout.kind, layout.name.clone(), layout.size)) {
+                    layouts.push(layout);
+                }
+            }
+        }
+
+        Ok(layouts)
+    }
+
+    /// Looks up the layout of a single type by name. Typedefs to anonymous
+    /// structures are resolved as well, so `find_struct("DIR")` works for
+    /// `typedef struct { ... } DIR`.
+    pub fn find_struct(&self, name: &str) -> Result<Option<StructLayout>> {
+        for unit in self.units() {
+            for die in &unit.dies {
+                if die.attr(DW_AT_DECLARATION).is_some() {
+                    continue;
+                }
+
+                let target = if is_struct_tag(die.tag) {
+                    die
+                } else if die.tag == DW_TAG_TYPEDEF {
+                    match self.type_die(die) {
+                        Some((_, t)) if is_struct_tag(t.tag) && t.attr(DW_AT_NAME).is_none() => t,
+                        _ => continue,
+                    }
+                } else {
+                    continue;
+                };
+
+                if self.die_name(unit, die).as_deref() != Some(name) {
+                    continue;
+                }
+
+                if let Some(mut layout) = self.struct_layout(unit, target)? {
+                    layout.name = name.to_string();
+                    return Ok(Some(layout));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Writes every struct layout in pahole format to `out`.
+    pub fn dump_struct_layouts<W: Write>(&self, out: &mut W, cacheline: usize) -> Result<()> {
+        for layout in self.struct_layouts()? {
+            writeln!(out, "{}", layout.render(cacheline))?;
+        }
+
+        Ok(())
+    }
+
+    fn struct_layout(&self, unit: &Unit, die: &Die) -> Result<Option<StructLayout>> {
+        let size = match die.attr(DW_AT_BYTE_SIZE).and_then(|v| v.as_u64()) {
+            Some(s) => s as usize,
+            None => return Ok(None),
+        };
+
+        let name = match self.die_name(unit, die) {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+
+        let kind = StructKind::from_tag(die.tag);
+        let mut members = Vec::new();
+
+        for &child in &die.children {
+            let m = &unit.dies[child];
+            if m.tag != DW_TAG_MEMBER && m.tag != DW_TAG_INHERITANCE {
+                continue;
+            }
+
+            let location = m.attr(DW_AT_DATA_MEMBER_LOCATION);
+            // Static data members of C++ classes carry no location
+            if location.is_none() && kind != StructKind::Union && m.attr(DW_AT_DECLARATION).is_some() {
+                continue;
+            }
+
+            let type_die = self.type_die(m);
+            let type_name = match type_die {
+                Some((u, t)) => self.type_name(u, t, 0),
+                None => "void".to_string(),
+            };
+            let type_size = match type_die {
+                Some((u, t)) => self.type_size(u, t, 0).unwrap_or(0),
+                None => 0,
+            };
+
+            let mut offset = location.map(member_location).unwrap_or(0);
+            let bit_size = m.attr(DW_AT_BIT_SIZE).and_then(|v| v.as_u64()).map(|b| b as usize);
+            let mut size = m.attr(DW_AT_BYTE_SIZE)
+                .and_then(|v| v.as_u64())
+                .map(|b| b as usize)
+                .unwrap_or(type_size);
+
+            let mut bit_offset = None;
+            if let Some(bits) = bit_size {
+                let storage = if size == 0 { 1 } else { size };
+                if let Some(dbo) = m.attr(DW_AT_DATA_BIT_OFFSET).and_then(|v| v.as_u64()) {
+                    // DWARF 4+: offset from the start of the structure
+                    let dbo = dbo as usize;
+                    offset = (dbo / 8) / storage * storage;
+                    bit_offset = Some(dbo - offset * 8);
+                } else if let Some(bo) = m.attr(DW_AT_BIT_OFFSET).and_then(|v| v.as_u64()) {
+                    // DWARF 2/3: big endian bit numbering within the storage unit
+                    bit_offset = Some(storage.saturating_mul(8).saturating_sub((bo as usize).saturating_add(bits)));
+                } else {
+                    bit_offset = Some(0);
+                }
+                size = storage;
+            }
+
+            let name = if m.tag == DW_TAG_INHERITANCE {
+                String::new()
+            } else {
+                self.die_name(unit, m).unwrap_or_default()
+            };
+
+            let (type_name, name) = split_array_type(type_name, name);
+
+            members.push(Member {
+                name,
+                type_name,
+                offset,
+                size,
+                bit_offset,
+                bit_size,
+                inherited: m.tag == DW_TAG_INHERITANCE,
+            });
+        }
+
+        if kind != StructKind::Union {
+            members.sort_by_key(|m| m.start_bits());
+        }
+
+        Ok(Some(StructLayout { kind, name, size, members }))
+    }
+
+    fn type_die(&self, die: &Die) -> Option<(&Unit<'a>, &Die<'a>)> {
+        match die.attr(DW_AT_TYPE) {
+            Some(&AttrValue::Ref(off)) => self.die_at(off),
+            _ => None,
+        }
+    }
+
+    /// Returns a C style name for the type described by `die`.
+    pub fn type_name(&self, unit: &Unit, die: &Die, depth: usize) -> String {
+        if depth > 32 {
+            return "...".to_string();
+        }
+
+        let inner = |s: &Self| match s.type_die(die) {
+            Some((u, t)) => s.type_name(u, t, depth + 1),
+            None => "void".to_string(),
+        };
+
+        match die.tag {
+            DW_TAG_POINTER_TYPE => {
+                if let Some((u, t)) = self.type_die(die) {
+                    if t.tag == DW_TAG_SUBROUTINE_TYPE {
+                        return self.subroutine_name(u, t, depth);
+                    }
+                }
+                format!("{} *", inner(self))
+            },
+            DW_TAG_REFERENCE_TYPE => format!("{} &", inner(self)),
+            DW_TAG_RVALUE_REFERENCE_TYPE => format!("{} &&", inner(self)),
+            DW_TAG_CONST_TYPE => format!("const {}", inner(self)),
+            DW_TAG_VOLATILE_TYPE => format!("volatile {}", inner(self)),
+            DW_TAG_RESTRICT_TYPE => format!("{} restrict", inner(self)),
+            DW_TAG_ATOMIC_TYPE => format!("_Atomic {}", inner(self)),
+            DW_TAG_ARRAY_TYPE => {
+                let dims: String = self.array_dimensions(unit, die).iter()
+                    .map(|d| format!("[{}]", d))
+                    .collect();
+                format!("{}{}", inner(self), dims)
+            },
+            DW_TAG_SUBROUTINE_TYPE => self.subroutine_name(unit, die, depth),
+            DW_TAG_STRUCTURE_TYPE | DW_TAG_CLASS_TYPE | DW_TAG_UNION_TYPE | DW_TAG_ENUMERATION_TYPE => {
+                let kind = match die.tag {
+                    DW_TAG_ENUMERATION_TYPE => "enum".to_string(),
+                    tag => StructKind::from_tag(tag).to_string(),
+                };
+                match self.die_name(unit, die) {
+                    Some(n) => format!("{kind} {n}"),
+                    None => format!("{kind} {{...}}"),
+                }
+            },
+            _ => self.die_name(unit, die).unwrap_or_else(|| "void".to_string()),
+        }
+    }
+
+    fn subroutine_name(&self, unit: &Unit, die: &Die, depth: usize) -> String {
+        let ret = match self.type_die(die) {
+            Some((u, t)) => self.type_name(u, t, depth + 1),
+            None => "void".to_string(),
+        };
+
+        let params: Vec<String> = die.children.iter()
+            .map(|&c| &unit.dies[c])
+            .filter_map(|p| self.type_die(p))
+            .map(|(u, t)| self.type_name(u, t, depth + 1))
+            .collect();
+
+        format!("{} (*)({})", ret, params.join(", "))
+    }
+
+    /// Returns the size in bytes of the type described by `die`.
+    pub fn type_size(&self, unit: &Unit, die: &Die, depth: usize) -> Option<usize> {
+        if depth > 32 {
+            return None;
+        }
+
+        if let Some(size) = die.attr(DW_AT_BYTE_SIZE).and_then(|v| v.as_u64()) {
+            return Some(size as usize);
+        }
+
+        match die.tag {
+            DW_TAG_POINTER_TYPE | DW_TAG_REFERENCE_TYPE
+                | DW_TAG_RVALUE_REFERENCE_TYPE | DW_TAG_PTR_TO_MEMBER_TYPE =>
+                Some(unit.address_size as usize),
+            DW_TAG_ARRAY_TYPE => {
+                let (u, t) = self.type_die(die)?;
+                let elem = self.type_size(u, t, depth + 1)?;
+                let count = self.array_dimensions(unit, die).iter()
+                    .try_fold(1usize, |count, &d| count.checked_mul(d))?;
+                elem.checked_mul(count)
+            },
+            _ => {
+                let (u, t) = self.type_die(die)?;
+                self.type_size(u, t, depth + 1)
+            },
+        }
+    }
+
+    fn array_dimensions(&self, unit: &Unit, die: &Die) -> Vec<usize> {
+        die.children.iter()
+            .map(|&c| &unit.dies[c])
+            .filter(|d| d.tag == DW_TAG_SUBRANGE_TYPE)
+            .map(|d| {
+                if let Some(count) = d.attr(DW_AT_COUNT).and_then(|v| v.as_u64()) {
+                    return count as usize;
+                }
+                let lower = d.attr(DW_AT_LOWER_BOUND).and_then(|v| v.as_i64()).unwrap_or(0);
+                match d.attr(DW_AT_UPPER_BOUND).and_then(|v| v.as_i64()) {
+                    Some(upper) if upper >= lower => upper.checked_sub(lower)
+                        .and_then(|n| n.checked_add(1))
+                        .map_or(0, |n| n as usize),
+                    _ => 0,
+                }
+            })
+            .collect()
+    }
+}
+
+fn is_struct_tag(tag: u64) -> bool {
+    matches!(tag, DW_TAG_STRUCTURE_TYPE | DW_TAG_CLASS_TYPE | DW_TAG_UNION_TYPE)
+}
+
+/// Decodes `DW_AT_data_member_location`, which is either a constant or, in
+/// older producers, a `DW_OP_plus_uconst` expression.
+fn member_location(value: &AttrValue) -> usize {
+    match *value {
+        AttrValue::Exprloc(expr) | AttrValue::Block(expr) => {
+            let mut r = Reader::new(expr);
+            match r.u8() {
+                Ok(DW_OP_PLUS_UCONST) => r.uleb128().unwrap_or(0) as usize,
+                _ => 0,
+            }
+        },
+        _ => value.as_u64().unwrap_or(0) as usize,
+    }
+}
+
+/// Moves array dimensions from the type to the member name, so that
+/// `char[256] d_name` is printed as `char d_name[256]`.
+fn split_array_type(type_name: String, name: String) -> (String, String) {
+    if !type_name.ends_with(']') || type_name.contains("(*)") {
+        return (type_name, name);
+    }
+
+    match type_name.find('[') {
+        Some(i) => (type_name[..i].to_string(), format!("{}{}", name, &type_name[i..])),
+        None => (type_name, name),
+    }
+}
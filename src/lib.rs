@@ -1,5 +1,4 @@
 use std::{
-    arch::asm,
     thread,
     time,
 };
@@ -10,6 +9,34 @@ pub mod section;
 pub mod segment;
 pub mod symbols;
 pub mod dynamic;
+pub mod dwarf;
+pub mod eh_frame;
+pub mod hash;
+pub mod auxv;
+pub mod note;
+pub mod coredump;
+pub mod image;
+pub mod link_map;
+pub mod versions;
+pub mod vdso;
+pub mod relocation;
+pub mod relocated;
+pub mod plthook;
+pub mod loader;
+pub mod process;
+pub mod symbolizer;
+pub mod ptrace;
+pub mod gopclntab;
+pub mod gobuildinfo;
+pub mod gotypes;
+pub mod symtab;
+pub mod toolchain;
+pub mod rustinfo;
+pub mod sbom;
+pub mod package;
+pub mod kmod;
+pub mod layout;
+pub mod unwind;
 pub mod utils;
 
 #[macro_use]
@@ -18,16 +45,14 @@ extern crate num_derive;
  use crate::symbols::SymbolType;
  use crate::section::SectionType;
 
- const PAGE_SIZE: usize = 0x1000;
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     //#[test]
     fn test_elf() {
-        let loadaddr = get_loadaddr();
-        println!("load address: {:#x}", loadaddr as usize);
+        let image = image::ProcessImage::this().expect("test failed");
+        println!("load address: {:#x}", image.base());
         
         let path = "test.bin";
         let elf = elf::Elf::open(path).expect("test failed");
@@ -82,41 +107,1054 @@ mod tests {
         let sym = elf.get_symbol(name)
             .expect(&String::from(format!("Failed to find symbol: '{name}'")));
         println!("{}", sym);
+
+        // Imports are only in the dynamic symbol table.
+        let elf = elf::Elf::open("./testbins/test.bin").unwrap();
+        assert!(elf.get_symbol("atoi").is_none());
+        assert!(elf.iter_symbols().all(|s| s.name != "atoi"));
+        assert_eq!(elf.find_symbol("atoi").expect("no dynamic atoi").shndx, 0);
+        assert!(elf.iter_all_symbols().any(|s| s.name == "atoi"));
+        assert_eq!(elf.find_symbol("main").unwrap().value, 0x11d9);
     }
 
-    //#[test]
-    fn test_dynamic_section() {
+    #[test]
+    fn test_dwarf_units() {
+        let path = "./testbins/test.bin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let dwarf = elf.dwarf().expect("failed to parse dwarf");
+        let cus = dwarf.compile_units().expect("failed to read compile units");
+        assert_eq!(cus.len(), 1);
+
+        let cu = &cus[0];
+        assert_eq!(cu.name.as_deref(), Some("test.c"));
+        assert_eq!(cu.language_name(), Some("C11"));
+        assert!(cu.producer.as_ref().unwrap().starts_with("GNU C17"));
+
+        let main = cu.functions.iter()
+            .find(|f| f.name.as_deref() == Some("main"))
+            .expect("main not found");
+        assert_eq!(main.ranges, vec![0x11d9..0x1254]);
+
+        let frames = dwarf.find_frames(0x1300).unwrap();
+        assert_eq!(frames[0].function.as_deref(), Some("getfds"));
+    }
+
+    #[test]
+    fn test_struct_layout() {
+        let path = "./testbins/test.bin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let dwarf = elf.dwarf().expect("failed to parse dwarf");
+        let layout = dwarf.find_struct("dirent")
+            .unwrap()
+            .expect("struct dirent not found");
+
+        assert_eq!(layout.kind, layout::StructKind::Struct);
+        assert_eq!(layout.size, 280);
+        assert_eq!(layout.members.len(), 5);
+        assert_eq!(layout.members[4].name, "d_name[256]");
+        assert_eq!(layout.members[4].offset, 19);
+        assert!(layout.holes().is_empty());
+        assert_eq!(layout.padding(), 5);
+
+        let expected = "\
+struct dirent {
+\t__ino_t d_ino;                                   /*     0     8 */
+\t__off_t d_off;                                   /*     8     8 */
+\tshort unsigned int d_reclen;                     /*    16     2 */
+\tunsigned char d_type;                            /*    18     1 */
+\tchar d_name[256];                                /*    19   256 */
+
+\t/* size: 280, cachelines: 5, members: 5 */
+\t/* padding: 5 */
+\t/* last cacheline: 24 bytes */
+};
+";
+        assert_eq!(layout.render(64), expected);
+        assert_eq!(layout.to_string(), expected);
+
+        // Small cache lines put a boundary marker before d_reclen.
+        let rendered = layout.render(16);
+        assert!(rendered.contains(concat!(
+            "/*     8     8 */\n",
+            "\t/* --- cacheline 1 boundary (16 bytes) --- */\n",
+            "\tshort unsigned int d_reclen;")));
+        assert!(rendered.contains("/* size: 280, cachelines: 18, members: 5 */"));
+        assert!(rendered.contains("/* last cacheline: 8 bytes */"));
+
+        let mut dump = Vec::new();
+        dwarf.dump_struct_layouts(&mut dump, 64).unwrap();
+        assert_eq!(String::from_utf8(dump).unwrap(), format!("{}\n", expected));
+    }
+
+    #[test]
+    fn test_eh_frame() {
         let path = "./testbins/rustbin";
         let elf = elf::Elf::open(path)
             .expect("failed to open {path}");
 
-        for d in elf.dynamic {
-            println!("{:?}: {:#x}", d.tag, d.val);
+        let eh_frame = elf.eh_frame().expect("failed to parse .eh_frame");
+        let fde = eh_frame.find_fde(0x5070).expect("no FDE for 0x5070");
+        assert_eq!(fde.pc_begin..fde.pc_end(), 0x5050..0x5135);
+
+        // push %rbp; mov %rsp,%rbp
+        let row = eh_frame.unwind_row(0x5070).unwrap().unwrap();
+        assert_eq!(row.cfa, eh_frame::CfaRule::RegisterOffset { register: 6, offset: 16 });
+        assert_eq!(row.register(6), Some(&eh_frame::RegisterRule::Offset(-16)));
+        assert_eq!(row.register(16), Some(&eh_frame::RegisterRule::Offset(-8)));
+    }
+
+    /// Memory for the unwinder tests, `data` mapped at `base`.
+    struct TestMemory {
+        base: u64,
+        data: Vec<u8>,
+    }
+
+    impl TestMemory {
+        fn new(base: u64, size: usize) -> Self {
+            TestMemory { base, data: vec![0; size] }
         }
 
+        fn write_u64(&mut self, addr: u64, value: u64) {
+            let off = (addr - self.base) as usize;
+            self.data[off..off + 8].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    impl unwind::MemoryProvider for TestMemory {
+        fn read_memory(&self, addr: u64, buf: &mut [u8]) -> std::io::Result<()> {
+            let bytes = addr.checked_sub(self.base)
+                .and_then(|off| self.data.get(off as usize..off as usize + buf.len()))
+                .ok_or_else(|| std::io::Error::other(format!("{:#x} is not mapped", addr)))?;
+            buf.copy_from_slice(bytes);
+            Ok(())
+        }
     }
-}
 
-#[inline(always)]
-fn get_ip() -> usize {
-    let ip: usize;
+    #[test]
+    fn test_dwarf_expression() {
+        let mut regs = unwind::Registers::new(unwind::Arch::X86_64);
+        regs.set_sp(0x1000);
+        regs.set_pc(0x102b);
+        let mut mem = TestMemory::new(0x1000, 0x20);
+        mem.write_u64(0x1008, 0xdeadbeef);
 
-    unsafe { asm!("lea   {}, [rip]", out(reg) ip); }
-    ip
-}
+        let eval = |expr: &[u8], initial| unwind::evaluate(expr, &regs, &mem, initial);
+
+        // DW_OP_breg7 8
+        assert_eq!(eval(&[0x77, 0x08], None).unwrap(), 0x1008);
+        // DW_OP_breg7 8; DW_OP_deref
+        assert_eq!(eval(&[0x77, 0x08, 0x06], None).unwrap(), 0xdeadbeef);
+        // DW_OP_lit3; DW_OP_lit4; DW_OP_plus; DW_OP_lit2; DW_OP_mul; DW_OP_lit5; DW_OP_minus
+        assert_eq!(eval(&[0x33, 0x34, 0x22, 0x32, 0x1e, 0x35, 0x1c], None).unwrap(), 9);
+        // DW_OP_plus_uconst 0x10 on the CFA
+        assert_eq!(eval(&[0x23, 0x10], Some(0x2000)).unwrap(), 0x2010);
+        // The PLT CFA expression of test.bin, past the pushq in the PLT entry.
+        let plt = [0x77, 0x08, 0x80, 0x00, 0x3f, 0x1a, 0x3b, 0x2a, 0x33, 0x24, 0x22];
+        assert_eq!(eval(&plt, None).unwrap(), 0x1010);
+
+        assert!(eval(&[0x22], None).is_err());
+        assert!(eval(&[0x06], Some(0x5000)).is_err());
+    }
+
+    #[test]
+    fn test_unwind_cfi() {
+        let path = "./testbins/test.bin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let (main, offset) = elf.symbolize(0x11e0).expect("no symbol for 0x11e0");
+        assert_eq!((main.name.as_str(), offset), ("main", 7));
+        assert!(elf.symbolize(0x10).is_none());
+
+        let mut unwinder = unwind::Unwinder::new();
+        unwinder.add_module("test.bin", &elf, 0);
+
+        // In main after `push %rbp; mov %rsp,%rbp`, called from _start.
+        let stack = 0x7000_0000;
+        let mut mem = TestMemory::new(stack, 0x300);
+        mem.write_u64(stack + 0x10, stack + 0x100);
+        mem.write_u64(stack + 0x18, 0x1100);
+        // A frame pointer chain past _start, which must not be followed.
+        mem.write_u64(stack + 0x100, stack + 0x200);
+        mem.write_u64(stack + 0x108, 0x11f0);
+
+        let mut regs = unwind::Registers::new(unwind::Arch::X86_64);
+        regs.set_pc(0x11e0);
+        regs.set_sp(stack);
+        regs.set_fp(stack + 0x10);
+
+        let frames = unwinder.unwind(&regs, &mem);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].symbol.as_deref(), Some("main"));
+        assert_eq!(frames[1].symbol.as_deref(), Some("_start"));
+        assert_eq!(frames[1].method, unwind::UnwindMethod::Cfi);
+        assert_eq!(frames[1].sp, stack + 0x20);
+    }
+
+    #[test]
+    fn test_unwind_frame_pointer() {
+        // No modules, so only the frame pointer chain is available.
+        let unwinder = unwind::Unwinder::new();
+
+        let stack = 0x7000_0000;
+        let mut mem = TestMemory::new(stack, 0x300);
+        mem.write_u64(stack + 0x100, stack + 0x200);
+        mem.write_u64(stack + 0x108, 0x4000);
+        mem.write_u64(stack + 0x200, 0);
+        mem.write_u64(stack + 0x208, 0x5000);
+
+        let mut regs = unwind::Registers::new(unwind::Arch::X86_64);
+        regs.set_pc(0x3000);
+        regs.set_sp(stack);
+        regs.set_fp(stack + 0x100);
+
+        let frames = unwinder.unwind(&regs, &mem);
+        let pcs: Vec<u64> = frames.iter().map(|f| f.pc).collect();
+        assert_eq!(pcs, [0x3000, 0x4000, 0x5000]);
+        assert!(frames[1..].iter().all(|f| f.method == unwind::UnwindMethod::FramePointer));
+        assert_eq!(frames[2].sp, stack + 0x210);
+    }
+
+    // Two nested functions with frame pointers and CFI. The inner one stores
+    // its pc, sp and fp to the array in rdi and calls rsi(rdi, rdx), so the
+    // callback runs while both frames are live.
+    #[cfg(target_arch = "x86_64")]
+    std::arch::global_asm!(
+        ".pushsection .text",
+        ".globl frieren_capture_outer",
+        ".type frieren_capture_outer, @function",
+        "frieren_capture_outer:",
+        ".cfi_startproc",
+        "push rbp",
+        ".cfi_def_cfa_offset 16",
+        ".cfi_offset rbp, -16",
+        "mov rbp, rsp",
+        ".cfi_def_cfa_register rbp",
+        "call frieren_capture_inner",
+        "pop rbp",
+        ".cfi_def_cfa rsp, 8",
+        "ret",
+        ".cfi_endproc",
+        ".size frieren_capture_outer, . - frieren_capture_outer",
+        ".type frieren_capture_inner, @function",
+        "frieren_capture_inner:",
+        ".cfi_startproc",
+        "push rbp",
+        ".cfi_def_cfa_offset 16",
+        ".cfi_offset rbp, -16",
+        "mov rbp, rsp",
+        ".cfi_def_cfa_register rbp",
+        "lea rax, [rip]",
+        "mov [rdi], rax",
+        "mov [rdi + 8], rsp",
+        "mov [rdi + 16], rbp",
+        "mov rax, rsi",
+        "mov rsi, rdx",
+        "call rax",
+        "pop rbp",
+        ".cfi_def_cfa rsp, 8",
+        "ret",
+        ".cfi_endproc",
+        ".size frieren_capture_inner, . - frieren_capture_inner",
+        ".popsection",
+    );
+
+    #[cfg(target_arch = "x86_64")]
+    type CaptureCallback = extern "C" fn(*const [u64; 3], *mut std::ffi::c_void);
+
+    #[cfg(target_arch = "x86_64")]
+    extern "C" {
+        fn frieren_capture_outer(regs: *mut [u64; 3], callback: CaptureCallback,
+                                 stacks: *mut std::ffi::c_void);
+    }
+
+    /// Unwinds from the captured registers through CFI and then through the
+    /// frame pointers alone.
+    #[cfg(target_arch = "x86_64")]
+    extern "C" fn unwind_captured(raw: *const [u64; 3], stacks: *mut std::ffi::c_void) {
+        let (raw, stacks) = unsafe { (*raw, &mut *(stacks as *mut Vec<Vec<unwind::Frame>>)) };
+        let mut regs = unwind::Registers::new(unwind::Arch::X86_64);
+        regs.set_pc(raw[0]);
+        regs.set_sp(raw[1]);
+        regs.set_fp(raw[2]);
+
+        let (process, symbolizer) = match (process::Process::this(), symbolizer::ProcessSymbolizer::this()) {
+            (Ok(p), Ok(s)) => (p, s),
+            _ => return,
+        };
+        let mut unwinder = unwind::Unwinder::new();
+        for module in symbolizer.modules() {
+            unwinder.add_module(module.name(), module.elf(), module.bias);
+        }
+
+        stacks.push(unwinder.unwind(&regs, &process));
+        stacks.push(unwind::Unwinder::new().unwind(&regs, &process));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_unwind_self() {
+        let mut raw = [0u64; 3];
+        let mut stacks: Vec<Vec<unwind::Frame>> = Vec::new();
+        unsafe { frieren_capture_outer(&mut raw, unwind_captured, &mut stacks as *mut _ as *mut std::ffi::c_void) };
+        assert_eq!(stacks.len(), 2, "failed to open the process");
+
+        let symbolizer = symbolizer::ProcessSymbolizer::this().expect("failed to read maps");
+        let mut unwinder = unwind::Unwinder::new();
+        for module in symbolizer.modules() {
+            unwinder.add_module(module.name(), module.elf(), module.bias);
+        }
+
+        let frames = &stacks[0];
+        assert!(frames.len() < unwinder.max_frames);
+        assert_eq!(frames[0].symbol.as_deref(), Some("frieren_capture_inner"));
+        assert_eq!(frames[1].symbol.as_deref(), Some("frieren_capture_outer"));
+        assert_eq!(frames[1].method, unwind::UnwindMethod::Cfi);
+        assert!(frames[2].symbol.as_deref().is_some_and(|s| s.contains("test_unwind_self")));
+        assert_eq!(frames[2].method, unwind::UnwindMethod::Cfi);
+
+        let frames = &stacks[1];
+        assert!(frames.len() >= 3);
+        assert_eq!(frames[0].pc, stacks[0][0].pc);
+        assert_eq!(frames[1].method, unwind::UnwindMethod::FramePointer);
+        let (_, outer, _) = unwinder.symbolize(frames[1].pc - 1);
+        assert_eq!(outer.as_deref(), Some("frieren_capture_outer"));
+        let (_, caller, _) = unwinder.symbolize(frames[2].pc - 1);
+        assert!(caller.is_some_and(|s| s.contains("test_unwind_self")));
+    }
+
+    #[test]
+    fn test_notes() {
+        let path = "./testbins/test.bin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let notes = elf.get_notes().expect("failed to parse notes");
+        assert_eq!(notes.len(), 3);
+
+        let build_id = elf.get_note("GNU", 3)
+            .expect("failed to parse notes")
+            .expect("no build-id");
+        assert_eq!(build_id.desc[..4], [0x5e, 0xe9, 0xef, 0xcd]);
+
+        assert!(elf.core().is_err());
+
+        // Drop the PT_NOTE covering the build-id, the section is still found.
+        let mut data = elf.as_bytes().to_vec();
+        let phdr = elf.header().e_phoff + 8 * std::mem::size_of::<segment::Phdr>();
+        data[phdr..phdr + 4].copy_from_slice(&(segment::SegmentType::Null as u32).to_le_bytes());
+        let elf = elf::Elf::from_bytes(data).unwrap();
+        assert_eq!(elf.get_notes().unwrap().len(), 1);
+        let build_id = elf.get_note("GNU", 3).unwrap().expect("no build-id");
+        assert_eq!(build_id.desc[..4], [0x5e, 0xe9, 0xef, 0xcd]);
+    }
+
+    #[test]
+    fn test_core() {
+        // A static program storing to 0x10, run as "./crash arg1 arg2".
+        let path = "./testbins/crash.core";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+        let core = elf.core().expect("failed to load core");
+
+        assert_eq!(core.arch, unwind::Arch::X86_64);
+        assert_eq!(core.threads.len(), 1);
+        assert_eq!(core.signal(), Some(11));
+
+        let thread = core.signal_thread().unwrap();
+        assert_eq!(thread.status.signo, 11);
+        assert_eq!(thread.status.pid, 6893);
+        assert!(thread.status.fpvalid);
+        // The faulting store in main.
+        assert_eq!(thread.registers().pc(), Some(0x401637));
+
+        let siginfo = thread.siginfo.as_ref().expect("no NT_SIGINFO");
+        assert_eq!(siginfo.signo, 11);
+        assert_eq!(siginfo.code, 1); // SEGV_MAPERR
+        assert_eq!(siginfo.addr, Some(0x10));
+
+        let process = core.process.as_ref().expect("no NT_PRPSINFO");
+        assert_eq!(process.fname, "crash");
+        assert_eq!(process.psargs.trim_end(), "./crash arg1 arg2");
+        assert_eq!(process.pid, 6893);
+        assert_eq!(process.ppid, 6880);
+
+        match thread.fpregs.as_ref().expect("no NT_FPREGSET") {
+            coredump::FpRegisters::X86_64 { mxcsr, xmm, .. } => {
+                assert_eq!(*mxcsr, 0x1f80);
+                // The volatile double 1.5 main kept in xmm0.
+                assert_eq!(xmm[0] as u64, 1.5f64.to_bits());
+            },
+            regs => panic!("unexpected registers {regs:?}"),
+        }
+
+        assert_eq!(core.files.len(), 5);
+        let file = core.find_file(0x401637).expect("no NT_FILE entry");
+        assert_eq!(file.path, "/tmp/core/crash");
+        assert_eq!((file.start, file.end, file.offset), (0x401000, 0x479000, 0x1000));
+
+        // char marker[64] = "frieren core marker" lives at 0x4a40e0.
+        let mut marker = [0; 19];
+        core.read_memory(0x4a40e0, &mut marker).expect("failed to read memory");
+        assert_eq!(&marker, b"frieren core marker");
+        assert!(core.read_memory(0x10, &mut marker).is_err());
+    }
+
+    #[test]
+    fn test_current_executable() {
+        let image = image::ProcessImage::this().expect("failed to locate executable");
+        let addr = test_current_executable as *const () as usize;
+        assert!(image.contains(addr));
+
+        let containing = image::ProcessImage::containing(addr)
+            .unwrap()
+            .expect("no image contains the test function");
+        assert_eq!(containing.base(), image.base());
+
+        let elf = elf::Elf::current_executable().expect("failed to parse executable");
+        assert_eq!(elf.header().e_phnum, image.header().e_phnum);
+        assert!(!elf.dynamic.is_empty());
+    }
+
+    #[test]
+    fn test_link_map() {
+        let maps = link_map::loaded_objects().expect("failed to walk link_map");
+        assert!(maps.len() > 1);
+        assert!(maps.iter().any(|m| m.name().contains("libc.so")));
+
+        for map in &maps {
+            let image = unsafe { map.image() }.expect("failed to locate image");
+            assert_eq!(image.dynamic_addr(), Some(map.ld()));
+        }
+    }
+
+    #[test]
+    fn test_process_memory() {
+        let process = process::Process::this().expect("failed to open /proc/self/mem");
+        let base = process.executable_base().expect("failed to locate executable");
+        assert_eq!(base as usize, image::ProcessImage::this().unwrap().base());
+
+        let (libc_base, _) = process.elf_bases().unwrap()
+            .into_iter()
+            .find(|(_, path)| path.contains("libc.so"))
+            .expect("libc not mapped");
+        let libc = process.read_elf(libc_base).expect("failed to parse libc");
+        let malloc = libc.lookup_dynamic_symbol("malloc")
+            .unwrap()
+            .expect("malloc not found");
+        assert_eq!(libc.find_symbol("malloc").unwrap().value, malloc.value);
+        assert!(libc.get_symbol("malloc").is_none());
+    }
+
+    #[test]
+    fn test_process_symbolizer() {
+        let mut symbolizer = symbolizer::ProcessSymbolizer::this().expect("failed to read maps");
+        let addr = test_process_symbolizer as *const () as usize as u64;
+
+        let sym = symbolizer.symbolize(addr);
+        assert!(sym.symbol.unwrap().contains("test_process_symbolizer"));
+        assert_eq!(sym.offset, 0);
+        assert!(sym.location.unwrap().file.ends_with("lib.rs"));
+
+        let libc = symbolizer.symbolize(libc::malloc as *const () as usize as u64);
+        assert!(libc.module.unwrap().starts_with("libc.so"));
+
+        // Files that are still mapped are not parsed again.
+        let elf = symbolizer.find_module(addr).unwrap().elf() as *const elf::Elf;
+        symbolizer.refresh().unwrap();
+        assert!(std::ptr::eq(symbolizer.find_module(addr).unwrap().elf(), elf));
+    }
+
+    #[test]
+    fn test_pstack() {
+        let mut child = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+        sleep(1);
+
+        let stacks = ptrace::pstack(child.id());
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        let stacks = stacks.expect("failed to trace child");
+        assert_eq!(stacks.len(), 1);
+        assert!(stacks[0].frames.iter().any(|f| f.location.module.as_deref()
+            .is_some_and(|m| m.starts_with("libc.so"))));
+    }
+
+    #[test]
+    fn test_auxv() {
+        let auxv = auxv::Auxv::this().expect("failed to read auxv");
+        let phdr = unsafe { libc::getauxval(libc::AT_PHDR) };
+        assert_eq!(auxv.phdr(), Some(phdr));
+        assert!(auxv.sysinfo_ehdr().is_some());
+
+        let process = process::Process::this().unwrap();
+        let execfn = auxv.execfn(&process).expect("failed to read AT_EXECFN");
+        assert!(!execfn.is_empty());
+    }
+
+    #[test]
+    fn test_vdso() {
+        let f = vdso::lookup("__vdso_clock_gettime", "LINUX_2.6")
+            .expect("__vdso_clock_gettime not found");
+        let clock_gettime: extern "C" fn(libc::clockid_t, *mut libc::timespec) -> libc::c_int =
+            unsafe { std::mem::transmute(f) };
+
+        let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        assert_eq!(clock_gettime(libc::CLOCK_MONOTONIC, &mut ts), 0);
+        assert!(ts.tv_sec != 0 || ts.tv_nsec != 0);
+
+        assert!(vdso::lookup("__vdso_clock_gettime", "LINUX_0.0").is_none());
+    }
+
+    extern "C" fn fake_getppid() -> libc::pid_t {
+        4242
+    }
+
+    #[test]
+    fn test_plthook() {
+        let hook = plthook::PltHook::open("").expect("failed to open executable");
+        let real = std::os::unix::process::parent_id();
+
+        let original = unsafe { hook.replace("getppid", fake_getppid as *const ()) }
+            .expect("failed to hook getppid");
+        assert_eq!(std::os::unix::process::parent_id(), 4242);
+        assert_eq!(unsafe { hook.get("getppid") }.unwrap(), fake_getppid as *const ());
+
+        unsafe { hook.replace("getppid", original) }.unwrap();
+        assert_eq!(std::os::unix::process::parent_id(), real);
+    }
+
+    #[test]
+    fn test_loader() {
+        type IntFn = unsafe extern "C" fn() -> i32;
+        type LenFn = unsafe extern "C" fn() -> usize;
+        type SetFn = unsafe extern "C" fn(*mut i32);
+
+        let path = "./testbins/libloadme.so.1";
+        let data = std::fs::read(path).expect("failed to read {path}");
+
+        let mut fini_flag = 0;
+        unsafe {
+            let obj = loader::load(data.clone()).expect("failed to load");
+            assert!(obj.contains(obj.base()) && !obj.contains(obj.base() + obj.size()));
+            let initialized: IntFn = std::mem::transmute(obj.dlsym("loadme_initialized").unwrap());
+            assert_eq!(initialized(), 1);
+            // Goes through a RELATIVE pointer and the strlen JUMP_SLOT.
+            let len: LenFn = std::mem::transmute(obj.dlsym("loadme_greeting_len").unwrap());
+            assert_eq!(len(), "hello from libloadme".len());
+            assert!(obj.dlsym("loadme_missing").is_none());
 
-fn get_loadaddr() -> *mut usize {
-    let ip = get_ip() & !(PAGE_SIZE -1);
-    let mut p = ip as *const u32;
-    let magic = 0x464c457f;         // ELF Magic bytes, little endian
+            let set_fini_flag: SetFn = std::mem::transmute(obj.dlsym("loadme_set_fini_flag").unwrap());
+            set_fini_flag(std::ptr::addr_of_mut!(fini_flag));
+            drop(obj);
+        }
+        assert_eq!(fini_flag, 1);
 
-    loop {
+        // Without section headers the dynamic section comes from PT_DYNAMIC.
+        let mut stripped = data.clone();
+        stripped[0x28..0x30].fill(0); // e_shoff
+        stripped[0x3c..0x40].fill(0); // e_shnum, e_shstrndx
         unsafe {
-            if *p == magic {
-                return p as *mut usize;
-            }
+            let obj = loader::load(stripped).expect("failed to load without section headers");
+            let len: LenFn = std::mem::transmute(obj.dlsym("loadme_greeting_len").unwrap());
+            assert_eq!(len(), "hello from libloadme".len());
+        }
+
+        // Turn DT_RELACOUNT into DT_RELR.
+        let elf = elf::Elf::from_bytes(data.clone()).unwrap();
+        let index = elf.dynamic.iter()
+            .position(|d| d.tag == dynamic::EntryType::RelaCount)
+            .unwrap();
+        let offset = elf.get_section_by_name(".dynamic").unwrap().offset + index * 16;
+        let mut relr = data;
+        relr[offset..offset + 8].copy_from_slice(&36u64.to_le_bytes());
+        assert!(unsafe { loader::load(relr) }.is_err());
+    }
+
+    #[test]
+    fn test_relocated_image() {
+        let path = "./testbins/test.bin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let base = 0x5555_0000_0000;
+        let image = elf.relocated_image(base).expect("failed to relocate");
+        let init_array = elf.get_section_by_name(".init_array").unwrap();
+        let text = elf.get_section_by_name(".text").unwrap();
+
+        let ctor = image.read_usize(base + init_array.addr).unwrap();
+        assert!(ctor >= base + text.addr && ctor < base + text.addr + text.size);
+    }
+
+    #[test]
+    fn test_relocated_object() {
+        let path = "./testbins/frieren.ko";
+        let data = std::fs::read(path).expect("failed to read {path}");
+        let elf = elf::Elf::from_bytes(data.clone()).unwrap();
+
+        let base = 0x10000;
+        let image = elf.relocated_image(base).expect("failed to relocate");
+        let addr = |name: &str| {
+            let index = elf.iter_sections().position(|s| s.name == name).unwrap();
+            image.section_addrs[index].expect("section not placed")
+        };
+        assert!(image.section_addrs[elf.iter_sections().position(|s| s.name == ".symtab").unwrap()].is_none());
+        assert!(image.unresolved.is_empty());
+
+        // __ksymtab holds PC32 offsets to the function and its name.
+        let ksymtab = addr("__ksymtab");
+        let offset = |place: usize| i32::from_le_bytes(image.read(place, 4).unwrap().try_into().unwrap()) as isize;
+        assert_eq!(ksymtab.wrapping_add_signed(offset(ksymtab)), addr(".text.frieren_widget_count"));
+        assert_eq!((ksymtab + 4).wrapping_add_signed(offset(ksymtab + 4)), addr("__ksymtab_strings"));
+
+        // The R_X86_64_64 pointer to init_module in struct module.
+        let this_module = addr(".gnu.linkonce.this_module");
+        assert_eq!(image.read_usize(this_module + 0x150), Some(addr(".text.frieren_init")));
+
+        // mov count(%rip), with the -4 addend of the PC32 relocation.
+        let init = addr(".text.frieren_init");
+        assert_eq!((init + 6).wrapping_add_signed(offset(init + 2)), addr(".data"));
+
+        // A relocation offset from the file that wraps around.
+        let mut bad = data;
+        bad[0xd90..0xd98].copy_from_slice(&u64::MAX.to_le_bytes());
+        let elf = elf::Elf::from_bytes(bad).unwrap();
+        assert!(elf.relocated_image(base).is_err());
+    }
+
+    #[test]
+    fn test_go_pclntab() {
+        let path = "./testbins/gobinary";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let pclntab = elf.go_pclntab().expect("failed to parse pclntab");
+        assert_eq!(pclntab.version, gopclntab::PclntabVersion::Go120);
+
+        let main = pclntab.lookup_func("main.main").unwrap().expect("main.main not found");
+        assert_eq!((main.entry, main.size()), (0x490b40, 170));
+
+        let (func, file, line) = pclntab.pc_to_line(0x490b60).unwrap().unwrap();
+        assert_eq!(func.name, "main.main");
+        assert_eq!((file.as_str(), line), ("/home/vilroi/test.go", 10));
+
+        // Without section headers the table is found by scanning segments.
+        let mut stripped = elf.as_bytes().to_vec();
+        stripped[0x28..0x30].fill(0); // e_shoff
+        stripped[0x3c..0x40].fill(0); // e_shnum, e_shstrndx
+        let stripped = elf::Elf::from_bytes(stripped).unwrap();
+        let found = stripped.go_pclntab().expect("failed to find pclntab");
+        assert_eq!(found.vaddr, pclntab.vaddr);
+        assert_eq!(found.nfunc, pclntab.nfunc);
+        let main = found.lookup_func("main.main").unwrap().expect("main.main not found");
+        assert_eq!((main.entry, main.size()), (0x490b40, 170));
+    }
+
+    #[test]
+    fn test_go_build_info() {
+        let path = "./testbins/gobinary";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let info = elf.go_build_info().expect("failed to read build info");
+        assert_eq!(info.go_version, "go1.23.1");
+        assert_eq!(info.path, "command-line-arguments");
+        assert_eq!((info.goos(), info.goarch()), (Some("linux"), Some("amd64")));
+        assert_eq!(info.cgo_enabled(), Some(true));
+        assert!(info.deps.is_empty());
+
+        // Without section headers the data segments are scanned.
+        let mut stripped = elf.as_bytes().to_vec();
+        stripped[0x28..0x30].fill(0); // e_shoff
+        stripped[0x3c..0x40].fill(0); // e_shnum, e_shstrndx
+        let info = elf::Elf::from_bytes(stripped.clone()).unwrap()
+            .go_build_info().expect("failed to find build info");
+        assert_eq!(info.go_version, "go1.23.1");
+
+        // Rewrite .go.buildinfo in the pre-1.18 format, which points to the
+        // Go string headers of the version and the module info.
+        let section = elf.get_section_by_name(".go.buildinfo").unwrap();
+        let (offset, vaddr) = (section.offset, section.addr as u64);
+        let modinfo = b"0123456789abcdefpath\texample.com/old\nbuild\tGOOS=linux\n0123456789abcdef";
+        let mut buildinfo = b"\xff Go buildinf:\x08\x00".to_vec();
+        for word in [vaddr + 0x20, vaddr + 0x30, vaddr + 0x40, 8, vaddr + 0x50, modinfo.len() as u64] {
+            buildinfo.extend_from_slice(&word.to_le_bytes());
+        }
+        buildinfo.extend_from_slice(b"go1.17.5\0\0\0\0\0\0\0\0");
+        buildinfo.extend_from_slice(modinfo);
+        assert!(buildinfo.len() <= section.size);
+
+        for data in [elf.as_bytes().to_vec(), stripped] {
+            let mut data = data;
+            data[offset..offset + buildinfo.len()].copy_from_slice(&buildinfo);
+            let info = elf::Elf::from_bytes(data).unwrap()
+                .go_build_info().expect("failed to read pointer based build info");
+            assert_eq!((info.go_version.as_str(), info.path.as_str()), ("go1.17.5", "example.com/old"));
+            assert_eq!(info.goos(), Some("linux"));
         }
-        p = (p as usize - PAGE_SIZE) as *const u32;
+    }
+
+    #[test]
+    fn test_go_modinfo() {
+        let modinfo = concat!(
+            "path\texample.com/app\n",
+            "mod\texample.com/app\t(devel)\t\n",
+            "dep\tgithub.com/pkg/errors\tv0.9.1\th1:FEBLx1zS214owpjy7qsBeixbURkuhQAwrK5UwLGTwt4=\n",
+            "dep\tgolang.org/x/sys\tv0.1.0\th1:kunALQeHf1/185U1i0GOB/fy1IPRDDpuoOOqRReG57c=\n",
+            "=>\t../sys\t(devel)\t\n",
+            "dep\tgolang.org/x/text\tv0.3.0\n",
+            "=>\tgithub.com/fork/text\tv0.3.1\th1:abc=\n",
+            "build\t-buildmode=exe\n",
+            "build\t-ldflags=\"-s -w -X \\\"main.version=1.0\\\"\"\n",
+            "build\tCGO_ENABLED=0\n",
+            "build\tGOOS=linux\n",
+            "build\tGOARCH=arm64\n",
+        );
+        // The linker wraps the text in 16 byte sentinels.
+        let mut data = vec![0x30; 16];
+        data.extend_from_slice(modinfo.as_bytes());
+        data.extend_from_slice(&[0xf9; 16]);
+
+        let info = gobuildinfo::GoBuildInfo::parse_modinfo("go1.22.0", &data)
+            .expect("failed to parse modinfo");
+        assert_eq!(info.go_version, "go1.22.0");
+        assert_eq!(info.path, "example.com/app");
+
+        let main = info.main.as_ref().unwrap();
+        assert_eq!((main.path.as_str(), main.version.as_str()), ("example.com/app", "(devel)"));
+        assert!(main.sum.is_none() && main.replace.is_none());
+
+        assert_eq!(info.deps.len(), 3);
+        let errors = &info.deps[0];
+        assert_eq!(errors.path, "github.com/pkg/errors");
+        assert_eq!(errors.version, "v0.9.1");
+        assert_eq!(errors.sum.as_deref(), Some("h1:FEBLx1zS214owpjy7qsBeixbURkuhQAwrK5UwLGTwt4="));
+        assert!(errors.replace.is_none());
+
+        let sys = info.deps[1].replace.as_ref().expect("no replacement for x/sys");
+        assert_eq!((sys.path.as_str(), sys.version.as_str()), ("../sys", "(devel)"));
+        assert!(sys.sum.is_none());
+
+        let text = &info.deps[2];
+        assert!(text.sum.is_none());
+        let fork = text.replace.as_ref().expect("no replacement for x/text");
+        assert_eq!(fork.path, "github.com/fork/text");
+        assert_eq!(fork.sum.as_deref(), Some("h1:abc="));
+
+        assert_eq!(info.ldflags(), Some("-s -w -X \"main.version=1.0\""));
+        assert_eq!(info.setting("-buildmode"), Some("exe"));
+        assert_eq!(info.cgo_enabled(), Some(false));
+        assert_eq!((info.goos(), info.goarch()), (Some("linux"), Some("arm64")));
+
+        // Display writes the same lines back, quoting included.
+        let expected: String = modinfo.lines()
+            .map(|line| format!("\t{}\n", line.trim_end_matches('\t')))
+            .collect();
+        assert_eq!(info.to_string(), format!("go1.22.0\n{}", expected));
+
+        assert!(gobuildinfo::GoBuildInfo::parse_modinfo("go1.22.0", b"=>\tfoo\tv1\n").is_err());
+        assert!(gobuildinfo::GoBuildInfo::parse_modinfo("go1.22.0", b"build\tnovalue\n").is_err());
+    }
+
+    #[test]
+    fn test_go_types() {
+        let path = "./testbins/gobinary";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let types = elf.go_types().expect("failed to locate moduledata");
+        let md = &types.moduledata;
+        assert_eq!(md.vaddr, 0x54b2a0);
+        assert_eq!((md.text, md.types), (0x401000, 0x491000));
+        assert_eq!((md.typelinks.len, md.itablinks.len), (0x1ac, 13));
+
+        let file = types.types().unwrap()
+            .into_iter()
+            .find(|t| t.name == "*os.File")
+            .expect("*os.File not found");
+        let file = types.type_at(file.elem.unwrap()).unwrap();
+        assert_eq!(file.kind, gotypes::GoKind::Struct);
+        assert_eq!(file.fields[0].name, "file");
+        assert!(file.fields[0].embedded);
+
+        let itab = types.itabs().unwrap()
+            .into_iter()
+            .find(|i| types.type_name(i.typ).unwrap() == "*errors.errorString")
+            .expect("no itab for *errors.errorString");
+        assert_eq!(types.type_name(itab.interface).unwrap(), "error");
+        let methods = types.type_at(itab.typ).unwrap().methods;
+        assert_eq!((methods[0].name.as_str(), methods[0].ifn), ("Error", Some(0x46f4c0)));
+    }
+
+    #[test]
+    fn test_with_symbols() {
+        let path = "./testbins/test.bin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let data = elf.with_symbols([("recovered", 0x11d9, 0x7b, SymbolType::Function)])
+            .expect("failed to add symbols");
+        let patched = elf::Elf::from_bytes(data).expect("failed to parse output");
+
+        let sym = patched.get_symbol("recovered").expect("symbol not added");
+        assert_eq!((sym.value, sym.size), (0x11d9, 0x7b));
+        assert_eq!(patched.get_section_by_name(".text").unwrap().addr,
+            elf.get_section_by_name(".text").unwrap().addr);
+        assert_eq!(patched.get_symbol("main").unwrap().value, 0x11d9);
+
+        // A copy with the section headers removed gets new tables.
+        let mut stripped = elf.as_bytes().to_vec();
+        stripped[0x28..0x30].fill(0); // e_shoff
+        stripped[0x3c..0x40].fill(0); // e_shnum, e_shstrndx
+        let stripped = elf::Elf::from_bytes(stripped).unwrap();
+        assert_eq!(stripped.iter_sections().count(), 0);
+
+        let data = stripped.with_symbols([("recovered", 0x11d9, 0x7b, SymbolType::Function)])
+            .expect("failed to add symbols");
+        let patched = elf::Elf::from_bytes(data).expect("failed to parse output");
+
+        let names: Vec<&str> = patched.iter_sections().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["", ".symtab", ".strtab", ".shstrtab"]);
+        let symtab = patched.get_section_by_name(".symtab").unwrap();
+        assert_eq!(symtab.typ, SectionType::Symtab as u32);
+        assert_eq!(symtab.link, 2);
+        assert_eq!(patched.get_section_by_name(".strtab").unwrap().typ, SectionType::Strtab as u32);
+        assert_eq!(patched.get_section_by_name(".shstrtab").unwrap().typ, SectionType::Strtab as u32);
+        assert_eq!(patched.header().e_shstrndx, 3);
+
+        let sym = patched.get_symbol("recovered").expect("symbol not added");
+        assert_eq!((sym.value, sym.size), (0x11d9, 0x7b));
+        assert_eq!(sym.symbol_type, SymbolType::Function);
+        assert_eq!(patched.iter_symbols().count(), 2);
+        assert!(patched.get_dynamic(dynamic::EntryType::Needed).is_some());
+    }
+
+    #[test]
+    fn test_demangle() {
+        let path = "./testbins/rustbin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let write = elf.get_demangled_symbol("core::fmt::write")
+            .expect("core::fmt::write not found");
+        assert!(write.name.starts_with("_ZN4core3fmt5write17h"));
+        assert_eq!(write.demangled().as_deref(), Some("core::fmt::write"));
+
+        assert_eq!(symbols::demangle("_RNvCs1234_7mycrate3foo").as_deref(), Some("mycrate::foo"));
+        assert_eq!(symbols::demangle("_ZNSt6vectorIiSaIiEE9push_backERKi").as_deref(),
+            Some("std::vector<int, std::allocator<int> >::push_back(int const&)"));
+        assert_eq!(symbols::demangle("main"), None);
+    }
+
+    #[test]
+    fn test_toolchain_info() {
+        use toolchain::{Language, Linker};
+
+        let c = elf::Elf::open("./testbins/test.bin").unwrap()
+            .toolchain_info().expect("failed to fingerprint test.bin");
+        assert_eq!(c.languages, vec![Language::C]);
+        assert_eq!(c.linker, Some(Linker::Bfd));
+        assert!(c.producers[0].starts_with("GNU C17 14.2.1"));
+        assert_eq!(c.properties, vec!["x86-64-baseline"]);
+
+        let rust = elf::Elf::open("./testbins/rustbin").unwrap()
+            .toolchain_info().expect("failed to fingerprint rustbin");
+        assert_eq!(rust.languages, vec![Language::Rust]);
+        assert_eq!(rust.rustc_version.as_deref(), Some("1.81.0"));
+
+        let go = elf::Elf::open("./testbins/gobinary").unwrap()
+            .toolchain_info().expect("failed to fingerprint gobinary");
+        assert_eq!(go.languages, vec![Language::Go]);
+        assert_eq!(go.linker, Some(Linker::Go));
+        assert_eq!(go.go_version.as_deref(), Some("go1.23.1"));
+    }
+
+    #[test]
+    fn test_rust_metadata() {
+        let path = "./testbins/rustbin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        assert!(elf.auditable_deps().unwrap().is_none());
+        let locations = elf.panic_locations().expect("failed to scan for locations");
+        assert!(locations.iter().any(|l| l.file.ends_with("core/src/str/pattern.rs")
+            && (l.line, l.column) == (1770, 73)));
+
+        let json = br#"{"packages":[{"name":"app","version":"0.1.0","source":"local","dependencies":[1],"root":true},
+            {"name":"cc","version":"1.1.0","source":"crates.io","kind":"build","dependencies":[]}]}"#;
+        let deps = rustinfo::AuditableDeps::parse(&miniz_oxide::deflate::compress_to_vec_zlib(json, 6))
+            .expect("failed to parse .dep-v0");
+        assert_eq!(deps.root().unwrap().dependencies, vec![1]);
+        assert_eq!(deps.packages[1].kind, rustinfo::DependencyKind::Build);
+
+        let location = rustinfo::PanicLocation {
+            addr: 0,
+            file: "/root/.cargo/registry/src/index.crates.io-6f17d22bba15001f/gimli-0.29.0/src/read/abbrev.rs".to_string(),
+            line: 1,
+            column: 1,
+        };
+        assert_eq!(location.registry_crate(), Some(("gimli".to_string(), "0.29.0".to_string())));
+    }
+
+    #[test]
+    fn test_sbom() {
+        let path = "./testbins/gobinary";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let mut sbom = elf.sbom(path).expect("failed to build sbom");
+        sbom.created = time::UNIX_EPOCH + time::Duration::from_secs(1700000000);
+        assert_eq!(sbom.main().name, "gobinary");
+        let stdlib = sbom.find("stdlib").expect("no Go stdlib component");
+        assert_eq!(stdlib.purl.as_deref(), Some("pkg:golang/stdlib@1.23.1"));
+
+        let bom: serde_json::Value = serde_json::from_str(&sbom.to_cyclonedx()).unwrap();
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["metadata"]["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(bom["metadata"]["component"]["hashes"][0]["content"], sbom.sha256.as_str());
+
+        let spdx: serde_json::Value = serde_json::from_str(&sbom.to_spdx()).unwrap();
+        assert_eq!(spdx["packages"].as_array().unwrap().len(), sbom.components.len());
+        assert_eq!(spdx["relationships"][1]["relationshipType"], "DEPENDS_ON");
+
+        let path = "./testbins/test.bin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let sbom = elf.sbom(path).expect("failed to build sbom");
+        assert_eq!(sbom.main().name, "test.bin");
+        let libc = sbom.find("libc.so.6").expect("no libc component");
+        assert_eq!(libc.source, "DT_NEEDED");
+        let versions = libc.properties.iter()
+            .find(|(k, _)| k == "elf:required-versions")
+            .map(|(_, v)| v.as_str());
+        assert_eq!(versions, Some("GLIBC_2.4,GLIBC_2.2.5,GLIBC_2.34"));
+        assert_eq!(sbom.main().dependencies, [sbom.components.iter().position(|c| c.name == "libc.so.6").unwrap()]);
+
+        // A malformed verneed table only loses the required versions.
+        let verneed = elf.get_section_by_name(".gnu.version_r").unwrap().offset;
+        let mut data = elf.as_bytes().to_vec();
+        data[verneed + 8..verneed + 12].copy_from_slice(&u32::MAX.to_le_bytes()); // vn_aux
+        let elf = elf::Elf::from_bytes(data).unwrap();
+        assert!(elf.get_version_requirements().is_err());
+        let sbom = elf.sbom(path).expect("failed to build sbom");
+        let libc = sbom.find("libc.so.6").expect("no libc component");
+        assert!(libc.properties.is_empty());
+    }
+
+    #[test]
+    fn test_package_metadata() {
+        let path = "./testbins/rustbin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+        assert!(elf.package_metadata().unwrap().is_none());
+
+        let mut package = package::PackageMetadata::new("rpm", "frieren");
+        package.version = Some("0.1.0-1.fc40".to_string());
+        package.architecture = Some("x86_64".to_string());
+        package.os = Some("fedora".to_string());
+        package.os_version = Some("40".to_string());
+
+        let elf = elf::Elf::from_bytes(elf.with_package_metadata(&package).unwrap()).unwrap();
+        assert_eq!(elf.package_metadata().unwrap().as_ref(), Some(&package));
+        assert_eq!(package.purl(), "pkg:rpm/fedora/frieren@0.1.0-1.fc40?arch=x86_64&distro=fedora-40");
+
+        // A shorter note is rewritten in place.
+        package.os_version = None;
+        let bytes = elf.with_package_metadata(&package).unwrap();
+        assert_eq!(bytes.len(), elf.as_bytes().len());
+        let elf = elf::Elf::from_bytes(bytes).unwrap();
+        assert_eq!(elf.package_metadata().unwrap().as_ref(), Some(&package));
+
+        package.debug_info_url = Some("https://debuginfod.fedoraproject.org/".to_string());
+        let elf = elf::Elf::from_bytes(elf.with_package_metadata(&package).unwrap()).unwrap();
+        assert_eq!(elf.package_metadata().unwrap().as_ref(), Some(&package));
+        assert_eq!(elf.sbom(path).unwrap().main().name, "frieren");
+    }
+
+    #[test]
+    fn test_allocated_package_note() {
+        // Linked with ld --package-metadata, the note is in the loaded image.
+        let path = "./testbins/packaged";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let note = elf.get_note(package::PACKAGE_NOTE_NAME, package::NT_FDO_PACKAGING_METADATA)
+            .unwrap().expect("no package note");
+        assert_eq!(note.offset, elf.get_section_by_name(".note.package").unwrap().offset);
+        let mut package = elf.package_metadata().unwrap().unwrap();
+        assert_eq!((package.name.as_str(), package.version.as_deref()), ("frieren", Some("0.1.0-1.fc40")));
+        assert_eq!(package.os_cpe.as_deref(), Some("cpe:/o:fedoraproject:fedora:40"));
+
+        package.debug_info_url = Some("https://debuginfod.fedoraproject.org/".to_string());
+        let patched = elf::Elf::from_bytes(elf.with_package_metadata(&package).unwrap()).unwrap();
+        assert_eq!(patched.package_metadata().unwrap().as_ref(), Some(&package));
+
+        let names: Vec<&str> = patched.iter_sections()
+            .map(|s| s.name.as_str())
+            .filter(|name| name.starts_with(".note.package"))
+            .collect();
+        assert_eq!(names, [".note.package.old", ".note.package"]);
+        let old = patched.get_section_by_name(".note.package.old").unwrap();
+        assert_eq!(old.addr, elf.get_section_by_name(".note.package").unwrap().addr);
+    }
+
+    #[test]
+    fn test_kernel_module() {
+        let path = "./testbins/frieren.ko";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        let module = elf.kernel_module().unwrap().expect("not a kernel module");
+        assert_eq!(module.name(), Some("frieren"));
+        assert_eq!(module.modinfo.license(), Some("GPL"));
+        assert_eq!(module.modinfo.depends(), ["libcrc32c", "ptp"]);
+        assert_eq!(module.modinfo.aliases().len(), 2);
+        let count = module.modinfo.parameters().into_iter().find(|p| p.name == "count").unwrap();
+        assert_eq!(count.typ.as_deref(), Some("int"));
+        assert_eq!(count.description.as_deref(), Some("Number of widgets"));
+
+        assert!(module.versions.contains(&kmod::ModVersion { name: "_printk".to_string(), crc: 0x92997ed8 }));
+        let this_module = module.this_module.unwrap();
+        assert_eq!(this_module.init.as_deref(), Some("frieren_init"));
+        assert_eq!(this_module.exit.as_deref(), Some("frieren_exit"));
+
+        let register = module.exports.iter().find(|e| e.name == "frieren_register").unwrap();
+        assert!(register.gpl);
+        assert_eq!(register.namespace.as_deref(), Some("FRIEREN"));
+
+        // A symbol value that overflows the namespace field address.
+        let symtab = elf.get_section_by_name(".symtab").unwrap().offset;
+        let index = elf.object_symbols(elf.section_index(".symtab").unwrap()).unwrap()
+            .iter().position(|s| s.name == "__ksymtab_frieren_register").unwrap();
+        let mut data = elf.as_bytes().to_vec();
+        let value = symtab + index * 24 + 8;
+        data[value..value + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let exports = elf::Elf::from_bytes(data).unwrap().module_exports().unwrap();
+        assert!(exports.iter().find(|e| e.name == "frieren_register").unwrap().namespace.is_none());
+
+        let sig = module.signature.expect("module is not signed");
+        assert_eq!(sig.signer(), Some("Build time autogenerated kernel key"));
+        assert_eq!(sig.hash_algorithm.as_deref(), Some("sha256"));
+        assert_eq!(sig.signature_algorithm.as_deref(), Some("rsaEncryption"));
+        assert!(elf::Elf::from_bytes(elf.as_bytes()[..sig.module_len].to_vec()).unwrap()
+            .module_signature().unwrap().is_none());
+    }
+
+    //#[test]
+    fn test_dynamic_section() {
+        let path = "./testbins/rustbin";
+        let elf = elf::Elf::open(path)
+            .expect("failed to open {path}");
+
+        for d in elf.dynamic {
+            println!("{:?}: {:#x}", d.tag, d.val);
+        }
+
     }
 }
 
@@ -0,0 +1,247 @@
+use std::{
+    ffi::CStr,
+    fmt,
+    io::*,
+    ptr,
+};
+
+use crate::dynamic::EntryType;
+use crate::elf::Elf;
+use crate::image::ProcessImage;
+
+/* Upper bound on the number of link_map entries, guards against cycles */
+const MAX_LINK_MAPS: usize = 4096;
+
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct RawRDebug {
+    pub r_version: i32,
+    pub r_map: usize,
+    pub r_brk: usize,
+    pub r_state: i32,
+    pub r_ldbase: usize,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+pub struct RawLinkMap {
+    pub l_addr: usize,
+    pub l_name: usize,
+    pub l_ld: usize,
+    pub l_next: usize,
+    pub l_prev: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, FromPrimitive)]
+pub enum RState {
+    Consistent = 0, /* Mapping change is complete */
+    Add,            /* Beginning to add a new object */
+    Delete,         /* Beginning to remove an object mapping */
+}
+
+/// The dynamic linker's `struct r_debug`, the rendezvous structure debuggers
+/// use to find the list of loaded objects.
+#[derive(Debug, Clone)]
+pub struct RDebug {
+    addr: usize,
+    version: i32,
+    map: usize,
+    brk: usize,
+    state: Option<RState>,
+    ldbase: usize,
+}
+
+/// An entry of the dynamic linker's list of loaded objects, as it was when
+/// the list was walked.
+#[derive(Debug, Clone)]
+pub struct LinkMap {
+    addr: usize,
+    bias: usize,
+    name: String,
+    ld: usize,
+    next: usize,
+    prev: usize,
+}
+
+impl fmt::Display for LinkMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#014x} {:#014x} {}", self.bias, self.ld, self.name)
+    }
+}
+
+impl RDebug {
+    /// Locates `r_debug` through the DT_DEBUG entry of the executable's
+    /// dynamic section, falling back to the loader's `_r_debug` symbol.
+    pub fn this() -> Result<Self> {
+        let exe = ProcessImage::this()?;
+        // The executable is never unloaded.
+        let debug = unsafe { exe.dynamic()? }
+            .into_iter()
+            .find(|d| matches!(d.tag, EntryType::Debug))
+            .map(|d| d.val)
+            .filter(|&val| val != 0);
+
+        let addr = match debug {
+            Some(addr) => addr,
+            None => {
+                let sym = unsafe { libc::dlsym(libc::RTLD_DEFAULT, c"_r_debug".as_ptr()) };
+                if sym.is_null() {
+                    return Err(Error::other("failed to locate r_debug"));
+                }
+                sym as usize
+            },
+        };
+
+        unsafe { RDebug::read(addr) }
+    }
+
+    /// Reads the `struct r_debug` at `addr`.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must point to a valid `struct r_debug` in this process.
+    pub unsafe fn read(addr: usize) -> Result<Self> {
+        let raw = ptr::read(addr as *const RawRDebug);
+
+        if raw.r_version < 1 {
+            return Err(Error::other(
+                    format!("unexpected r_debug version {}", raw.r_version)));
+        }
+
+        Ok(RDebug {
+            addr,
+            version: raw.r_version,
+            map: raw.r_map,
+            brk: raw.r_brk,
+            state: num_traits::FromPrimitive::from_i32(raw.r_state),
+            ldbase: raw.r_ldbase,
+        })
+    }
+
+    /// Address of the structure itself.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Address of the first `link_map` entry.
+    pub fn map(&self) -> usize {
+        self.map
+    }
+
+    pub fn brk(&self) -> usize {
+        self.brk
+    }
+
+    pub fn state(&self) -> Option<RState> {
+        self.state
+    }
+
+    pub fn ldbase(&self) -> usize {
+        self.ldbase
+    }
+
+    /// Walks the `link_map` list. The list is only stable while `state` is
+    /// `RState::Consistent`, i.e. no dlopen/dlclose is in progress.
+    pub fn link_maps(&self) -> Result<Vec<LinkMap>> {
+        let mut maps = Vec::new();
+        let mut next = self.map;
+
+        while next != 0 {
+            if maps.len() >= MAX_LINK_MAPS {
+                return Err(Error::other("link_map list does not terminate"));
+            }
+
+            let raw = unsafe { ptr::read(next as *const RawLinkMap) };
+            let name = if raw.l_name == 0 {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(raw.l_name as *const libc::c_char) }
+                    .to_string_lossy()
+                    .into_owned()
+            };
+
+            maps.push(LinkMap {
+                addr: next,
+                bias: raw.l_addr,
+                name,
+                ld: raw.l_ld,
+                next: raw.l_next,
+                prev: raw.l_prev,
+            });
+            next = raw.l_next;
+        }
+
+        Ok(maps)
+    }
+}
+
+impl LinkMap {
+    /// Address of the `struct link_map` itself.
+    pub fn addr(&self) -> usize {
+        self.addr
+    }
+
+    /// Load bias of the object.
+    pub fn bias(&self) -> usize {
+        self.bias
+    }
+
+    /// Path of the object, empty for the executable.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Runtime address of the object's dynamic section.
+    pub fn ld(&self) -> usize {
+        self.ld
+    }
+
+    pub fn next(&self) -> usize {
+        self.next
+    }
+
+    pub fn prev(&self) -> usize {
+        self.prev
+    }
+
+    /// Returns the loaded image of the object. The executable is located
+    /// through the auxiliary vector, other objects are assumed to be linked
+    /// at address 0, as shared objects are, with their ELF header at the bias.
+    ///
+    /// # Safety
+    ///
+    /// The object must not have been unloaded by dlclose(3) since the list
+    /// was walked.
+    pub unsafe fn image(&self) -> Result<ProcessImage> {
+        let exe = ProcessImage::this()?;
+        if exe.dynamic_addr() == Some(self.ld) {
+            return Ok(exe);
+        }
+
+        let image = ProcessImage::from_base(self.name.clone(), self.bias)?;
+        if image.dynamic_addr() != Some(self.ld) {
+            return Err(Error::other(
+                    format!("'{}' is not linked at address 0", self.name)));
+        }
+
+        Ok(image)
+    }
+
+    /// Parses the object from its in-memory image.
+    ///
+    /// # Safety
+    ///
+    /// The object must still be loaded, as for `image`.
+    pub unsafe fn to_elf(&self) -> Result<Elf> {
+        self.image()?.to_elf()
+    }
+}
+
+/// Lists every object loaded into the process, executable first.
+pub fn loaded_objects() -> Result<Vec<LinkMap>> {
+    RDebug::this()?.link_maps()
+}
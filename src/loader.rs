@@ -0,0 +1,333 @@
+use std::{
+    io::*,
+    ptr,
+};
+
+use crate::dynamic::EntryType;
+use crate::elf::Elf;
+use crate::header::ElfType;
+use crate::link_map;
+use crate::relocation::*;
+use crate::segment::{Segment, SegmentFlag, SegmentType};
+use crate::symbols::{SymbolBinding, SymbolType};
+
+const EM_X86_64: u16 = 62;
+
+type InitFn = unsafe extern "C" fn();
+type IfuncResolver = unsafe extern "C" fn() -> usize;
+
+/// A shared object mapped and relocated by `load`, without going through
+/// the dynamic loader. Dropping it runs the finalizers and unmaps it.
+#[derive(Debug)]
+pub struct LoadedObject {
+    /// Start of the mapping.
+    base: usize,
+    size: usize,
+    bias: usize,
+    elf: Elf,
+    /// Set once the initializers ran, only then are the finalizers run.
+    initialized: bool,
+}
+
+/// Loads the shared object in `data`. Imports are resolved against the
+/// objects already loaded into the process, which must include everything
+/// in its DT_NEEDED list. Only x86_64 objects without TLS are supported.
+///
+/// # Safety
+///
+/// This runs code from `data`: IFUNC resolvers, DT_INIT and DT_INIT_ARRAY,
+/// and DT_FINI and DT_FINI_ARRAY once the object is dropped. The object
+/// must be trusted not to break the invariants of the process, and must
+/// not depend on being loaded by the dynamic loader, e.g. through dlopen(3)
+/// or by having its own TLS.
+pub unsafe fn load(data: Vec<u8>) -> Result<LoadedObject> {
+    let mut elf = Elf::from_bytes(data)?;
+
+    // Without section headers from_bytes stops at the program headers.
+    if elf.dynamic.is_empty() {
+        elf.parse_dynamic_segment()?;
+        elf.parse_dynsym()?;
+    }
+
+    if elf.header().e_type != ElfType::Dynamic as u16 {
+        return Err(Error::other("not a shared object"));
+    }
+    if elf.header().e_machine != EM_X86_64 {
+        return Err(Error::other(
+                format!("unsupported machine {}", elf.header().e_machine)));
+    }
+    if elf.get_segments_by_type(SegmentType::Tls).next().is_some() {
+        return Err(Error::other("objects with TLS are not supported"));
+    }
+    if elf.get_segments_by_type(SegmentType::Dynamic).next().is_none() {
+        return Err(Error::other("no PT_DYNAMIC segment"));
+    }
+    if elf.get_dynamic(EntryType::Relr).is_some() {
+        return Err(Error::other("DT_RELR relocations are not supported"));
+    }
+
+    let mut obj = map_segments(elf)?;
+
+    obj.relocate()?;
+    obj.protect()?;
+    obj.run_init();
+    obj.initialized = true;
+
+    Ok(obj)
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Reserves the whole address range of the object, then copies in the file
+/// backed part of every PT_LOAD. The rest of each segment (.bss) stays zero.
+unsafe fn map_segments(elf: Elf) -> Result<LoadedObject> {
+    let page = page_size();
+    let loads: Vec<&Segment> = elf.get_segments_by_type(SegmentType::Load).collect();
+
+    let start = loads.iter().map(|s| s.vaddr).min()
+        .ok_or_else(|| Error::other("no PT_LOAD segment"))? & !(page - 1);
+    let end = loads.iter().map(|s| s.vaddr + s.memsz).max().unwrap_or(start);
+    let size = (end - start + page - 1) & !(page - 1);
+
+    let base = libc::mmap(ptr::null_mut(), size, libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0);
+    if base == libc::MAP_FAILED {
+        return Err(Error::last_os_error());
+    }
+    let base = base as usize;
+    let bias = base - start;
+
+    let data = elf.as_bytes();
+    for seg in loads {
+        let src = match data.get(seg.offset..seg.offset + seg.filesz) {
+            Some(src) => src,
+            None => {
+                libc::munmap(base as *mut libc::c_void, size);
+                return Err(Error::other(
+                        format!("PT_LOAD at {:#x} extends past the end of the file", seg.offset)));
+            },
+        };
+        ptr::copy_nonoverlapping(src.as_ptr(), (bias + seg.vaddr) as *mut u8, src.len());
+    }
+
+    Ok(LoadedObject { base, size, bias, elf, initialized: false })
+}
+
+impl LoadedObject {
+    pub fn elf(&self) -> &Elf {
+        &self.elf
+    }
+
+    /// Returns the start of the mapping.
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    /// Returns the size of the mapping.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the difference between runtime and file addresses.
+    pub fn bias(&self) -> usize {
+        self.bias
+    }
+
+    /// Returns the address of the exported symbol `name`, like dlsym(3).
+    ///
+    /// # Safety
+    ///
+    /// The resolver of an IFUNC symbol is called to get its address. The
+    /// returned pointer is only valid while the object is loaded, and must
+    /// be cast to the actual type of the symbol before it is used.
+    pub unsafe fn dlsym(&self, name: &str) -> Option<*const ()> {
+        let sym = self.elf.lookup_dynamic_symbol(name).ok()??;
+        let addr = self.bias + sym.value;
+
+        if sym.symbol_type == SymbolType::GnuIfunc {
+            return Some(std::mem::transmute::<usize, IfuncResolver>(addr)() as *const ());
+        }
+        Some(addr as *const ())
+    }
+
+    /// Returns true if `addr` lies within the object's mapping.
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.base && addr < self.base + self.size
+    }
+
+    unsafe fn relocate(&mut self) -> Result<()> {
+        let mut relocs = self.elf.get_dynamic_relocations()?;
+        relocs.extend(self.elf.get_plt_relocations()?);
+
+        let needs_imports = relocs.iter()
+            .filter_map(|r| self.elf.get_relocation_symbol(r))
+            .any(|s| s.shndx == 0);
+        let modules = match needs_imports {
+            true => loaded_modules()?,
+            false => Vec::new(),
+        };
+
+        // IRELATIVE resolvers may depend on other relocations, run them last.
+        let (irelative, relocs): (Vec<_>, Vec<_>) = relocs.into_iter()
+            .partition(|r| r.typ == R_X86_64_IRELATIVE);
+
+        for reloc in relocs.iter().chain(irelative.iter()) {
+            let target = (self.bias + reloc.offset) as *mut usize;
+            let addend = reloc.addend.unwrap_or_else(|| *target as i64);
+
+            let value = match reloc.typ {
+                R_X86_64_NONE => continue,
+                R_X86_64_RELATIVE => self.bias.wrapping_add_signed(addend as isize),
+                R_X86_64_64 => self.resolve(reloc, &modules)?.wrapping_add_signed(addend as isize),
+                R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT => self.resolve(reloc, &modules)?,
+                R_X86_64_IRELATIVE => {
+                    let resolver: IfuncResolver =
+                        std::mem::transmute(self.bias.wrapping_add_signed(addend as isize));
+                    resolver()
+                },
+                _ => return Err(Error::other(format!("unsupported relocation type {} at {:#x}",
+                        reloc.type_name(EM_X86_64).unwrap_or("?"), reloc.offset))),
+            };
+
+            *target = value;
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the symbol of a relocation. Definitions within the object
+    /// take precedence, as if it was linked with -Bsymbolic.
+    unsafe fn resolve(&self, reloc: &Relocation, modules: &[(usize, Elf)]) -> Result<usize> {
+        let sym = match self.elf.get_relocation_symbol(reloc) {
+            Some(s) => s,
+            None => return Ok(0),
+        };
+
+        if sym.shndx != 0 {
+            let addr = self.bias + sym.value;
+            if sym.symbol_type == SymbolType::GnuIfunc {
+                return Ok(std::mem::transmute::<usize, IfuncResolver>(addr)());
+            }
+            return Ok(addr);
+        }
+
+        let version = self.elf.get_symbol_version(reloc.symbol as usize)?
+            .map(|(version, _)| version);
+
+        for (bias, elf) in modules {
+            if let Some(def) = elf.lookup_versioned_symbol(&sym.name, version.as_deref())? {
+                let addr = bias + def.value;
+                if def.symbol_type == SymbolType::GnuIfunc {
+                    return Ok(std::mem::transmute::<usize, IfuncResolver>(addr)());
+                }
+                return Ok(addr);
+            }
+        }
+
+        match sym.binding {
+            SymbolBinding::Weak => Ok(0),
+            _ => Err(Error::other(format!("undefined symbol: {}", sym.name))),
+        }
+    }
+
+    /// Applies the final protections of each segment and of PT_GNU_RELRO.
+    unsafe fn protect(&self) -> Result<()> {
+        let page = page_size();
+
+        for seg in self.elf.iter_segments() {
+            let prot = if seg.is_type(SegmentType::Load) {
+                segment_prot(seg)
+            } else if seg.is_type(SegmentType::GnuRelro) {
+                libc::PROT_READ
+            } else {
+                continue;
+            };
+
+            let start = (self.bias + seg.vaddr) & !(page - 1);
+            let mut end = self.bias + seg.vaddr + seg.memsz;
+            end = match seg.is_type(SegmentType::GnuRelro) {
+                true => end & !(page - 1),
+                false => (end + page - 1) & !(page - 1),
+            };
+
+            if end > start && libc::mprotect(start as *mut libc::c_void, end - start, prot) != 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn init_array(&self, array: EntryType, size: EntryType) -> Vec<usize> {
+        match (self.elf.get_dynamic(array), self.elf.get_dynamic(size)) {
+            (Some(vaddr), Some(size)) => {
+                let entries = unsafe {
+                    std::slice::from_raw_parts((self.bias + vaddr) as *const usize, size / 8)
+                };
+                entries.iter()
+                    .copied()
+                    .filter(|&f| f != 0 && f != usize::MAX)
+                    .collect()
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    unsafe fn run_init(&self) {
+        if let Some(init) = self.elf.get_dynamic(EntryType::Init) {
+            std::mem::transmute::<usize, InitFn>(self.bias + init)();
+        }
+        // The array entries were relocated already.
+        for f in self.init_array(EntryType::InitArray, EntryType::InitArraySize) {
+            std::mem::transmute::<usize, InitFn>(f)();
+        }
+    }
+
+    unsafe fn run_fini(&self) {
+        for f in self.init_array(EntryType::FiniArray, EntryType::FiniArraySize).into_iter().rev() {
+            std::mem::transmute::<usize, InitFn>(f)();
+        }
+        if let Some(fini) = self.elf.get_dynamic(EntryType::Fini) {
+            std::mem::transmute::<usize, InitFn>(self.bias + fini)();
+        }
+    }
+}
+
+impl Drop for LoadedObject {
+    fn drop(&mut self) {
+        unsafe {
+            // A failed load leaves the object partly relocated.
+            if self.initialized {
+                self.run_fini();
+            }
+            libc::munmap(self.base as *mut libc::c_void, self.size);
+        }
+    }
+}
+
+fn segment_prot(seg: &Segment) -> libc::c_int {
+    let mut prot = libc::PROT_NONE;
+    if seg.has_flag(SegmentFlag::Read) {
+        prot |= libc::PROT_READ;
+    }
+    if seg.has_flag(SegmentFlag::Write) {
+        prot |= libc::PROT_WRITE;
+    }
+    if seg.has_flag(SegmentFlag::Execute) {
+        prot |= libc::PROT_EXEC;
+    }
+    prot
+}
+
+/// Parses the objects loaded by the dynamic loader, in lookup order.
+fn loaded_modules() -> Result<Vec<(usize, Elf)>> {
+    let mut modules = Vec::new();
+    for map in link_map::loaded_objects()? {
+        // Just found in the list of loaded objects.
+        let image = unsafe { map.image()? };
+        modules.push((image.bias(), unsafe { image.to_elf()? }));
+    }
+    Ok(modules)
+}
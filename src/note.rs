@@ -0,0 +1,139 @@
+use std::{
+    fmt,
+    io::*,
+};
+
+use crate::elf::Elf;
+use crate::section::SectionType;
+use crate::segment::SegmentType;
+use crate::utils::*;
+
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct Nhdr {
+    pub n_namesz: u32,
+    pub n_descsz: u32,
+    pub n_type: u32,
+}
+
+/// A single entry of a PT_NOTE segment or SHT_NOTE section.
+#[derive(Debug, Clone)]
+pub struct Note<'a> {
+    pub name: String,
+    pub typ: u32,
+    pub desc: &'a [u8],
+    /// Offset of the note header, in the file for notes returned by `Elf`
+    /// and in the parsed data for `parse_notes`.
+    pub offset: usize,
+}
+
+impl fmt::Display for Note<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} type={:#x} descsz={:#x}", self.name, self.typ, self.desc.len())
+    }
+}
+
+/// Parses the notes contained in `data`. `align` is the alignment of the
+/// containing segment or section, 8 byte aligned notes (e.g. GNU properties)
+/// pad the descriptor to 8 bytes.
+pub fn parse_notes(data: &[u8], align: usize) -> Result<Vec<Note<'_>>> {
+    let align = if align == 8 { 8 } else { 4 };
+    let mut notes = Vec::new();
+    let mut r = Reader::new(data);
+
+    while r.remaining() >= 12 {
+        let offset = r.pos();
+        let namesz = r.u32()? as usize;
+        let descsz = r.u32()? as usize;
+        let typ = r.u32()?;
+
+        let name = r.bytes(namesz)?;
+        let name = match name.iter().position(|&b| b == 0) {
+            Some(len) => &name[..len],
+            None => name,
+        };
+        r.seek(align_up(r.pos(), align));
+
+        let desc = r.bytes(descsz)?;
+        r.seek(align_up(r.pos(), align));
+
+        notes.push(Note {
+            name: String::from_utf8_lossy(name).into_owned(),
+            typ,
+            desc,
+            offset,
+        });
+    }
+
+    Ok(notes)
+}
+
+/// Makes the offsets of notes parsed from the file data at `base` relative
+/// to the start of the file.
+fn in_file(notes: Vec<Note<'_>>, base: usize) -> impl Iterator<Item = Note<'_>> {
+    notes.into_iter().map(move |n| Note { offset: base + n.offset, ..n })
+}
+
+fn align_up(val: usize, align: usize) -> usize {
+    (val + align - 1) & !(align - 1)
+}
+
+impl Elf {
+    /// Returns all notes in the file. PT_NOTE segments are used if present,
+    /// as they are all a core file has, otherwise SHT_NOTE sections.
+    pub fn get_notes(&self) -> Result<Vec<Note<'_>>> {
+        let notes = self.segment_notes()?;
+        if !notes.is_empty() {
+            return Ok(notes);
+        }
+
+        self.section_notes(false)
+    }
+
+    fn segment_notes(&self) -> Result<Vec<Note<'_>>> {
+        let mut notes = Vec::new();
+        let data = self.as_bytes();
+
+        for seg in self.get_segments_by_type(SegmentType::Note) {
+            let bytes = match seg.offset.checked_add(seg.filesz) {
+                Some(end) if end <= data.len() => &data[seg.offset..end],
+                _ => return Err(Error::other(
+                        format!("PT_NOTE at {:#x} extends past the end of the file", seg.offset))),
+            };
+            notes.extend(in_file(parse_notes(bytes, seg.align)?, seg.offset));
+        }
+
+        Ok(notes)
+    }
+
+    /// Parses the SHT_NOTE sections, only those outside of every PT_NOTE
+    /// segment if `uncovered` is set.
+    fn section_notes(&self, uncovered: bool) -> Result<Vec<Note<'_>>> {
+        let mut notes = Vec::new();
+        for section in self.get_section_by_type(SectionType::Note) {
+            let covered = self.get_segments_by_type(SegmentType::Note).any(|seg| {
+                section.offset >= seg.offset
+                    && section.offset.saturating_add(section.size) <= seg.offset.saturating_add(seg.filesz)
+            });
+            if uncovered && covered {
+                continue;
+            }
+
+            let bytes = self.get_section_contents(section)?;
+            notes.extend(in_file(parse_notes(bytes, section.addralign)?, section.offset));
+        }
+
+        Ok(notes)
+    }
+
+    /// Returns the first note matching `name` and `typ`. Note sections that
+    /// are not part of any PT_NOTE segment are searched as well.
+    pub fn get_note(&self, name: &str, typ: u32) -> Result<Option<Note<'_>>> {
+        let matches = |n: &Note| n.name == name && n.typ == typ;
+        if let Some(note) = self.segment_notes()?.into_iter().find(matches) {
+            return Ok(Some(note));
+        }
+
+        Ok(self.section_notes(true)?.into_iter().find(matches))
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    fmt,
+    fs,
+    io::*,
+};
+
+use serde_json::{Map, Value};
+
+use crate::elf::Elf;
+use crate::sbom::purl_encode;
+use crate::section::{SectionFlag, SectionType, Shdr};
+use crate::symtab::{append, to_shdr, write_section_headers, StringTable};
+
+/* .note.package, https://systemd.io/ELF_PACKAGE_METADATA/ */
+pub const PACKAGE_NOTE_NAME: &str = "FDO";
+pub const NT_FDO_PACKAGING_METADATA: u32 = 0xcafe1a7e;
+const PACKAGE_SECTION: &str = ".note.package";
+const OLD_PACKAGE_SECTION: &str = ".note.package.old";
+
+/* Nhdr plus "FDO\0" */
+const NOTE_HEADER_SIZE: usize = 16;
+
+/// The package metadata systemd and the distributions embed in
+/// `.note.package`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PackageMetadata {
+    /// "rpm", "deb", "apk", "alpm" or similar.
+    pub typ: String,
+    pub name: String,
+    pub version: Option<String>,
+    pub architecture: Option<String>,
+    /// ID from os-release, e.g. "fedora".
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    pub os_cpe: Option<String>,
+    pub debug_info_url: Option<String>,
+    /// Fields not covered above, kept as they are.
+    pub extra: Map<String, Value>,
+}
+
+impl PackageMetadata {
+    pub fn new(typ: &str, name: &str) -> Self {
+        PackageMetadata { typ: typ.to_string(), name: name.to_string(), ..Default::default() }
+    }
+
+    /// Parses the JSON descriptor of the note. Trailing padding is ignored.
+    pub fn parse(desc: &[u8]) -> Result<Self> {
+        let json = desc.split(|&b| b == 0).next().unwrap_or(&[]);
+        let json: Value = serde_json::from_slice(json)
+            .map_err(|e| Error::other(format!("invalid package note: {}", e)))?;
+        let mut fields = match json {
+            Value::Object(fields) => fields,
+            _ => return Err(Error::other("package note is not a JSON object")),
+        };
+
+        let mut take = |key: &str| match fields.remove(key) {
+            Some(Value::String(s)) => Some(s),
+            Some(other) => {
+                // Keep what we do not understand.
+                fields.insert(key.to_string(), other);
+                None
+            },
+            None => None,
+        };
+
+        Ok(PackageMetadata {
+            typ: take("type").unwrap_or_default(),
+            name: take("name").ok_or_else(|| Error::other("package note has no name"))?,
+            version: take("version"),
+            architecture: take("architecture"),
+            os: take("os"),
+            os_version: take("osVersion"),
+            os_cpe: take("osCpe"),
+            debug_info_url: take("debugInfoUrl"),
+            extra: fields,
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut fields = self.extra.clone();
+        let mut put = |key: &str, value: Option<&String>| {
+            if let Some(value) = value {
+                fields.insert(key.to_string(), Value::String(value.clone()));
+            }
+        };
+        put("type", Some(&self.typ));
+        put("name", Some(&self.name));
+        put("version", self.version.as_ref());
+        put("architecture", self.architecture.as_ref());
+        put("os", self.os.as_ref());
+        put("osVersion", self.os_version.as_ref());
+        put("osCpe", self.os_cpe.as_ref());
+        put("debugInfoUrl", self.debug_info_url.as_ref());
+        Value::Object(fields).to_string()
+    }
+
+    /// Returns the note, including its header, as it is laid out in the
+    /// file. The JSON is NUL terminated and padded to 4 bytes.
+    pub fn to_note(&self) -> Vec<u8> {
+        let mut desc = self.to_json().into_bytes();
+        desc.push(0);
+        desc.resize(desc.len().next_multiple_of(4), 0);
+
+        let mut note = Vec::with_capacity(NOTE_HEADER_SIZE + desc.len());
+        note.extend_from_slice(&(PACKAGE_NOTE_NAME.len() as u32 + 1).to_le_bytes());
+        note.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+        note.extend_from_slice(&NT_FDO_PACKAGING_METADATA.to_le_bytes());
+        note.extend_from_slice(PACKAGE_NOTE_NAME.as_bytes());
+        note.push(0);
+        note.extend_from_slice(&desc);
+        note
+    }
+
+    /// Package URL, e.g. "pkg:rpm/fedora/bash@5.2.26-3.fc40?arch=x86_64".
+    pub fn purl(&self) -> String {
+        let name = purl_encode(&self.name);
+        let os = self.os.as_deref().unwrap_or("");
+
+        let mut purl = match self.typ.as_str() {
+            typ @ ("rpm" | "deb" | "apk" | "alpm") => format!("pkg:{}/{}/{}", typ, purl_encode(os), name),
+            _ => format!("pkg:generic/{}", name),
+        };
+        if let Some(version) = &self.version {
+            purl.push('@');
+            purl.push_str(&purl_encode(version));
+        }
+
+        let mut qualifiers = Vec::new();
+        if let Some(arch) = &self.architecture {
+            qualifiers.push(format!("arch={}", purl_encode(arch)));
+        }
+        if let Some(version) = &self.os_version {
+            qualifiers.push(format!("distro={}-{}", purl_encode(os), purl_encode(version)));
+        }
+        if !qualifiers.is_empty() {
+            purl.push('?');
+            purl.push_str(&qualifiers.join("&"));
+        }
+        purl
+    }
+}
+
+impl fmt::Display for PackageMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, "-{}", version)?;
+        }
+        if let Some(arch) = &self.architecture {
+            write!(f, ".{}", arch)?;
+        }
+        write!(f, " ({}", self.typ)?;
+        if let Some(os) = &self.os {
+            write!(f, ", {}", os)?;
+            if let Some(version) = &self.os_version {
+                write!(f, " {}", version)?;
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+impl Elf {
+    /// Decodes the FDO_PACKAGING_METADATA note, None if there is none.
+    pub fn package_metadata(&self) -> Result<Option<PackageMetadata>> {
+        match self.get_note(PACKAGE_NOTE_NAME, NT_FDO_PACKAGING_METADATA)? {
+            Some(note) => Ok(Some(PackageMetadata::parse(note.desc)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns a copy of the file carrying `metadata` in its package note.
+    ///
+    /// An existing note is overwritten in place when the new one fits, so it
+    /// stays in its PT_NOTE segment. Otherwise the old note is disabled and a
+    /// new, non-allocated `.note.package` section is appended along with new
+    /// section headers; such a note is only visible to tools reading the
+    /// section headers, not in the loaded image. An allocated `.note.package`
+    /// holding the old note is kept as `.note.package.old`.
+    pub fn with_package_metadata(&self, metadata: &PackageMetadata) -> Result<Vec<u8>> {
+        let note = metadata.to_note();
+        let mut out = self.as_bytes().to_vec();
+
+        if let Some(old) = self.get_note(PACKAGE_NOTE_NAME, NT_FDO_PACKAGING_METADATA)? {
+            let desc_off = old.offset + NOTE_HEADER_SIZE;
+            let desc = &note[NOTE_HEADER_SIZE..];
+            if desc.len() <= old.desc.len() {
+                // Keep n_descsz, the JSON is padded out with NULs.
+                out[desc_off..desc_off + old.desc.len()].fill(0);
+                out[desc_off..desc_off + desc.len()].copy_from_slice(desc);
+                return Ok(out);
+            }
+
+            // n_type, after n_namesz and n_descsz.
+            let type_off = old.offset + 8;
+            out[type_off..type_off + 4].fill(0);
+        }
+
+        let mut sections: Vec<Shdr> = self.iter_sections().map(to_shdr).collect();
+        if sections.is_empty() {
+            sections.push(Shdr::default());
+        }
+
+        // An allocated .note.package keeps its header, it still describes
+        // the segment contents, but is renamed so the new note is the only
+        // .note.package.
+        let mut shstrtab = StringTable::new();
+        for (shdr, section) in sections.iter_mut().zip(self.iter_sections()) {
+            shdr.sh_name = match section.name == PACKAGE_SECTION && section.has_flag(SectionFlag::Alloc) {
+                true => shstrtab.add(OLD_PACKAGE_SECTION),
+                false => shstrtab.add(&section.name),
+            };
+        }
+
+        let note_index = match self.get_section_by_name(PACKAGE_SECTION) {
+            Some(s) if !s.has_flag(SectionFlag::Alloc) => self.section_index(PACKAGE_SECTION),
+            _ => None,
+        };
+        let note_index = note_index.unwrap_or_else(|| {
+            sections.push(Shdr::default());
+            sections.len() - 1
+        });
+        let shstrtab_index = match self.header().e_shstrndx as usize {
+            i if i != 0 && i < self.iter_sections().count() => i,
+            _ => {
+                sections.push(Shdr::default());
+                sections.len() - 1
+            },
+        };
+
+        let offset = append(&mut out, &note, 4);
+        sections[note_index] = Shdr {
+            sh_name: shstrtab.add(PACKAGE_SECTION),
+            sh_type: SectionType::Note as u32,
+            sh_offset: offset,
+            sh_size: note.len(),
+            sh_addralign: 4,
+            ..Default::default()
+        };
+        sections[shstrtab_index].sh_name = shstrtab.add(".shstrtab");
+
+        write_section_headers(&mut out, &mut sections, &shstrtab, shstrtab_index)?;
+        Ok(out)
+    }
+
+    /// Writes a copy of the file carrying `metadata` to `path`.
+    pub fn write_with_package_metadata(&self, path: &str, metadata: &PackageMetadata) -> Result<()> {
+        fs::write(path, self.with_package_metadata(metadata)?)
+    }
+}
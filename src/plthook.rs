@@ -0,0 +1,173 @@
+use std::{
+    io::*,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::elf::Elf;
+use crate::link_map::{self, LinkMap};
+use crate::segment::{Segment, SegmentType};
+
+/// Intercepts calls a loaded module makes to an imported function, by
+/// rewriting the module's GOT entry for that function.
+#[derive(Debug)]
+pub struct PltHook {
+    name: String,
+    bias: usize,
+    elf: Elf,
+    relro: Option<(usize, usize)>,
+}
+
+impl PltHook {
+    /// Opens the loaded module whose path ends with `name`, the executable
+    /// if `name` is empty.
+    pub fn open(name: &str) -> Result<Self> {
+        let maps = link_map::loaded_objects()?;
+        let map = match name {
+            "" => maps.first(),
+            _ => maps.iter().find(|m| m.name().ends_with(name)),
+        };
+
+        match map {
+            // Just found in the list of loaded objects.
+            Some(map) => unsafe { PltHook::from_link_map(map) },
+            None => Err(Error::other(format!("'{}' is not loaded", name))),
+        }
+    }
+
+    /// Opens the module that contains `addr`, e.g. a function of it.
+    pub fn containing(addr: usize) -> Result<Self> {
+        for map in link_map::loaded_objects()? {
+            // Just found in the list of loaded objects.
+            if unsafe { map.image()? }.contains(addr) {
+                return unsafe { PltHook::from_link_map(&map) };
+            }
+        }
+
+        Err(Error::other(format!("no loaded module contains {:#x}", addr)))
+    }
+
+    /// Opens the module described by `map`.
+    ///
+    /// # Safety
+    ///
+    /// The module must not have been unloaded by dlclose(3) since `map` was
+    /// read, see [`LinkMap::image`].
+    pub unsafe fn from_link_map(map: &LinkMap) -> Result<Self> {
+        let image = unsafe { map.image()? };
+        let relro = image.iter_segments()
+            .find(|s| s.is_type(SegmentType::GnuRelro))
+            .map(|s: &Segment| (image.runtime_addr(s.vaddr), image.runtime_addr(s.vaddr + s.memsz)));
+
+        Ok(PltHook {
+            name: image.name().to_string(),
+            bias: image.bias(),
+            elf: unsafe { image.to_elf()? },
+            relro,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the difference between runtime and file addresses.
+    pub fn bias(&self) -> usize {
+        self.bias
+    }
+
+    pub fn elf(&self) -> &Elf {
+        &self.elf
+    }
+
+    /// Returns the runtime addresses of the GOT entries for `symbol`.
+    pub fn got_slots(&self, symbol: &str) -> Result<Vec<usize>> {
+        Ok(self.elf.get_got_slots(symbol)?
+            .into_iter()
+            .map(|slot| self.bias.wrapping_add(slot))
+            .collect())
+    }
+
+    /// Returns the address the module currently calls for `symbol`.
+    ///
+    /// # Safety
+    ///
+    /// The module must still be loaded, i.e. not have been unloaded by
+    /// dlclose(3) since the hook was opened.
+    pub unsafe fn get(&self, symbol: &str) -> Result<*const ()> {
+        let slot = self.first_slot(symbol)?;
+        Ok((*(slot as *const AtomicUsize)).load(Ordering::SeqCst) as *const ())
+    }
+
+    /// Makes the module call `new` instead of `symbol`, returning the previous
+    /// target so the hook can chain to it. Passing that value back to
+    /// `replace` removes the hook.
+    ///
+    /// With lazy binding the previous target may be the PLT resolver stub
+    /// rather than the function itself, it is still safe to call.
+    ///
+    /// When `symbol` has several GOT entries they must all hold the same
+    /// target, otherwise there is no single value to restore and nothing is
+    /// written.
+    ///
+    /// # Safety
+    ///
+    /// `new` must be a function with the same signature as `symbol`, and the
+    /// module must still be loaded as for [`PltHook::get`].
+    pub unsafe fn replace(&self, symbol: &str, new: *const ()) -> Result<*const ()> {
+        let slots = self.got_slots(symbol)?;
+        let original = match slots.first() {
+            Some(&slot) => (*(slot as *const AtomicUsize)).load(Ordering::SeqCst),
+            None => return Err(Error::other(
+                    format!("'{}' does not import '{}'", self.name, symbol))),
+        };
+
+        if slots.iter().any(|&slot| (*(slot as *const AtomicUsize)).load(Ordering::SeqCst) != original) {
+            return Err(Error::other(
+                    format!("the GOT entries for '{}' in '{}' differ", symbol, self.name)));
+        }
+
+        for slot in slots {
+            self.write_slot(slot, new as usize)?;
+        }
+
+        Ok(original as *const ())
+    }
+
+    fn first_slot(&self, symbol: &str) -> Result<usize> {
+        self.got_slots(symbol)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::other(
+                    format!("'{}' does not import '{}'", self.name, symbol)))
+    }
+
+    /// Writes the GOT entry at `slot`. Entries in the PT_GNU_RELRO region are
+    /// read-only once relocated, so the page is made writable for the write.
+    unsafe fn write_slot(&self, slot: usize, value: usize) -> Result<()> {
+        let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+        let page = slot & !(page_size - 1);
+
+        // The loader only protects the whole pages of the region.
+        let in_relro = self.relro.is_some_and(|(start, end)|
+            page >= start & !(page_size - 1) && page < end & !(page_size - 1));
+
+        if in_relro {
+            mprotect(page, page_size, libc::PROT_READ | libc::PROT_WRITE)?;
+        }
+
+        (*(slot as *const AtomicUsize)).store(value, Ordering::SeqCst);
+
+        if in_relro {
+            mprotect(page, page_size, libc::PROT_READ)?;
+        }
+
+        Ok(())
+    }
+}
+
+unsafe fn mprotect(addr: usize, len: usize, prot: libc::c_int) -> Result<()> {
+    if libc::mprotect(addr as *mut libc::c_void, len, prot) != 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use std::{
+    fmt,
+    fs,
+    io::*,
+    mem,
+    os::unix::fs::FileExt,
+    ptr,
+};
+
+use crate::auxv::Auxv;
+use crate::elf::Elf;
+use crate::header::{Ehdr, ELFMAG};
+use crate::segment::{first_load, header_vaddr, parse_phdrs, Phdr, Segment, SegmentFlag, SegmentType};
+use crate::unwind::MemoryProvider;
+
+const PAGE_SIZE: usize = 0x1000;
+/* Upper bound on the size of an image rebuilt from memory */
+const MAX_IMAGE_SIZE: usize = 1 << 30;
+
+/// A line of /proc/<pid>/maps.
+#[derive(Debug, Clone)]
+pub struct MemoryMap {
+    pub start: u64,
+    pub end: u64,
+    pub perms: String,
+    pub offset: u64,
+    pub dev: String,
+    pub inode: u64,
+    /// Backing file or pseudo path such as [stack], empty for anonymous maps.
+    pub path: String,
+}
+
+impl MemoryMap {
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.perms.starts_with('r')
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.perms.as_bytes().get(2) == Some(&b'x')
+    }
+
+    /// True if the backing file was unlinked after being mapped.
+    pub fn is_deleted(&self) -> bool {
+        self.path.ends_with(" (deleted)")
+    }
+
+    /// True if the map is backed by a memfd_create(2) file.
+    pub fn is_memfd(&self) -> bool {
+        self.path.starts_with("/memfd:")
+    }
+}
+
+impl fmt::Display for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:x}-{:x} {} {:08x} {} {} {}",
+            self.start, self.end, self.perms, self.offset, self.dev, self.inode, self.path)
+    }
+}
+
+pub fn parse_maps(text: &str) -> Result<Vec<MemoryMap>> {
+    let mut maps = Vec::new();
+
+    for line in text.lines() {
+        let mut fields = line.splitn(6, ' ');
+        let mut next = || fields.next()
+            .ok_or_else(|| Error::other(format!("malformed maps line: '{}'", line)));
+
+        let range = next()?;
+        let perms = next()?.to_string();
+        let offset = next()?;
+        let dev = next()?.to_string();
+        let inode = next()?;
+        let path = fields.next().unwrap_or("").trim_start().to_string();
+
+        let (start, end) = range.split_once('-')
+            .ok_or_else(|| Error::other(format!("malformed address range: '{}'", range)))?;
+
+        maps.push(MemoryMap {
+            start: parse_hex(start)?,
+            end: parse_hex(end)?,
+            perms,
+            offset: parse_hex(offset)?,
+            dev,
+            inode: inode.parse().map_err(Error::other)?,
+            path,
+        });
+    }
+
+    Ok(maps)
+}
+
+fn parse_hex(s: &str) -> Result<u64> {
+    u64::from_str_radix(s, 16).map_err(Error::other)
+}
+
+/// A process whose memory is read through /proc/<pid>/mem. This requires
+/// the same permissions as ptrace(2), but the process need not be stopped.
+#[derive(Debug)]
+pub struct Process {
+    pub pid: u32,
+    mem: fs::File,
+}
+
+impl Process {
+    pub fn open(pid: u32) -> Result<Self> {
+        let mem = fs::File::open(format!("/proc/{}/mem", pid))?;
+        Ok(Process { pid, mem })
+    }
+
+    pub fn this() -> Result<Self> {
+        Process::open(std::process::id())
+    }
+
+    pub fn maps(&self) -> Result<Vec<MemoryMap>> {
+        parse_maps(&fs::read_to_string(format!("/proc/{}/maps", self.pid))?)
+    }
+
+    pub fn auxv(&self) -> Result<Auxv> {
+        Auxv::from_pid(self.pid)
+    }
+
+    /// Returns the address of the executable's ELF header, found through the
+    /// AT_PHDR entry of the auxiliary vector.
+    pub fn executable_base(&self) -> Result<u64> {
+        let auxv = self.auxv()?;
+        let (phdr, phnum) = match (auxv.phdr(), auxv.phnum()) {
+            (Some(phdr), Some(phnum)) => (phdr, phnum as usize),
+            _ => return Err(Error::other("AT_PHDR or AT_PHNUM missing from auxv")),
+        };
+        let segments = read_phdrs(self, phdr, phnum)?;
+
+        let first = first_load(&segments)?;
+        let bias = match segments.iter().find(|s| s.is_type(SegmentType::Phdr)) {
+            Some(s) => phdr.wrapping_sub(s.vaddr as u64),
+            None => phdr.wrapping_sub((first.vaddr + mem::size_of::<Ehdr>()) as u64),
+        };
+
+        Ok(bias.wrapping_add(header_vaddr(&segments)? as u64))
+    }
+
+    /// Finds every map that starts with an ELF header, file backed or not.
+    /// This covers deleted files, memfds and images unpacked at runtime.
+    pub fn elf_bases(&self) -> Result<Vec<(u64, String)>> {
+        let mut bases = Vec::new();
+
+        for map in self.maps()? {
+            if !map.is_readable() || map.offset != 0 {
+                continue;
+            }
+
+            let mut magic = [0; 4];
+            if self.read_memory(map.start, &mut magic).is_ok() && magic == ELFMAG {
+                bases.push((map.start, map.path));
+            }
+        }
+
+        Ok(bases)
+    }
+
+    /// Parses the ELF image whose header is at `base`.
+    pub fn read_elf(&self, base: u64) -> Result<Elf> {
+        Elf::from_memory(self, base)
+    }
+}
+
+impl MemoryProvider for Process {
+    fn read_memory(&self, addr: u64, buf: &mut [u8]) -> Result<()> {
+        self.mem.read_exact_at(buf, addr)
+    }
+}
+
+fn read_phdrs(mem: &dyn MemoryProvider, addr: u64, phnum: usize) -> Result<Vec<Segment>> {
+    let mut buf = vec![0; phnum * mem::size_of::<Phdr>()];
+    mem.read_memory(addr, &mut buf)?;
+
+    Ok(parse_phdrs(&buf))
+}
+
+impl Elf {
+    /// Parses an ELF image mapped in another process, `base` being the
+    /// address of its ELF header.
+    pub fn from_process(pid: u32, base: u64) -> Result<Self> {
+        Process::open(pid)?.read_elf(base)
+    }
+
+    /// Parses an ELF image from arbitrary memory. The file backed part of
+    /// each PT_LOAD is copied back to its file offset, so the result behaves
+    /// like a file whose section headers were stripped.
+    pub fn from_memory(mem: &dyn MemoryProvider, base: u64) -> Result<Self> {
+        let mut buf = [0; mem::size_of::<Ehdr>()];
+        mem.read_memory(base, &mut buf)?;
+        if buf[..4] != ELFMAG {
+            return Err(Error::other(format!("no ELF header at {:#x}", base)));
+        }
+
+        let header: Ehdr = unsafe { ptr::read_unaligned(buf.as_ptr() as *const Ehdr) };
+        let phdr = base.checked_add(header.e_phoff as u64)
+            .ok_or_else(|| Error::other("e_phoff out of range"))?;
+        let segments = read_phdrs(mem, phdr, header.e_phnum as usize)?;
+
+        let bias = base.wrapping_sub(header_vaddr(&segments)? as u64);
+
+        let loads: Vec<&Segment> = segments.iter()
+            .filter(|s| s.is_type(SegmentType::Load) && s.has_flag(SegmentFlag::Read))
+            .collect();
+        let mut len = 0;
+        for seg in &loads {
+            match seg.offset.checked_add(seg.filesz) {
+                Some(end) if end <= MAX_IMAGE_SIZE => len = len.max(end),
+                _ => return Err(Error::other(
+                        format!("PT_LOAD at {:#x} does not fit a {} byte image", seg.vaddr, MAX_IMAGE_SIZE))),
+            }
+        }
+        let mut data = vec![0; len];
+
+        for seg in loads {
+            let dst = &mut data[seg.offset..seg.offset + seg.filesz];
+            let addr = bias.wrapping_add(seg.vaddr as u64);
+
+            // Holes, e.g. pages unmapped by the process, are left zeroed.
+            if mem.read_memory(addr, dst).is_err() {
+                for (i, page) in dst.chunks_mut(PAGE_SIZE).enumerate() {
+                    let _ = mem.read_memory(addr.wrapping_add((i * PAGE_SIZE) as u64), page);
+                }
+            }
+        }
+
+        Elf::from_memory_image(data, bias as usize)
+    }
+}
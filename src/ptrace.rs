@@ -0,0 +1,225 @@
+use std::{
+    fmt,
+    fs,
+    io::*,
+    mem,
+};
+
+use crate::coredump::{Aarch64Regs, GeneralRegisters, X86_64Regs, NT_PRSTATUS};
+use crate::process::Process;
+use crate::symbolizer::{ProcessSymbolizer, SymbolizedAddress};
+use crate::unwind::*;
+use crate::utils::Reader;
+
+/* Large enough for user_regs_struct on x86_64 and user_pt_regs on aarch64 */
+const REGSET_SIZE: usize = 34 * 8;
+
+/// A process stopped with ptrace(2), every thread of it attached. Threads
+/// are detached, and resume, when it is dropped.
+#[derive(Debug)]
+pub struct Tracee {
+    pub pid: u32,
+    pub arch: Arch,
+    threads: Vec<u32>,
+    process: Process,
+}
+
+impl Tracee {
+    pub fn attach(pid: u32) -> Result<Self> {
+        if pid == std::process::id() {
+            return Err(Error::other("a process can not trace itself"));
+        }
+
+        let arch = Arch::host().ok_or_else(|| Error::other("unsupported architecture"))?;
+        let mut tracee = Tracee {
+            pid,
+            arch,
+            threads: Vec::new(),
+            process: Process::open(pid)?,
+        };
+
+        // Threads may be created while attaching, repeat until none are new.
+        loop {
+            let mut new = 0;
+            for tid in list_threads(pid)? {
+                if tracee.threads.contains(&tid) {
+                    continue;
+                }
+                match attach_thread(tid) {
+                    Ok(()) => {
+                        tracee.threads.push(tid);
+                        new += 1;
+                    },
+                    // The thread exited in the meantime.
+                    Err(e) if e.raw_os_error() == Some(libc::ESRCH) => (),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if new == 0 {
+                break;
+            }
+        }
+
+        if tracee.threads.is_empty() {
+            return Err(Error::other(format!("process {} has no threads", pid)));
+        }
+
+        Ok(tracee)
+    }
+
+    /// Reads the general purpose registers of a stopped thread.
+    pub fn general_registers(&self, tid: u32) -> Result<GeneralRegisters> {
+        let mut buf = [0u8; REGSET_SIZE];
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+
+        let ret = unsafe {
+            libc::ptrace(libc::PTRACE_GETREGSET, tid as libc::pid_t,
+                NT_PRSTATUS as usize as *mut libc::c_void,
+                &mut iov as *mut libc::iovec as *mut libc::c_void)
+        };
+        if ret < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let mut r = Reader::new(&buf[..iov.iov_len]);
+        Ok(match self.arch {
+            Arch::X86_64 => GeneralRegisters::X86_64(X86_64Regs::parse(&mut r)?),
+            Arch::Aarch64 => GeneralRegisters::Aarch64(Aarch64Regs::parse(&mut r)?),
+        })
+    }
+}
+
+impl MemoryProvider for Tracee {
+    fn read_memory(&self, addr: u64, buf: &mut [u8]) -> Result<()> {
+        self.process.read_memory(addr, buf)
+    }
+}
+
+impl ThreadProvider for Tracee {
+    fn threads(&self) -> Result<Vec<u32>> {
+        Ok(self.threads.clone())
+    }
+
+    fn registers(&self, tid: u32) -> Result<Registers> {
+        Ok(self.general_registers(tid)?.to_registers())
+    }
+}
+
+impl Drop for Tracee {
+    fn drop(&mut self) {
+        for &tid in &self.threads {
+            unsafe {
+                libc::ptrace(libc::PTRACE_DETACH, tid as libc::pid_t,
+                    std::ptr::null_mut::<libc::c_void>(), std::ptr::null_mut::<libc::c_void>());
+            }
+        }
+    }
+}
+
+fn list_threads(pid: u32) -> Result<Vec<u32>> {
+    let mut tids = Vec::new();
+    for entry in fs::read_dir(format!("/proc/{}/task", pid))? {
+        if let Ok(tid) = entry?.file_name().to_string_lossy().parse() {
+            tids.push(tid);
+        }
+    }
+    tids.sort();
+
+    Ok(tids)
+}
+
+fn attach_thread(tid: u32) -> Result<()> {
+    unsafe {
+        if libc::ptrace(libc::PTRACE_ATTACH, tid as libc::pid_t,
+            std::ptr::null_mut::<libc::c_void>(), std::ptr::null_mut::<libc::c_void>()) < 0 {
+            return Err(Error::last_os_error());
+        }
+
+        let mut status = 0;
+        if libc::waitpid(tid as libc::pid_t, &mut status, libc::__WALL) < 0 {
+            return Err(Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// A frame of a `ThreadStack`, with its symbolized location.
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    pub frame: Frame,
+    pub location: SymbolizedAddress,
+}
+
+/// The call stack of one thread, innermost frame first.
+#[derive(Debug, Clone)]
+pub struct ThreadStack {
+    pub tid: u32,
+    /// The thread name from /proc/<pid>/task/<tid>/comm.
+    pub name: String,
+    pub frames: Vec<StackFrame>,
+}
+
+impl fmt::Display for ThreadStack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "TID {} ({}):", self.tid, self.name)?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            let sym = &frame.location;
+            write!(f, "#{:<2} {:#018x}", i, frame.frame.pc)?;
+            match (&sym.module, &sym.symbol) {
+                (Some(module), Some(symbol)) => write!(f, " {}+{:#x} - {}", symbol, sym.offset, module)?,
+                (Some(module), None) => write!(f, " ?? - {}", module)?,
+                (None, _) => write!(f, " ??")?,
+            }
+            writeln!(f)?;
+
+            if let Some(location) = &sym.location {
+                writeln!(f, "    {}", location)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stops every thread of `pid`, unwinds and symbolizes their stacks, then
+/// lets the process continue, like eu-stack(1).
+pub fn pstack(pid: u32) -> Result<Vec<ThreadStack>> {
+    let tracee = Tracee::attach(pid)?;
+    let symbolizer = ProcessSymbolizer::new(pid)?;
+
+    let mut unwinder = Unwinder::new();
+    for module in symbolizer.modules() {
+        unwinder.add_module(module.name(), module.elf(), module.bias);
+    }
+
+    let mut stacks = Vec::new();
+    for (tid, frames) in unwinder.unwind_all(&tracee)? {
+        // Return addresses point after the call, look up the call itself.
+        let pcs: Vec<u64> = frames.iter()
+            .map(|f| match f.method {
+                UnwindMethod::Initial => f.pc,
+                _ => f.pc.saturating_sub(1),
+            })
+            .collect();
+
+        let frames = frames.into_iter()
+            .zip(symbolizer.symbolize_many(&pcs))
+            .map(|(frame, location)| StackFrame { frame, location })
+            .collect();
+
+        let name = fs::read_to_string(format!("/proc/{}/task/{}/comm", pid, tid))
+            .map(|s| s.trim_end().to_string())
+            .unwrap_or_default();
+
+        stacks.push(ThreadStack { tid, name, frames });
+    }
+
+    // Make sure every thread is resumed before returning.
+    mem::drop(tracee);
+
+    Ok(stacks)
+}
@@ -0,0 +1,358 @@
+use std::io::*;
+
+use crate::elf::Elf;
+use crate::header::ElfType;
+use crate::relocation::*;
+use crate::section::{Section, SectionFlag, SectionType};
+use crate::segment::SegmentType;
+use crate::symbols::{ElfSym, Symbol, SymbolBinding};
+use crate::unwind::MemoryProvider;
+use crate::utils::*;
+
+pub const R_X86_64_PC32: u32 = 2;
+pub const R_X86_64_PLT32: u32 = 4;
+pub const R_X86_64_32: u32 = 10;
+pub const R_X86_64_32S: u32 = 11;
+pub const R_X86_64_PC64: u32 = 24;
+
+pub const R_AARCH64_ABS32: u32 = 258;
+pub const R_AARCH64_PREL64: u32 = 260;
+pub const R_AARCH64_PREL32: u32 = 261;
+
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xfff1;
+
+const PAGE_SIZE: usize = 0x1000;
+const SYM_SIZE: usize = 24;
+
+/// A flat memory image of an ELF file as it would look once loaded at
+/// `base`, with the relocations that can be resolved statically applied.
+#[derive(Debug)]
+pub struct RelocatedImage {
+    pub base: usize,
+    pub data: Vec<u8>,
+    /// Difference between the image and the link time addresses.
+    pub bias: usize,
+    /// Address of each section, indexed like the section headers. Only
+    /// allocated sections of relocatable objects have one.
+    pub section_addrs: Vec<Option<usize>>,
+    /// Relocations left untouched, either because they refer to a symbol
+    /// defined elsewhere or because their type is not supported.
+    pub unresolved: Vec<Relocation>,
+}
+
+impl RelocatedImage {
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.base && addr - self.base < self.data.len()
+    }
+
+    /// Returns `len` bytes at `addr`, or None if they are not in the image.
+    pub fn read(&self, addr: usize, len: usize) -> Option<&[u8]> {
+        let start = addr.checked_sub(self.base)?;
+        self.data.get(start..start.checked_add(len)?)
+    }
+
+    pub fn read_usize(&self, addr: usize) -> Option<usize> {
+        let bytes = self.read(addr, 8)?;
+        Some(u64::from_le_bytes(bytes.try_into().ok()?) as usize)
+    }
+
+    fn write(&mut self, addr: usize, value: u64, size: usize) -> Result<()> {
+        let start = addr.wrapping_sub(self.base);
+        match self.data.get_mut(start..start.saturating_add(size)) {
+            Some(dst) => {
+                dst.copy_from_slice(&value.to_le_bytes()[..size]);
+                Ok(())
+            },
+            None => Err(Error::other(
+                    format!("relocation target {:#x} is outside of the image", addr))),
+        }
+    }
+}
+
+impl MemoryProvider for RelocatedImage {
+    fn read_memory(&self, addr: u64, buf: &mut [u8]) -> Result<()> {
+        match self.read(addr as usize, buf.len()) {
+            Some(src) => {
+                buf.copy_from_slice(src);
+                Ok(())
+            },
+            None => Err(Error::other(
+                    format!("address {:#x} is outside of the image", addr))),
+        }
+    }
+}
+
+/// How a dynamic relocation computes its value.
+enum DynamicKind {
+    /// B + A
+    Relative,
+    /// S + A
+    Absolute,
+    /// S
+    Slot,
+}
+
+/// How a resolved relocation value is stored at its target.
+enum Field {
+    Abs64,
+    Abs32,
+    Abs32Signed,
+    Pc64,
+    Pc32,
+}
+
+impl Elf {
+    /// Lays out the file in memory as if loaded at `base` and applies its
+    /// relocations, so pointers in e.g. .data.rel.ro, .init_array or the GOT
+    /// read as real addresses.
+    ///
+    /// Executables and shared objects are laid out by PT_LOAD and relocated
+    /// with their dynamic relocations, imports are left unresolved. Position
+    /// dependent executables are always placed at their link time address.
+    /// Relocatable objects have their SHF_ALLOC sections placed one after
+    /// the other and their .rela/.rel sections applied.
+    pub fn relocated_image(&self, base: usize) -> Result<RelocatedImage> {
+        match self.header().e_machine {
+            EM_X86_64 | EM_AARCH64 => {},
+            machine => return Err(Error::other(
+                    format!("relocation of machine {} is not supported", machine))),
+        }
+
+        if self.header().e_type == ElfType::Reloc as u16 {
+            self.relocate_object(base)
+        } else {
+            self.relocate_loadable(base)
+        }
+    }
+
+    fn relocate_loadable(&self, base: usize) -> Result<RelocatedImage> {
+        let loads: Vec<_> = self.get_segments_by_type(SegmentType::Load).collect();
+        let start = loads.iter().map(|s| s.vaddr).min()
+            .ok_or_else(|| Error::other("no PT_LOAD segment"))? & !(PAGE_SIZE - 1);
+        let mut end = start;
+        for s in &loads {
+            end = end.max(s.vaddr.checked_add(s.memsz).ok_or_else(|| Error::other(
+                        format!("PT_LOAD at {:#x} ends past the address space", s.vaddr)))?);
+        }
+
+        let base = match self.header().e_type == ElfType::Exec as u16 {
+            true => start,
+            false => base,
+        };
+        let bias = base.wrapping_sub(start);
+
+        let mut data = vec![0u8; end - start];
+        for seg in &loads {
+            let src = seg.offset.checked_add(seg.filesz)
+                .and_then(|end| self.as_bytes().get(seg.offset..end))
+                .ok_or_else(|| Error::other(
+                        format!("PT_LOAD at {:#x} extends past the end of the file", seg.offset)))?;
+            let dst = seg.vaddr - start;
+            data.get_mut(dst..dst + src.len())
+                .ok_or_else(|| Error::other(
+                        format!("PT_LOAD at {:#x} has a file size larger than its memory size", seg.vaddr)))?
+                .copy_from_slice(src);
+        }
+
+        let mut image = RelocatedImage {
+            base, data, bias,
+            section_addrs: Vec::new(),
+            unresolved: Vec::new(),
+        };
+
+        let mut relocs = self.get_dynamic_relocations()?;
+        relocs.extend(self.get_plt_relocations()?);
+
+        for reloc in relocs {
+            let target = bias.wrapping_add(reloc.offset);
+            let addend = match reloc.addend {
+                Some(a) => a,
+                None => image.read_usize(target).unwrap_or(0) as i64,
+            };
+
+            let symbol = match self.get_relocation_symbol(&reloc) {
+                Some(sym) if sym.shndx != SHN_UNDEF => Some(bias.wrapping_add(sym.value)),
+                Some(_) => None,
+                None => Some(0),
+            };
+
+            // IFUNCs resolve to their resolver, which can not be run here.
+            let value = match (self.dynamic_kind(&reloc), symbol) {
+                (Some(DynamicKind::Relative), _) => bias.wrapping_add_signed(addend as isize),
+                (Some(DynamicKind::Absolute), Some(s)) => s.wrapping_add_signed(addend as isize),
+                (Some(DynamicKind::Slot), Some(s)) => s,
+                _ => {
+                    image.unresolved.push(reloc);
+                    continue;
+                },
+            };
+
+            image.write(target, value as u64, 8)?;
+        }
+
+        Ok(image)
+    }
+
+    fn dynamic_kind(&self, reloc: &Relocation) -> Option<DynamicKind> {
+        let kind = match (self.header().e_machine, reloc.typ) {
+            (EM_X86_64, R_X86_64_RELATIVE | R_X86_64_IRELATIVE) => DynamicKind::Relative,
+            (EM_X86_64, R_X86_64_64) => DynamicKind::Absolute,
+            (EM_X86_64, R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT) => DynamicKind::Slot,
+            (EM_AARCH64, R_AARCH64_RELATIVE | R_AARCH64_IRELATIVE) => DynamicKind::Relative,
+            (EM_AARCH64, R_AARCH64_ABS64 | R_AARCH64_GLOB_DAT | R_AARCH64_JUMP_SLOT) => DynamicKind::Absolute,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    fn relocate_object(&self, base: usize) -> Result<RelocatedImage> {
+        let sections: Vec<&Section> = self.iter_sections().collect();
+
+        let mut section_addrs = vec![None; sections.len()];
+        let mut size: usize = 0;
+        for (i, s) in sections.iter().enumerate() {
+            if !s.has_flag(SectionFlag::Alloc) {
+                continue;
+            }
+            let align = s.addralign.max(1);
+            let start = size.div_ceil(align).checked_mul(align)
+                .filter(|start| start.checked_add(s.size).is_some())
+                .ok_or_else(|| Error::other(
+                        format!("section {} does not fit in the address space", s.name)))?;
+            section_addrs[i] = Some(base.wrapping_add(start));
+            size = start + s.size;
+        }
+
+        let mut data = vec![0u8; size];
+        for (i, s) in sections.iter().enumerate() {
+            if let Some(addr) = section_addrs[i] {
+                let src = self.get_section_contents(s)?;
+                let dst = addr.wrapping_sub(base);
+                data[dst..dst + src.len()].copy_from_slice(src);
+            }
+        }
+
+        let mut image = RelocatedImage {
+            base, data,
+            bias: base,
+            section_addrs,
+            unresolved: Vec::new(),
+        };
+
+        for s in &sections {
+            let rela = s.typ == SectionType::Rela as u32;
+            if !rela && s.typ != SectionType::Rel as u32 {
+                continue;
+            }
+            let target = match image.section_addrs.get(s.info as usize) {
+                Some(&Some(addr)) => addr,
+                _ => continue,
+            };
+
+            let symtab = self.object_symbols(s.link as usize)?;
+            let relocs = match rela {
+                true => Relocation::parse_rela(self.get_section_contents(s)?)?,
+                false => Relocation::parse_rel(self.get_section_contents(s)?)?,
+            };
+
+            for reloc in relocs {
+                let place = target.checked_add(reloc.offset).ok_or_else(|| Error::other(
+                        format!("relocation offset {:#x} is out of range", reloc.offset)))?;
+                let symbol = match symtab.get(reloc.symbol as usize) {
+                    Some(sym) => match sym.shndx {
+                        SHN_UNDEF if sym.binding == SymbolBinding::Weak => Some(0),
+                        SHN_UNDEF => None,
+                        SHN_ABS => Some(sym.value),
+                        shndx => image.section_addrs.get(shndx as usize)
+                            .copied()
+                            .flatten()
+                            .map(|addr| addr.wrapping_add(sym.value)),
+                    },
+                    None => None,
+                };
+
+                let (field, symbol) = match (self.object_field(&reloc), symbol) {
+                    (Some(field), Some(symbol)) => (field, symbol),
+                    _ => {
+                        image.unresolved.push(reloc);
+                        continue;
+                    },
+                };
+
+                let (size, implicit) = match field {
+                    Field::Abs64 | Field::Pc64 => (8, image.read_usize(place).unwrap_or(0) as i64),
+                    _ => (4, image.read(place, 4)
+                        .map(|b| i32::from_le_bytes(b.try_into().unwrap()) as i64)
+                        .unwrap_or(0)),
+                };
+                let addend = reloc.addend.unwrap_or(implicit);
+                let value = symbol.wrapping_add_signed(addend as isize);
+
+                let value = match field {
+                    Field::Abs64 => value as u64,
+                    Field::Abs32 => value as u32 as u64,
+                    Field::Abs32Signed => value as i32 as u64,
+                    Field::Pc64 => value.wrapping_sub(place) as u64,
+                    Field::Pc32 => value.wrapping_sub(place) as i32 as u64,
+                };
+                image.write(place, value, size)?;
+            }
+        }
+
+        Ok(image)
+    }
+
+    fn object_field(&self, reloc: &Relocation) -> Option<Field> {
+        let field = match (self.header().e_machine, reloc.typ) {
+            (EM_X86_64, R_X86_64_64) => Field::Abs64,
+            (EM_X86_64, R_X86_64_32) => Field::Abs32,
+            (EM_X86_64, R_X86_64_32S) => Field::Abs32Signed,
+            (EM_X86_64, R_X86_64_PC64) => Field::Pc64,
+            (EM_X86_64, R_X86_64_PC32 | R_X86_64_PLT32) => Field::Pc32,
+            (EM_AARCH64, R_AARCH64_ABS64) => Field::Abs64,
+            (EM_AARCH64, R_AARCH64_ABS32) => Field::Abs32,
+            (EM_AARCH64, R_AARCH64_PREL64) => Field::Pc64,
+            (EM_AARCH64, R_AARCH64_PREL32) => Field::Pc32,
+            _ => return None,
+        };
+        Some(field)
+    }
+
+    /// Reads the symbol table in section `index` in table order, which the
+    /// name keyed symbol map does not preserve.
+    pub(crate) fn object_symbols(&self, index: usize) -> Result<Vec<Symbol>> {
+        let symtab = match self.iter_sections().nth(index) {
+            Some(s) => s,
+            None => return Err(Error::other(
+                    format!("invalid symbol table index {}", index))),
+        };
+        let strtab = match self.iter_sections().nth(symtab.link as usize) {
+            Some(s) => self.get_section_contents(s)?,
+            None => &[],
+        };
+
+        let data = self.get_section_contents(symtab)?;
+        let mut r = Reader::new(data);
+        let mut symbols = Vec::with_capacity(data.len() / SYM_SIZE);
+
+        while r.remaining() >= SYM_SIZE {
+            let sym = ElfSym {
+                st_name: r.u32()?,
+                st_info: r.u8()?,
+                st_other: r.u8()?,
+                st_shndx: r.u16()?,
+                st_value: r.u64()? as usize,
+                st_size: r.u64()? as usize,
+            };
+            let mut symbol = Symbol::from_elfsym(&sym)?;
+            symbol.name = c_str_at(strtab, sym.st_name as usize).unwrap_or_default();
+            symbols.push(symbol);
+        }
+
+        Ok(symbols)
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    io::*,
+};
+
+use crate::dynamic::EntryType;
+use crate::elf::Elf;
+use crate::symbols::Symbol;
+use crate::utils::Reader;
+
+pub const R_X86_64_NONE: u32 = 0;
+pub const R_X86_64_64: u32 = 1;
+pub const R_X86_64_COPY: u32 = 5;
+pub const R_X86_64_GLOB_DAT: u32 = 6;
+pub const R_X86_64_JUMP_SLOT: u32 = 7;
+pub const R_X86_64_RELATIVE: u32 = 8;
+pub const R_X86_64_DTPMOD64: u32 = 16;
+pub const R_X86_64_DTPOFF64: u32 = 17;
+pub const R_X86_64_TPOFF64: u32 = 18;
+pub const R_X86_64_IRELATIVE: u32 = 37;
+
+pub const R_AARCH64_NONE: u32 = 0;
+pub const R_AARCH64_ABS64: u32 = 257;
+pub const R_AARCH64_COPY: u32 = 1024;
+pub const R_AARCH64_GLOB_DAT: u32 = 1025;
+pub const R_AARCH64_JUMP_SLOT: u32 = 1026;
+pub const R_AARCH64_RELATIVE: u32 = 1027;
+pub const R_AARCH64_TLS_DTPMOD: u32 = 1028;
+pub const R_AARCH64_TLS_DTPREL: u32 = 1029;
+pub const R_AARCH64_TLS_TPREL: u32 = 1030;
+pub const R_AARCH64_IRELATIVE: u32 = 1032;
+
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+/* DT_PLTREL values */
+const DT_REL: usize = 17;
+const DT_RELA: usize = 7;
+
+const RELA_SIZE: usize = 24;
+const REL_SIZE: usize = 16;
+
+/// A dynamic relocation, from either an Elf64_Rela or an Elf64_Rel table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    /// Link time address of the location to patch.
+    pub offset: usize,
+    pub typ: u32,
+    /// Index into the dynamic symbol table, 0 if none.
+    pub symbol: u32,
+    /// Explicit addend, None for REL entries whose addend is stored in place.
+    pub addend: Option<i64>,
+}
+
+impl Relocation {
+    pub fn parse_rela(data: &[u8]) -> Result<Vec<Self>> {
+        let mut r = Reader::new(data);
+        let mut relocs = Vec::with_capacity(data.len() / RELA_SIZE);
+
+        while r.remaining() >= RELA_SIZE {
+            let offset = r.u64()? as usize;
+            let info = r.u64()?;
+            let addend = r.i64()?;
+            relocs.push(Relocation {
+                offset,
+                typ: info as u32,
+                symbol: (info >> 32) as u32,
+                addend: Some(addend),
+            });
+        }
+
+        Ok(relocs)
+    }
+
+    pub fn parse_rel(data: &[u8]) -> Result<Vec<Self>> {
+        let mut r = Reader::new(data);
+        let mut relocs = Vec::with_capacity(data.len() / REL_SIZE);
+
+        while r.remaining() >= REL_SIZE {
+            let offset = r.u64()? as usize;
+            let info = r.u64()?;
+            relocs.push(Relocation {
+                offset,
+                typ: info as u32,
+                symbol: (info >> 32) as u32,
+                addend: None,
+            });
+        }
+
+        Ok(relocs)
+    }
+
+    /// Returns true if the relocation fills in a GOT entry with the address
+    /// of its symbol, i.e. a PLT slot or a -fno-plt style GOT load.
+    pub fn is_got_slot(&self, machine: u16) -> bool {
+        match machine {
+            EM_X86_64 => matches!(self.typ, R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT),
+            EM_AARCH64 => matches!(self.typ, R_AARCH64_GLOB_DAT | R_AARCH64_JUMP_SLOT),
+            _ => false,
+        }
+    }
+
+    pub fn type_name(&self, machine: u16) -> Option<&'static str> {
+        let name = match (machine, self.typ) {
+            (EM_X86_64, R_X86_64_NONE) => "R_X86_64_NONE",
+            (EM_X86_64, R_X86_64_64) => "R_X86_64_64",
+            (EM_X86_64, R_X86_64_COPY) => "R_X86_64_COPY",
+            (EM_X86_64, R_X86_64_GLOB_DAT) => "R_X86_64_GLOB_DAT",
+            (EM_X86_64, R_X86_64_JUMP_SLOT) => "R_X86_64_JUMP_SLOT",
+            (EM_X86_64, R_X86_64_RELATIVE) => "R_X86_64_RELATIVE",
+            (EM_X86_64, R_X86_64_DTPMOD64) => "R_X86_64_DTPMOD64",
+            (EM_X86_64, R_X86_64_DTPOFF64) => "R_X86_64_DTPOFF64",
+            (EM_X86_64, R_X86_64_TPOFF64) => "R_X86_64_TPOFF64",
+            (EM_X86_64, R_X86_64_IRELATIVE) => "R_X86_64_IRELATIVE",
+            (EM_AARCH64, R_AARCH64_NONE) => "R_AARCH64_NONE",
+            (EM_AARCH64, R_AARCH64_ABS64) => "R_AARCH64_ABS64",
+            (EM_AARCH64, R_AARCH64_COPY) => "R_AARCH64_COPY",
+            (EM_AARCH64, R_AARCH64_GLOB_DAT) => "R_AARCH64_GLOB_DAT",
+            (EM_AARCH64, R_AARCH64_JUMP_SLOT) => "R_AARCH64_JUMP_SLOT",
+            (EM_AARCH64, R_AARCH64_RELATIVE) => "R_AARCH64_RELATIVE",
+            (EM_AARCH64, R_AARCH64_TLS_DTPMOD) => "R_AARCH64_TLS_DTPMOD",
+            (EM_AARCH64, R_AARCH64_TLS_DTPREL) => "R_AARCH64_TLS_DTPREL",
+            (EM_AARCH64, R_AARCH64_TLS_TPREL) => "R_AARCH64_TLS_TPREL",
+            (EM_AARCH64, R_AARCH64_IRELATIVE) => "R_AARCH64_IRELATIVE",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+impl fmt::Display for Relocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#014x} type={} sym={}", self.offset, self.typ, self.symbol)?;
+        if let Some(addend) = self.addend {
+            write!(f, " addend={:#x}", addend)?;
+        }
+        Ok(())
+    }
+}
+
+impl Elf {
+    fn read_relocations(&self, table: EntryType, size: EntryType, rela: bool) -> Result<Vec<Relocation>> {
+        let (vaddr, size) = match (self.get_dynamic_vaddr(table), self.get_dynamic(size)) {
+            (Some(vaddr), Some(size)) => (vaddr, size),
+            _ => return Ok(Vec::new()),
+        };
+
+        let data = self.read_vaddr(vaddr, size)?;
+        if data.len() != size {
+            return Err(Error::other(
+                    format!("relocation table at {:#x} is truncated", vaddr)));
+        }
+
+        if rela {
+            Relocation::parse_rela(data)
+        } else {
+            Relocation::parse_rel(data)
+        }
+    }
+
+    /// Returns the PLT relocations, DT_JMPREL.
+    pub fn get_plt_relocations(&self) -> Result<Vec<Relocation>> {
+        let rela = match self.get_dynamic(EntryType::PltRel) {
+            Some(DT_REL) => false,
+            Some(DT_RELA) | None => true,
+            Some(other) => return Err(Error::other(
+                    format!("invalid DT_PLTREL {}", other))),
+        };
+
+        self.read_relocations(EntryType::JumpRel, EntryType::PltRelSize, rela)
+    }
+
+    /// Returns the DT_RELA and DT_REL relocations, excluding the PLT ones.
+    pub fn get_dynamic_relocations(&self) -> Result<Vec<Relocation>> {
+        let mut relocs = self.read_relocations(EntryType::Rela, EntryType::RelaSize, true)?;
+        relocs.extend(self.read_relocations(EntryType::Rel, EntryType::Relsize, false)?);
+
+        // Some linkers make DT_RELA cover DT_JMPREL as well.
+        let plt: HashSet<(usize, u32)> = self.get_plt_relocations()?
+            .iter()
+            .map(|r| (r.offset, r.typ))
+            .collect();
+        relocs.retain(|r| !plt.contains(&(r.offset, r.typ)));
+
+        Ok(relocs)
+    }
+
+    /// Returns the symbol a relocation refers to.
+    pub fn get_relocation_symbol(&self, reloc: &Relocation) -> Option<&Symbol> {
+        if reloc.symbol == 0 {
+            return None;
+        }
+        self.get_dynamic_symbol(reloc.symbol as usize)
+    }
+
+    /// Returns the link time addresses of the GOT entries that will hold the
+    /// address of the imported symbol `name`.
+    pub fn get_got_slots(&self, name: &str) -> Result<Vec<usize>> {
+        let machine = self.header().e_machine;
+        let mut relocs = self.get_plt_relocations()?;
+        relocs.extend(self.get_dynamic_relocations()?);
+
+        Ok(relocs.iter()
+            .filter(|r| r.is_got_slot(machine))
+            .filter(|r| self.get_relocation_symbol(r).is_some_and(|s| s.name == name))
+            .map(|r| r.offset)
+            .collect())
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::*,
+};
+
+use serde_json::Value;
+
+use crate::elf::Elf;
+use crate::relocation::{R_AARCH64_RELATIVE, R_X86_64_RELATIVE};
+use crate::segment::{SegmentFlag, SegmentType};
+use crate::utils::Reader;
+
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+/* core::panic::Location { file: &str, line: u32, col: u32 } */
+const LOCATION_SIZE: usize = 24;
+const MAX_PATH_LEN: usize = 4096;
+
+/// The registry source directory, e.g.
+/// ~/.cargo/registry/src/index.crates.io-6f17d22bba15001f/gimli-0.29.0/src/lib.rs
+const REGISTRY_SRC: &str = "/registry/src/";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DependencyKind {
+    Runtime,
+    Build,
+}
+
+/// A package as recorded by cargo-auditable.
+#[derive(Debug, Clone)]
+pub struct AuditablePackage {
+    pub name: String,
+    pub version: String,
+    /// "crates.io", "git", "local", "registry" or "other".
+    pub source: String,
+    pub kind: DependencyKind,
+    /// Indices into `AuditableDeps::packages`.
+    pub dependencies: Vec<usize>,
+    /// Set for the package that was built.
+    pub root: bool,
+}
+
+impl fmt::Display for AuditablePackage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} ({})", self.name, self.version, self.source)?;
+        if self.kind == DependencyKind::Build {
+            write!(f, " [build]")?;
+        }
+        Ok(())
+    }
+}
+
+/// The dependency tree embedded by `cargo auditable` in `.dep-v0`.
+#[derive(Debug, Clone, Default)]
+pub struct AuditableDeps {
+    pub packages: Vec<AuditablePackage>,
+}
+
+impl AuditableDeps {
+    /// Parses the zlib compressed JSON contents of `.dep-v0`.
+    pub fn parse(compressed: &[u8]) -> Result<Self> {
+        let json = miniz_oxide::inflate::decompress_to_vec_zlib(compressed)
+            .map_err(|e| Error::other(format!("failed to decompress .dep-v0: {:?}", e)))?;
+        let json: Value = serde_json::from_slice(&json)
+            .map_err(|e| Error::other(format!("invalid .dep-v0 JSON: {}", e)))?;
+
+        let packages = json.get("packages")
+            .and_then(|p| p.as_array())
+            .ok_or_else(|| Error::other(".dep-v0 has no package list"))?;
+
+        let mut deps = AuditableDeps::default();
+        for package in packages {
+            let string = |key: &str| package.get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+
+            deps.packages.push(AuditablePackage {
+                name: string("name").ok_or_else(|| Error::other("package without a name"))?,
+                version: string("version").unwrap_or_default(),
+                source: string("source").unwrap_or_else(|| "crates.io".to_string()),
+                kind: match string("kind").as_deref() {
+                    Some("build") => DependencyKind::Build,
+                    _ => DependencyKind::Runtime,
+                },
+                dependencies: package.get("dependencies")
+                    .and_then(|d| d.as_array())
+                    .map(|d| d.iter().filter_map(|i| i.as_u64()).map(|i| i as usize).collect())
+                    .unwrap_or_default(),
+                root: package.get("root").and_then(|r| r.as_bool()).unwrap_or(false),
+            });
+        }
+
+        if let Some(bad) = deps.packages.iter().flat_map(|p| &p.dependencies).find(|&&i| i >= packages.len()) {
+            return Err(Error::other(format!("dependency index {} out of range", bad)));
+        }
+
+        Ok(deps)
+    }
+
+    pub fn root(&self) -> Option<&AuditablePackage> {
+        self.packages.iter().find(|p| p.root)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AuditablePackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+}
+
+/// A `core::panic::Location`, as embedded for panics, unwraps and
+/// #[track_caller] functions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanicLocation {
+    /// Address of the Location record.
+    pub addr: usize,
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl PanicLocation {
+    /// Returns the crate name and version if the file is in the cargo
+    /// registry, e.g. ("gimli", "0.29.0").
+    pub fn registry_crate(&self) -> Option<(String, String)> {
+        let (_, rest) = self.file.split_once(REGISTRY_SRC)?;
+        // Skip the registry index directory.
+        let dir = rest.split('/').nth(1)?;
+
+        let split = dir.char_indices()
+            .filter(|&(i, c)| c == '-' && dir[i + 1..].starts_with(|c: char| c.is_ascii_digit()))
+            .map(|(i, _)| i)
+            .next_back()?;
+        Some((dir[..split].to_string(), dir[split + 1..].to_string()))
+    }
+}
+
+impl fmt::Display for PanicLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl Elf {
+    /// Decodes the cargo-auditable dependency list, None if the binary was
+    /// not built with `cargo auditable`.
+    pub fn auditable_deps(&self) -> Result<Option<AuditableDeps>> {
+        match self.get_section_by_name(".dep-v0") {
+            Some(s) => Ok(Some(AuditableDeps::parse(self.get_section_contents(s)?)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Finds the panic Location records in the read-only data. Only the
+    /// program headers are needed, so this works on stripped binaries too.
+    pub fn panic_locations(&self) -> Result<Vec<PanicLocation>> {
+        let machine = self.header().e_machine;
+
+        // In position independent binaries the file pointers are only
+        // filled in by RELATIVE relocations.
+        let relative: HashMap<usize, usize> = self.get_dynamic_relocations()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|r| r.typ == R_X86_64_RELATIVE && machine == EM_X86_64
+                || r.typ == R_AARCH64_RELATIVE && machine == EM_AARCH64)
+            .filter_map(|r| Some((r.offset, r.addend? as usize)))
+            .collect();
+
+        let mut locations = Vec::new();
+        let mut seen = HashSet::new();
+        let data = self.as_bytes();
+
+        for seg in self.get_segments_by_type(SegmentType::Load) {
+            if seg.has_flag(SegmentFlag::Execute) {
+                continue;
+            }
+            let bytes = match data.get(seg.offset..seg.offset + seg.filesz) {
+                Some(b) => b,
+                None => continue,
+            };
+
+            // Locations are pointer aligned.
+            let skew = (8 - seg.vaddr % 8) % 8;
+            let mut off = skew;
+            while off + LOCATION_SIZE <= bytes.len() {
+                let addr = seg.vaddr + off;
+                let mut r = Reader::at(bytes, off);
+                let ptr = r.u64()? as usize;
+                let len = r.u64()? as usize;
+                let line = r.u32()?;
+                let column = r.u32()?;
+
+                let ptr = match ptr {
+                    0 => relative.get(&addr).copied().unwrap_or(0),
+                    p => p,
+                };
+
+                if ptr != 0 && line != 0 && len > 3 && len <= MAX_PATH_LEN {
+                    if let Some(file) = self.location_file(ptr, len) {
+                        if seen.insert((file.clone(), line, column)) {
+                            locations.push(PanicLocation { addr, file, line, column });
+                        }
+                        off += LOCATION_SIZE;
+                        continue;
+                    }
+                }
+                off += 8;
+            }
+        }
+
+        Ok(locations)
+    }
+
+    /// Returns the crates, with versions, that panic locations point into.
+    pub fn panic_location_crates(&self) -> Result<Vec<(String, String)>> {
+        let mut crates: Vec<(String, String)> = self.panic_locations()?
+            .iter()
+            .filter_map(|l| l.registry_crate())
+            .collect();
+        crates.sort();
+        crates.dedup();
+        Ok(crates)
+    }
+
+    fn location_file(&self, ptr: usize, len: usize) -> Option<String> {
+        let bytes = self.read_vaddr(ptr, len).ok()?;
+        if bytes.len() != len || !bytes.ends_with(b".rs") {
+            return None;
+        }
+        if bytes.iter().any(|&b| b < 0x20 || b == 0x7f) {
+            return None;
+        }
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
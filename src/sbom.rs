@@ -0,0 +1,370 @@
+use std::{
+    io::*,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::elf::Elf;
+
+const TOOL_NAME: &str = "frieren";
+const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ComponentKind {
+    Application,
+    Library,
+}
+
+/// An entry of the bill of materials.
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub name: String,
+    pub version: Option<String>,
+    pub kind: ComponentKind,
+    /// Package URL, e.g. "pkg:cargo/serde@1.0.210".
+    pub purl: Option<String>,
+    /// Where the component was found, e.g. "DT_NEEDED" or "cargo-auditable".
+    pub source: &'static str,
+    pub properties: Vec<(String, String)>,
+    /// Indices into `Sbom::components` of the components this one uses.
+    pub dependencies: Vec<usize>,
+}
+
+impl Component {
+    fn new(name: &str, kind: ComponentKind, source: &'static str) -> Self {
+        Component {
+            name: name.to_string(),
+            version: None,
+            kind,
+            purl: None,
+            source,
+            properties: Vec::new(),
+            dependencies: Vec::new(),
+        }
+    }
+}
+
+/// A software bill of materials for a single binary. The first component
+/// is the binary itself.
+#[derive(Debug, Clone)]
+pub struct Sbom {
+    pub components: Vec<Component>,
+    /// SHA-256 of the file, hex encoded.
+    pub sha256: String,
+    pub created: SystemTime,
+}
+
+impl Sbom {
+    pub fn main(&self) -> &Component {
+        &self.components[0]
+    }
+
+    /// Adds a component used by the binary, returning its index.
+    pub fn add(&mut self, component: Component) -> usize {
+        self.components.push(component);
+        let index = self.components.len() - 1;
+        self.components[0].dependencies.push(index);
+        index
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Component> {
+        self.components.iter().find(|c| c.name == name)
+    }
+
+    /// Serializes the SBOM as CycloneDX 1.5 JSON.
+    pub fn to_cyclonedx(&self) -> String {
+        let bom_ref = |i: usize| format!("component-{}", i);
+        let component = |i: usize| {
+            let c = &self.components[i];
+            let mut v = json!({
+                "type": match c.kind {
+                    ComponentKind::Application => "application",
+                    ComponentKind::Library => "library",
+                },
+                "bom-ref": bom_ref(i),
+                "name": c.name,
+            });
+            if let Some(version) = &c.version {
+                v["version"] = json!(version);
+            }
+            if let Some(purl) = &c.purl {
+                v["purl"] = json!(purl);
+            }
+            let mut properties = vec![json!({ "name": "frieren:source", "value": c.source })];
+            properties.extend(c.properties.iter().map(|(k, v)| json!({ "name": k, "value": v })));
+            v["properties"] = Value::Array(properties);
+            v
+        };
+
+        let mut main = component(0);
+        main["hashes"] = json!([{ "alg": "SHA-256", "content": self.sha256 }]);
+
+        let dependencies: Vec<Value> = self.components.iter().enumerate()
+            .map(|(i, c)| json!({
+                "ref": bom_ref(i),
+                "dependsOn": c.dependencies.iter().map(|&d| bom_ref(d)).collect::<Vec<_>>(),
+            }))
+            .collect();
+
+        let bom = json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "timestamp": rfc3339(self.created),
+                "tools": [{ "name": TOOL_NAME, "version": TOOL_VERSION }],
+                "component": main,
+            },
+            "components": (1..self.components.len()).map(component).collect::<Vec<_>>(),
+            "dependencies": dependencies,
+        });
+        serde_json::to_string_pretty(&bom).unwrap_or_default()
+    }
+
+    /// Serializes the SBOM as an SPDX 2.3 JSON document.
+    pub fn to_spdx(&self) -> String {
+        let spdx_id = |i: usize| format!("SPDXRef-Package-{}", i);
+
+        let packages: Vec<Value> = self.components.iter().enumerate()
+            .map(|(i, c)| {
+                let mut v = json!({
+                    "name": c.name,
+                    "SPDXID": spdx_id(i),
+                    "downloadLocation": "NOASSERTION",
+                    "filesAnalyzed": false,
+                    "primaryPackagePurpose": match c.kind {
+                        ComponentKind::Application => "APPLICATION",
+                        ComponentKind::Library => "LIBRARY",
+                    },
+                    "comment": format!("found through {}", c.source),
+                });
+                if let Some(version) = &c.version {
+                    v["versionInfo"] = json!(version);
+                }
+                if let Some(purl) = &c.purl {
+                    v["externalRefs"] = json!([{
+                        "referenceCategory": "PACKAGE-MANAGER",
+                        "referenceType": "purl",
+                        "referenceLocator": purl,
+                    }]);
+                }
+                if i == 0 {
+                    v["checksums"] = json!([{ "algorithm": "SHA256", "checksumValue": self.sha256 }]);
+                }
+                v
+            })
+            .collect();
+
+        let mut relationships = vec![json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": spdx_id(0),
+        })];
+        for (i, c) in self.components.iter().enumerate() {
+            for &dep in &c.dependencies {
+                relationships.push(json!({
+                    "spdxElementId": spdx_id(i),
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": spdx_id(dep),
+                }));
+            }
+        }
+
+        let name = &self.main().name;
+        let doc = json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": name,
+            "documentNamespace": format!("https://spdx.org/spdxdocs/{}-{}", purl_encode(name), self.sha256),
+            "creationInfo": {
+                "created": rfc3339(self.created),
+                "creators": [format!("Tool: {}-{}", TOOL_NAME, TOOL_VERSION)],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        });
+        serde_json::to_string_pretty(&doc).unwrap_or_default()
+    }
+}
+
+impl Elf {
+    /// Collects the components of the binary from DT_NEEDED and the symbol
+    /// versions required from each library, Go build info, cargo-auditable
+    /// data and the systemd package note. `path` names the artifact when
+    /// nothing inside it does. Metadata that is malformed is left out, like
+    /// metadata that is missing, rather than failing the whole SBOM.
+    pub fn sbom(&self, path: &str) -> Result<Sbom> {
+        let name = Path::new(path).file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string());
+
+        let mut sbom = Sbom {
+            components: vec![Component::new(&name, ComponentKind::Application, "file")],
+            sha256: Sha256::digest(self.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect(),
+            created: SystemTime::now(),
+        };
+
+        // The distribution package is the most authoritative name.
+        if let Ok(Some(package)) = self.package_metadata() {
+            let main = &mut sbom.components[0];
+            main.name = package.name.clone();
+            main.version = package.version.clone();
+            main.purl = Some(package.purl());
+            main.source = ".note.package";
+
+            let fields = [
+                ("type", Some(&package.typ)),
+                ("architecture", package.architecture.as_ref()),
+                ("os", package.os.as_ref()),
+                ("osVersion", package.os_version.as_ref()),
+                ("osCpe", package.os_cpe.as_ref()),
+                ("debugInfoUrl", package.debug_info_url.as_ref()),
+            ];
+            for (key, value) in fields {
+                if let Some(value) = value {
+                    main.properties.push((format!("package:{}", key), value.clone()));
+                }
+            }
+            for (key, value) in &package.extra {
+                if let Some(value) = value.as_str() {
+                    main.properties.push((format!("package:{}", key), value.to_string()));
+                }
+            }
+        }
+
+        if let Ok(Some(deps)) = self.auditable_deps() {
+            let mut indices = Vec::with_capacity(deps.packages.len());
+            for package in &deps.packages {
+                if package.root {
+                    if sbom.components[0].version.is_none() {
+                        let main = &mut sbom.components[0];
+                        main.name = package.name.clone();
+                        main.version = Some(package.version.clone());
+                        main.purl = cargo_purl(&package.name, &package.version, &package.source);
+                        main.source = "cargo-auditable";
+                    }
+                    indices.push(0);
+                    continue;
+                }
+
+                let mut component = Component::new(&package.name, ComponentKind::Library, "cargo-auditable");
+                component.version = Some(package.version.clone());
+                component.purl = cargo_purl(&package.name, &package.version, &package.source);
+                component.properties.push(("cargo:source".to_string(), package.source.clone()));
+                if package.kind == crate::rustinfo::DependencyKind::Build {
+                    component.properties.push(("cargo:kind".to_string(), "build".to_string()));
+                }
+                sbom.components.push(component);
+                indices.push(sbom.components.len() - 1);
+            }
+
+            // Keep the crate graph, with the root's edges on the binary.
+            for (package, &index) in deps.packages.iter().zip(&indices) {
+                let edges: Vec<usize> = package.dependencies.iter().map(|&d| indices[d]).collect();
+                sbom.components[index].dependencies.extend(edges);
+            }
+        }
+
+        if let Ok(info) = self.go_build_info() {
+            if let Some(module) = &info.main {
+                let main = &mut sbom.components[0];
+                if main.version.is_none() {
+                    main.name = module.path.clone();
+                    main.version = Some(module.version.clone());
+                    main.purl = go_purl(&module.path, &module.version);
+                    main.source = "Go build info";
+                }
+            }
+            if !info.path.is_empty() {
+                sbom.components[0].properties.push(("go:path".to_string(), info.path.clone()));
+            }
+            for (key, value) in &info.settings {
+                sbom.components[0].properties.push((format!("go:build:{}", key), value.clone()));
+            }
+
+            let go_version = info.go_version.trim_start_matches("go");
+            let mut stdlib = Component::new("stdlib", ComponentKind::Library, "Go build info");
+            stdlib.version = Some(go_version.to_string());
+            stdlib.purl = go_purl("stdlib", go_version);
+            sbom.add(stdlib);
+
+            for module in &info.deps {
+                let mut component = Component::new(&module.path, ComponentKind::Library, "Go build info");
+                component.version = Some(module.version.clone());
+                component.purl = go_purl(&module.path, &module.version);
+                if let Some(sum) = &module.sum {
+                    component.properties.push(("go:sum".to_string(), sum.clone()));
+                }
+                if let Some(replace) = &module.replace {
+                    component.properties.push(("go:replace".to_string(),
+                        format!("{} {}", replace.path, replace.version)));
+                }
+                sbom.add(component);
+            }
+        }
+
+        let requirements = self.get_version_requirements().unwrap_or_default();
+        for lib in self.needed_libraries().unwrap_or_default() {
+            let mut component = Component::new(&lib, ComponentKind::Library, "DT_NEEDED");
+            let versions: Vec<&str> = requirements.iter()
+                .filter(|r| r.file == lib)
+                .flat_map(|r| r.needs.iter().map(|n| n.name.as_str()))
+                .collect();
+            if !versions.is_empty() {
+                component.properties.push(("elf:required-versions".to_string(), versions.join(",")));
+            }
+            sbom.add(component);
+        }
+
+        Ok(sbom)
+    }
+}
+
+fn cargo_purl(name: &str, version: &str, source: &str) -> Option<String> {
+    match source {
+        "crates.io" => Some(format!("pkg:cargo/{}@{}", purl_encode(name), purl_encode(version))),
+        _ => None,
+    }
+}
+
+fn go_purl(path: &str, version: &str) -> Option<String> {
+    match version {
+        "" | "(devel)" => None,
+        _ => Some(format!("pkg:golang/{}@{}", path, purl_encode(version))),
+    }
+}
+
+pub(crate) fn purl_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' | b'+' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp.
+fn rfc3339(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // Days since the epoch to a civil date, from Howard Hinnant's algorithms.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
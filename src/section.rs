@@ -60,6 +60,24 @@ impl Section {
             }
         }
     }
+
+    pub fn has_flag(&self, flag: SectionFlag) -> bool {
+        self.flags & flag as usize != 0
+    }
+}
+
+pub enum SectionFlag {
+    Write =             0x1,        /* Writable */
+    Alloc =             0x2,        /* Occupies memory during execution */
+    ExecInstr =         0x4,        /* Executable */
+    Merge =             0x10,       /* Might be merged */
+    Strings =           0x20,       /* Contains nul-terminated strings */
+    InfoLink =          0x40,       /* `sh_info' contains SHT index */
+    LinkOrder =         0x80,       /* Preserve order after combining */
+    OsNonconforming =   0x100,      /* Non-standard OS specific handling required */
+    Group =             0x200,      /* Section is member of a group.  */
+    Tls =               0x400,      /* Section hold thread-local data.  */
+    Compressed =        0x800,      /* Section with compressed data. */
 }
 
 pub enum SectionType {
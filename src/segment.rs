@@ -1,4 +1,9 @@
-use std::fmt;
+use std::{
+    fmt,
+    io::*,
+    mem,
+    ptr,
+};
 
 #[derive(Debug, Default)]
 #[repr(C)]
@@ -21,14 +26,14 @@ impl fmt::Display for Phdr {
 
 #[derive(Debug)]
 pub struct Segment {
-    typ: u32,
-    flags: u32,
-    offset: usize,
-    vaddr: usize,
-    paddr: usize,
-    filesz: usize,
-    memsz: usize,
-    align: usize,
+    pub typ: u32,
+    pub flags: u32,
+    pub offset: usize,
+    pub vaddr: usize,
+    pub paddr: usize,
+    pub filesz: usize,
+    pub memsz: usize,
+    pub align: usize,
 }
 
 impl Segment {
@@ -48,9 +53,72 @@ impl Segment {
     }
 }
 
+impl Segment {
+    pub fn is_type(&self, typ: SegmentType) -> bool {
+        self.typ == typ as u32
+    }
+
+    pub fn has_flag(&self, flag: SegmentFlag) -> bool {
+        self.flags & flag as u32 != 0
+    }
+
+    /// Returns true if `vaddr` lies within the memory image of this segment.
+    pub fn contains_vaddr(&self, vaddr: usize) -> bool {
+        vaddr >= self.vaddr && vaddr - self.vaddr < self.memsz
+    }
+}
+
+/// Parses the program headers laid out in `data`, which need not be aligned.
+pub(crate) fn parse_phdrs(data: &[u8]) -> Vec<Segment> {
+    data.chunks_exact(mem::size_of::<Phdr>())
+        .map(|chunk| {
+            let phdr: Phdr = unsafe { ptr::read_unaligned(chunk.as_ptr() as *const Phdr) };
+            Segment::from_phdr_ptr(&phdr)
+        })
+        .collect()
+}
+
+/// Returns the link time address of the ELF header, mapped along with the
+/// start of the file by the first PT_LOAD.
+pub(crate) fn header_vaddr(segments: &[Segment]) -> Result<usize> {
+    let first = first_load(segments)?;
+    first.vaddr.checked_sub(first.offset)
+        .ok_or_else(|| Error::other("first PT_LOAD maps the file below address 0"))
+}
+
+/// Returns the PT_LOAD with the lowest address, the one mapping the start of
+/// the file.
+pub(crate) fn first_load(segments: &[Segment]) -> Result<&Segment> {
+    segments.iter()
+        .filter(|s| s.is_type(SegmentType::Load))
+        .min_by_key(|s| s.vaddr)
+        .ok_or_else(|| Error::other("no PT_LOAD segment"))
+}
+
 impl fmt::Display for Segment {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{:#x?}", self)
     }
 }
 
+
+pub enum SegmentType {
+    Null =          0,              /* Program header table entry unused */
+    Load =          1,              /* Loadable program segment */
+    Dynamic =       2,              /* Dynamic linking information */
+    Interp =        3,              /* Program interpreter */
+    Note =          4,              /* Auxiliary information */
+    Shlib =         5,              /* Reserved */
+    Phdr =          6,              /* Entry for header table itself */
+    Tls =           7,              /* Thread-local storage segment */
+    GnuEhFrame =    0x6474e550,     /* GCC .eh_frame_hdr segment */
+    GnuStack =      0x6474e551,     /* Indicates stack executability */
+    GnuRelro =      0x6474e552,     /* Read-only after relocation */
+    GnuProperty =   0x6474e553,     /* GNU property */
+}
+
+pub enum SegmentFlag {
+    Execute =   0x1,
+    Write =     0x2,
+    Read =      0x4,
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    io::*,
+    path::Path,
+    rc::Rc,
+};
+
+use crate::dwarf::{Dwarf, Location};
+use crate::elf::Elf;
+use crate::process::{MemoryMap, Process};
+use crate::segment::SegmentType;
+
+const PAGE_SIZE: u64 = 0x1000;
+const NT_GNU_BUILD_ID: u32 = 3;
+const DEBUG_DIR: &str = "/usr/lib/debug";
+
+/// A parsed object and its separate debug file.
+type CachedElf = (Rc<Elf>, Option<Rc<Elf>>);
+
+/// The build-id of a mapped file and its parsed object.
+type MappedFile = (Option<Vec<u8>>, CachedElf);
+
+/// An ELF object mapped into a process, along with its separate debug
+/// file if one was found.
+#[derive(Debug)]
+pub struct Module {
+    pub path: String,
+    pub start: u64,
+    pub end: u64,
+    /// Difference between the runtime and the link time addresses.
+    pub bias: u64,
+    pub build_id: Option<Vec<u8>>,
+    elf: Rc<Elf>,
+    debug: Option<Rc<Elf>>,
+}
+
+impl Module {
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.start && addr < self.end
+    }
+
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    pub fn elf(&self) -> &Elf {
+        &self.elf
+    }
+
+    pub fn debug_elf(&self) -> Option<&Elf> {
+        self.debug.as_deref()
+    }
+
+    /// The object DWARF is read from, the debug file if there is one.
+    fn dwarf_elf(&self) -> &Elf {
+        match &self.debug {
+            Some(debug) if debug.get_section_by_name(".debug_info").is_some() => debug,
+            _ => &self.elf,
+        }
+    }
+}
+
+/// An address resolved to `module!symbol+offset`, and to a source location
+/// when debug information is available.
+#[derive(Debug, Clone)]
+pub struct SymbolizedAddress {
+    pub addr: u64,
+    pub module: Option<String>,
+    /// The address translated to the module's link time address.
+    pub vaddr: u64,
+    pub symbol: Option<String>,
+    pub offset: u64,
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for SymbolizedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.module, &self.symbol) {
+            (Some(module), Some(symbol)) => write!(f, "{}!{}+{:#x}", module, symbol, self.offset)?,
+            (Some(module), None) => write!(f, "{}+{:#x}", module, self.vaddr)?,
+            (None, _) => write!(f, "{:#x}", self.addr)?,
+        }
+
+        if let Some(location) = &self.location {
+            write!(f, " ({})", location)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves addresses of a running process using /proc/<pid>/maps. Parsed
+/// objects are cached by the device and inode of their mapping, and shared
+/// by build-id between files, so they survive `refresh` and switching to
+/// another process.
+#[derive(Debug)]
+pub struct ProcessSymbolizer {
+    pub pid: u32,
+    modules: Vec<Module>,
+    cache: HashMap<Vec<u8>, CachedElf>,
+    /// Build-id and parsed object of each mapped file, by device and inode.
+    files: HashMap<(String, u64), MappedFile>,
+}
+
+impl ProcessSymbolizer {
+    pub fn new(pid: u32) -> Result<Self> {
+        let mut symbolizer = ProcessSymbolizer {
+            pid,
+            modules: Vec::new(),
+            cache: HashMap::new(),
+            files: HashMap::new(),
+        };
+        symbolizer.refresh()?;
+
+        Ok(symbolizer)
+    }
+
+    pub fn this() -> Result<Self> {
+        ProcessSymbolizer::new(std::process::id())
+    }
+
+    /// Switches to another process, keeping the cache.
+    pub fn attach(&mut self, pid: u32) -> Result<()> {
+        self.pid = pid;
+        self.refresh()
+    }
+
+    /// Re-reads the memory maps, e.g. after the process loaded a library.
+    pub fn refresh(&mut self) -> Result<()> {
+        let process = Process::open(self.pid)?;
+        let maps = process.maps()?;
+
+        let mut groups: Vec<Vec<&MemoryMap>> = Vec::new();
+        for map in &maps {
+            if map.inode == 0 || map.path.is_empty() || map.path.starts_with('[') {
+                continue;
+            }
+            match groups.last_mut() {
+                Some(group) if group[0].path == map.path => group.push(map),
+                _ => groups.push(vec![map]),
+            }
+        }
+
+        let mut modules = Vec::with_capacity(groups.len());
+        for group in groups {
+            // Objects that are not ELF, e.g. mapped data files, are skipped.
+            if let Ok(module) = self.load_module(&process, &group) {
+                modules.push(module);
+            }
+        }
+
+        self.modules = modules;
+        Ok(())
+    }
+
+    fn load_module(&mut self, process: &Process, maps: &[&MemoryMap]) -> Result<Module> {
+        let first = maps[0];
+        let last = maps[maps.len() - 1];
+        let path = first.path.trim_end_matches(" (deleted)").to_string();
+
+        let key = (first.dev.clone(), first.inode);
+        let (build_id, (elf, debug)) = match self.files.get(&key) {
+            Some(entry) => entry.clone(),
+            None => {
+                let entry = self.parse_module(process, first, &path)?;
+                self.files.insert(key, entry.clone());
+                entry
+            },
+        };
+
+        let bias = maps.iter()
+            .find_map(|map| load_bias(&elf, map))
+            .ok_or_else(|| Error::other(
+                    format!("no PT_LOAD of '{}' matches its mappings", path)))?;
+
+        Ok(Module {
+            path,
+            start: first.start,
+            end: last.end,
+            bias,
+            build_id,
+            elf,
+            debug,
+        })
+    }
+
+    fn parse_module(&mut self, process: &Process, first: &MemoryMap, path: &str)
+        -> Result<MappedFile> {
+        // map_files still works once the file has been deleted or replaced.
+        let map_file = format!("/proc/{}/map_files/{:x}-{:x}", self.pid, first.start, first.end);
+        let elf = match fs::read(&map_file).or_else(|_| fs::read(path)) {
+            Ok(data) => Elf::from_bytes(data)?,
+            Err(_) if first.offset == 0 => process.read_elf(first.start)?,
+            Err(e) => return Err(e),
+        };
+
+        let build_id = elf.get_note("GNU", NT_GNU_BUILD_ID)?.map(|n| n.desc.to_vec());
+        if let Some(entry) = build_id.as_ref().and_then(|id| self.cache.get(id)) {
+            return Ok((build_id, entry.clone()));
+        }
+
+        let debug = find_debug_file(&elf, path, build_id.as_deref()).map(Rc::new);
+        let entry = (Rc::new(elf), debug);
+        if let Some(id) = &build_id {
+            self.cache.insert(id.clone(), entry.clone());
+        }
+        Ok((build_id, entry))
+    }
+
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    pub fn find_module(&self, addr: u64) -> Option<&Module> {
+        self.modules.iter().find(|m| m.contains(addr))
+    }
+
+    pub fn symbolize(&self, addr: u64) -> SymbolizedAddress {
+        self.symbolize_many(&[addr]).remove(0)
+    }
+
+    /// Symbolizes a batch of addresses, e.g. a stack trace. DWARF is parsed
+    /// once per module rather than once per address.
+    pub fn symbolize_many(&self, addrs: &[u64]) -> Vec<SymbolizedAddress> {
+        let mut dwarfs: HashMap<usize, Option<Dwarf>> = HashMap::new();
+
+        addrs.iter().map(|&addr| {
+            let (index, module) = match self.modules.iter().enumerate().find(|(_, m)| m.contains(addr)) {
+                Some(found) => found,
+                None => return SymbolizedAddress {
+                    addr,
+                    module: None,
+                    vaddr: addr,
+                    symbol: None,
+                    offset: 0,
+                    location: None,
+                },
+            };
+
+            let vaddr = addr.wrapping_sub(module.bias);
+            let symbol = module.elf.symbolize(vaddr as usize)
+                .or_else(|| module.debug.as_ref()?.symbolize(vaddr as usize));
+
+            let dwarf = dwarfs.entry(index)
+                .or_insert_with(|| module.dwarf_elf().dwarf().ok());
+            let location = dwarf.as_ref()
+                .and_then(|d| d.find_location(vaddr).ok().flatten());
+
+            SymbolizedAddress {
+                addr,
+                module: Some(module.name().to_string()),
+                vaddr,
+                symbol: symbol.map(|(s, _)| s.name.clone()),
+                offset: symbol.map(|(_, off)| off as u64).unwrap_or(0),
+                location,
+            }
+        }).collect()
+    }
+}
+
+/// Computes the load bias from the PT_LOAD that `map` is a mapping of.
+fn load_bias(elf: &Elf, map: &MemoryMap) -> Option<u64> {
+    elf.get_segments_by_type(SegmentType::Load)
+        .find(|s| s.offset as u64 & !(PAGE_SIZE - 1) == map.offset)
+        .map(|s| map.start.wrapping_sub(s.vaddr as u64 & !(PAGE_SIZE - 1)))
+}
+
+/// Looks for a separate debug file, first by build-id then through
+/// .gnu_debuglink, in the places gdb searches by default.
+fn find_debug_file(elf: &Elf, path: &str, build_id: Option<&[u8]>) -> Option<Elf> {
+    if elf.get_section_by_name(".debug_info").is_some() {
+        return None;
+    }
+
+    let mut candidates = Vec::new();
+
+    if let Some(id) = build_id.filter(|id| id.len() > 1) {
+        let hex: String = id.iter().map(|b| format!("{:02x}", b)).collect();
+        candidates.push(format!("{}/.build-id/{}/{}.debug", DEBUG_DIR, &hex[..2], &hex[2..]));
+    }
+
+    if let Ok(link) = elf.get_section_bytes(".gnu_debuglink") {
+        let name = String::from_utf8_lossy(link.split(|&b| b == 0).next().unwrap_or(&[])).into_owned();
+        let dir = Path::new(path).parent().and_then(|d| d.to_str()).unwrap_or("");
+        candidates.push(format!("{}/{}", dir, name));
+        candidates.push(format!("{}/.debug/{}", dir, name));
+        candidates.push(format!("{}{}/{}", DEBUG_DIR, dir, name));
+    }
+
+    candidates.iter()
+        .filter(|c| c.as_str() != path)
+        .find_map(|c| Elf::open(c).ok())
+}
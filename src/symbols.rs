@@ -3,7 +3,7 @@ use std::{
     io::*
 };
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SymbolType {
     NoType = 0,
     Object,
@@ -19,7 +19,7 @@ pub enum SymbolType {
     HiProc = 15
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum SymbolBinding {
     Local,
     Global,
@@ -48,7 +48,7 @@ impl fmt::Display for ElfSym {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Symbol {
     pub name: String,
     pub binding: SymbolBinding,
@@ -92,6 +92,27 @@ impl Symbol {
         let end = self.value + self.size;
         val >= self.value && val < end
     }
+
+    /// Returns the demangled name, or None if the name is not mangled.
+    pub fn demangled(&self) -> Option<String> {
+        demangle(&self.name)
+    }
+}
+
+/// Demangles Rust (legacy and v0) and Itanium C++ ABI symbol names. The hash
+/// suffix of legacy Rust names is left out.
+pub fn demangle(name: &str) -> Option<String> {
+    // Legacy Rust names are valid Itanium names too, try Rust first.
+    if let Ok(sym) = rustc_demangle::try_demangle(name) {
+        return Some(format!("{:#}", sym));
+    }
+
+    if !name.starts_with("_Z") {
+        return None;
+    }
+    cpp_demangle::Symbol::new(name).ok()?
+        .demangle(&cpp_demangle::DemangleOptions::default())
+        .ok()
 }
 
 impl fmt::Display for Symbol {
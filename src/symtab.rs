@@ -0,0 +1,304 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::*,
+    mem,
+};
+
+use crate::elf::Elf;
+use crate::section::{Section, SectionFlag, SectionType, Shdr};
+use crate::symbols::{ElfSym, SymbolBinding, SymbolType};
+use crate::utils::{c_str_at, Reader};
+
+const SHN_UNDEF: u16 = 0;
+const SHN_LORESERVE: u16 = 0xff00;
+const SHN_ABS: u16 = 0xfff1;
+
+/* Ehdr field offsets */
+const EHDR_SHOFF: usize = 0x28;
+const EHDR_SHNUM: usize = 0x3c;
+const EHDR_SHSTRNDX: usize = 0x3e;
+
+/// A symbol to add to the symbol table.
+#[derive(Debug, Clone)]
+pub struct NewSymbol {
+    pub name: String,
+    pub addr: usize,
+    pub size: usize,
+    pub symbol_type: SymbolType,
+}
+
+impl<S: AsRef<str>> From<(S, usize, usize, SymbolType)> for NewSymbol {
+    fn from((name, addr, size, symbol_type): (S, usize, usize, SymbolType)) -> Self {
+        NewSymbol { name: name.as_ref().to_string(), addr, size, symbol_type }
+    }
+}
+
+/// Accumulates a string table, sharing identical strings.
+#[derive(Default)]
+pub(crate) struct StringTable {
+    data: Vec<u8>,
+    offsets: HashMap<String, u32>,
+}
+
+impl StringTable {
+    pub(crate) fn new() -> Self {
+        StringTable { data: vec![0], ..Default::default() }
+    }
+
+    pub(crate) fn add(&mut self, s: &str) -> u32 {
+        if s.is_empty() {
+            return 0;
+        }
+        if let Some(&off) = self.offsets.get(s) {
+            return off;
+        }
+
+        let off = self.data.len() as u32;
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(0);
+        self.offsets.insert(s.to_string(), off);
+        off
+    }
+}
+
+impl Elf {
+    /// Returns a copy of the file with `symbols` added to its `.symtab`,
+    /// creating the table if the binary was stripped. Symbols already in the
+    /// table are kept, new symbols with the same name are skipped. The
+    /// tables and the section headers are appended to the end of the file,
+    /// the old ones are left behind unreferenced.
+    pub fn with_symbols<I, S>(&self, symbols: I) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<NewSymbol>,
+    {
+        let mut sections: Vec<Shdr> = self.iter_sections().map(to_shdr).collect();
+        if sections.is_empty() {
+            // The section headers are gone entirely, start a new table.
+            sections.push(Shdr::default());
+        }
+
+        let mut strtab = StringTable::new();
+        let mut locals = vec![ElfSym::default()];
+        let mut globals = Vec::new();
+        let mut names = HashSet::new();
+
+        let old_symtab = self.get_section_by_name(".symtab")
+            .filter(|s| s.typ == SectionType::Symtab as u32);
+        if let Some(symtab) = old_symtab {
+            let old_strtab = self.iter_sections().nth(symtab.link as usize)
+                .ok_or_else(|| Error::other(".symtab has no string table"))?;
+            let old_strtab = self.get_section_contents(old_strtab)?;
+
+            let mut r = Reader::new(self.get_section_contents(symtab)?);
+            r.skip(mem::size_of::<ElfSym>())?;
+            while r.remaining() >= mem::size_of::<ElfSym>() {
+                let mut sym = read_sym(&mut r)?;
+                let name = c_str_at(old_strtab, sym.st_name as usize)?;
+                sym.st_name = strtab.add(&name);
+                names.insert(name);
+
+                match sym.st_info >> 4 {
+                    0 => locals.push(sym),
+                    _ => globals.push(sym),
+                }
+            }
+        }
+
+        for sym in symbols {
+            let sym: NewSymbol = sym.into();
+            if sym.name.is_empty() || !names.insert(sym.name.clone()) {
+                continue;
+            }
+
+            globals.push(ElfSym {
+                st_name: strtab.add(&sym.name),
+                st_info: ((SymbolBinding::Global as u8) << 4) | sym.symbol_type as u8,
+                st_other: 0,
+                st_shndx: self.section_index_of(sym.addr),
+                st_value: sym.addr,
+                st_size: sym.size,
+            });
+        }
+
+        // Names of the sections, reusing the existing .shstrtab.
+        let mut shstrtab = StringTable::new();
+        for (shdr, section) in sections.iter_mut().zip(self.iter_sections()) {
+            shdr.sh_name = shstrtab.add(&section.name);
+        }
+
+        let symtab_index = match old_symtab.and_then(|s| self.section_index(&s.name)) {
+            Some(i) => i,
+            None => {
+                sections.push(Shdr::default());
+                sections.len() - 1
+            },
+        };
+        let strtab_index = match old_symtab.map(|s| s.link as usize) {
+            // Leave a string table shared with something else alone.
+            Some(i) if self.iter_sections().nth(i).is_some_and(|s| s.name == ".strtab") => i,
+            _ => {
+                sections.push(Shdr::default());
+                sections.len() - 1
+            },
+        };
+        let shstrtab_index = match self.header().e_shstrndx as usize {
+            i if i != 0 && i < self.iter_sections().count() => i,
+            _ => {
+                sections.push(Shdr::default());
+                sections.len() - 1
+            },
+        };
+        sections[symtab_index].sh_name = shstrtab.add(".symtab");
+        sections[strtab_index].sh_name = shstrtab.add(".strtab");
+        sections[shstrtab_index].sh_name = shstrtab.add(".shstrtab");
+
+        let mut out = self.as_bytes().to_vec();
+
+        let offset = append(&mut out, &strtab.data, 1);
+        sections[strtab_index] = Shdr {
+            sh_name: sections[strtab_index].sh_name,
+            sh_type: SectionType::Strtab as u32,
+            sh_offset: offset,
+            sh_size: strtab.data.len(),
+            sh_addralign: 1,
+            ..Default::default()
+        };
+
+        let first_global = locals.len();
+        let mut data = Vec::with_capacity((locals.len() + globals.len()) * mem::size_of::<ElfSym>());
+        for sym in locals.iter().chain(&globals) {
+            write_sym(&mut data, sym);
+        }
+        let offset = append(&mut out, &data, 8);
+        sections[symtab_index] = Shdr {
+            sh_name: sections[symtab_index].sh_name,
+            sh_type: SectionType::Symtab as u32,
+            sh_offset: offset,
+            sh_size: data.len(),
+            sh_link: strtab_index as u32,
+            sh_info: first_global as u32,
+            sh_addralign: 8,
+            sh_entsize: mem::size_of::<ElfSym>(),
+            ..Default::default()
+        };
+
+        write_section_headers(&mut out, &mut sections, &shstrtab, shstrtab_index)?;
+
+        Ok(out)
+    }
+
+    /// Writes a copy of the file with `symbols` added to `path`.
+    pub fn write_with_symbols<I, S>(&self, path: &str, symbols: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<NewSymbol>,
+    {
+        fs::write(path, self.with_symbols(symbols)?)
+    }
+
+    pub(crate) fn section_index(&self, name: &str) -> Option<usize> {
+        self.iter_sections().position(|s| s.name == name)
+    }
+
+    /// The index of the allocated section containing `addr`, SHN_ABS if there
+    /// is none.
+    fn section_index_of(&self, addr: usize) -> u16 {
+        self.iter_sections()
+            .position(|s| s.has_flag(SectionFlag::Alloc) && addr >= s.addr && addr - s.addr < s.size)
+            .filter(|&i| i != SHN_UNDEF as usize)
+            .map(|i| i as u16)
+            .unwrap_or(SHN_ABS)
+    }
+}
+
+/// Appends the section name table and the section headers, and points the
+/// ELF header at them.
+pub(crate) fn write_section_headers(
+    out: &mut Vec<u8>,
+    sections: &mut [Shdr],
+    shstrtab: &StringTable,
+    shstrtab_index: usize,
+) -> Result<()> {
+    let offset = append(out, &shstrtab.data, 1);
+    sections[shstrtab_index] = Shdr {
+        sh_name: sections[shstrtab_index].sh_name,
+        sh_type: SectionType::Strtab as u32,
+        sh_offset: offset,
+        sh_size: shstrtab.data.len(),
+        sh_addralign: 1,
+        ..Default::default()
+    };
+
+    if sections.len() >= SHN_LORESERVE as usize {
+        return Err(Error::other("too many sections"));
+    }
+
+    let mut data = Vec::with_capacity(mem::size_of_val(sections));
+    for shdr in sections.iter() {
+        write_shdr(&mut data, shdr);
+    }
+    let shoff = append(out, &data, 8);
+
+    out[EHDR_SHOFF..EHDR_SHOFF + 8].copy_from_slice(&(shoff as u64).to_le_bytes());
+    out[EHDR_SHNUM..EHDR_SHNUM + 2].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+    out[EHDR_SHSTRNDX..EHDR_SHSTRNDX + 2].copy_from_slice(&(shstrtab_index as u16).to_le_bytes());
+    Ok(())
+}
+
+pub(crate) fn to_shdr(s: &Section) -> Shdr {
+    Shdr {
+        sh_name: s.name_offset,
+        sh_type: s.typ,
+        sh_flags: s.flags,
+        sh_addr: s.addr,
+        sh_offset: s.offset,
+        sh_size: s.size,
+        sh_link: s.link,
+        sh_info: s.info,
+        sh_addralign: s.addralign,
+        sh_entsize: s.entsize,
+    }
+}
+
+/// Appends `data` at an offset aligned to `align`, returning the offset.
+pub(crate) fn append(out: &mut Vec<u8>, data: &[u8], align: usize) -> usize {
+    out.resize(out.len().next_multiple_of(align), 0);
+    let offset = out.len();
+    out.extend_from_slice(data);
+    offset
+}
+
+fn read_sym(r: &mut Reader) -> Result<ElfSym> {
+    Ok(ElfSym {
+        st_name: r.u32()?,
+        st_info: r.u8()?,
+        st_other: r.u8()?,
+        st_shndx: r.u16()?,
+        st_value: r.u64()? as usize,
+        st_size: r.u64()? as usize,
+    })
+}
+
+fn write_sym(out: &mut Vec<u8>, sym: &ElfSym) {
+    out.extend_from_slice(&sym.st_name.to_le_bytes());
+    out.push(sym.st_info);
+    out.push(sym.st_other);
+    out.extend_from_slice(&sym.st_shndx.to_le_bytes());
+    out.extend_from_slice(&(sym.st_value as u64).to_le_bytes());
+    out.extend_from_slice(&(sym.st_size as u64).to_le_bytes());
+}
+
+fn write_shdr(out: &mut Vec<u8>, shdr: &Shdr) {
+    out.extend_from_slice(&shdr.sh_name.to_le_bytes());
+    out.extend_from_slice(&shdr.sh_type.to_le_bytes());
+    out.extend_from_slice(&(shdr.sh_flags as u64).to_le_bytes());
+    out.extend_from_slice(&(shdr.sh_addr as u64).to_le_bytes());
+    out.extend_from_slice(&(shdr.sh_offset as u64).to_le_bytes());
+    out.extend_from_slice(&(shdr.sh_size as u64).to_le_bytes());
+    out.extend_from_slice(&shdr.sh_link.to_le_bytes());
+    out.extend_from_slice(&shdr.sh_info.to_le_bytes());
+    out.extend_from_slice(&(shdr.sh_addralign as u64).to_le_bytes());
+    out.extend_from_slice(&(shdr.sh_entsize as u64).to_le_bytes());
+}
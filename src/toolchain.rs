@@ -0,0 +1,393 @@
+use std::{
+    fmt,
+    io::*,
+};
+
+use crate::dynamic::EntryType;
+use crate::elf::Elf;
+use crate::note::parse_notes;
+use crate::section::SectionType;
+use crate::segment::{SegmentFlag, SegmentType};
+use crate::utils::{c_str_at, Reader};
+
+/* Note types */
+const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+const NT_GNU_GOLD_VERSION: u32 = 4;
+
+/* GNU property types */
+const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc0000000;
+const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc0000002;
+const GNU_PROPERTY_X86_ISA_1_NEEDED: u32 = 0xc0008002;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Language {
+    C,
+    Cpp,
+    Rust,
+    Go,
+    Zig,
+    Swift,
+    Nim,
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Language::C => "C",
+            Language::Cpp => "C++",
+            Language::Rust => "Rust",
+            Language::Go => "Go",
+            Language::Zig => "Zig",
+            Language::Swift => "Swift",
+            Language::Nim => "Nim",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Linker {
+    Bfd,
+    Gold,
+    Lld,
+    Mold,
+    /// The Go toolchain's internal linker.
+    Go,
+}
+
+impl fmt::Display for Linker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Linker::Bfd => "GNU ld",
+            Linker::Gold => "GNU gold",
+            Linker::Lld => "LLD",
+            Linker::Mold => "mold",
+            Linker::Go => "Go internal linker",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What could be inferred about how a binary was built. Everything here is
+/// a best guess from whatever the toolchain left behind.
+#[derive(Debug, Clone, Default)]
+pub struct ToolchainInfo {
+    /// Languages that appear to have been linked in, sorted.
+    pub languages: Vec<Language>,
+    /// Compiler identification strings from .comment, e.g. "GCC: (GNU) 14.2.1".
+    pub compilers: Vec<String>,
+    /// DW_AT_producer of each distinct compile unit producer.
+    pub producers: Vec<String>,
+    pub linker: Option<Linker>,
+    pub linker_version: Option<String>,
+    /// e.g. "1.81.0", from .comment.
+    pub rustc_version: Option<String>,
+    /// Commit hash of the rustc that built the standard library, from the
+    /// source paths embedded in panic locations.
+    pub rustc_commit: Option<String>,
+    /// e.g. "go1.23.1"
+    pub go_version: Option<String>,
+    /// Decoded .note.gnu.property entries, e.g. "IBT" or "x86-64-v3".
+    pub properties: Vec<String>,
+}
+
+impl ToolchainInfo {
+    pub fn has_language(&self, lang: Language) -> bool {
+        self.languages.contains(&lang)
+    }
+
+    fn add_language(&mut self, lang: Language) {
+        if !self.has_language(lang) {
+            self.languages.push(lang);
+        }
+    }
+}
+
+impl fmt::Display for ToolchainInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let langs: Vec<String> = self.languages.iter().map(|l| l.to_string()).collect();
+        match langs.is_empty() {
+            true => writeln!(f, "languages: unknown")?,
+            false => writeln!(f, "languages: {}", langs.join(", "))?,
+        }
+        for compiler in &self.compilers {
+            writeln!(f, "compiler: {}", compiler)?;
+        }
+        for producer in &self.producers {
+            writeln!(f, "producer: {}", producer)?;
+        }
+        match (&self.linker, &self.linker_version) {
+            (Some(linker), Some(version)) => writeln!(f, "linker: {} {}", linker, version)?,
+            (Some(linker), None) => writeln!(f, "linker: {}", linker)?,
+            _ => (),
+        }
+        if let Some(version) = &self.rustc_version {
+            writeln!(f, "rustc: {}", version)?;
+        }
+        if let Some(commit) = &self.rustc_commit {
+            writeln!(f, "rustc commit: {}", commit)?;
+        }
+        if let Some(version) = &self.go_version {
+            writeln!(f, "go: {}", version)?;
+        }
+        if !self.properties.is_empty() {
+            writeln!(f, "properties: {}", self.properties.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Elf {
+    /// Fingerprints the source languages, compilers and linker of the binary.
+    pub fn toolchain_info(&self) -> Result<ToolchainInfo> {
+        let mut info = ToolchainInfo::default();
+
+        for comment in self.comments()? {
+            if let Some(version) = comment.strip_prefix("Linker: LLD ") {
+                info.linker = Some(Linker::Lld);
+                info.linker_version = Some(version.to_string());
+            } else if let Some(version) = comment.strip_prefix("mold ") {
+                info.linker = Some(Linker::Mold);
+                info.linker_version = version.split_whitespace().next().map(|v| v.to_string());
+            } else {
+                if let Some(version) = comment.strip_prefix("rustc version ") {
+                    info.rustc_version = version.split_whitespace().next().map(|v| v.to_string());
+                    info.add_language(Language::Rust);
+                }
+                if !info.compilers.contains(&comment) {
+                    info.compilers.push(comment);
+                }
+            }
+        }
+
+        if let Some(version) = self.gold_version()? {
+            info.linker = Some(Linker::Gold);
+            info.linker_version = Some(version);
+        }
+        info.properties = self.gnu_properties()?;
+
+        // Debug information is optional, ignore it if it can not be parsed.
+        if let Ok(units) = self.dwarf().and_then(|d| d.compile_units()) {
+            for unit in units {
+                if let Some(lang) = unit.language.and_then(dwarf_language) {
+                    info.add_language(lang);
+                }
+                if let Some(producer) = unit.producer {
+                    if !info.producers.contains(&producer) {
+                        info.producers.push(producer);
+                    }
+                }
+            }
+        }
+
+        if let Ok(build) = self.go_build_info() {
+            info.go_version = Some(build.go_version);
+            info.add_language(Language::Go);
+        } else if self.get_section_by_name(".gopclntab").is_some()
+            || self.get_section_by_name(".note.go.buildid").is_some() {
+            info.add_language(Language::Go);
+        }
+
+        for lib in self.needed_libraries()? {
+            if lib.starts_with("libstdc++.so") || lib.starts_with("libc++.so") {
+                info.add_language(Language::Cpp);
+            } else if lib.starts_with("libswiftCore.so") {
+                info.add_language(Language::Swift);
+            }
+        }
+
+        if self.iter_sections().any(|s| s.name.starts_with("swift5_")) {
+            info.add_language(Language::Swift);
+        }
+
+        for sym in self.iter_all_symbols() {
+            if let Some(lang) = symbol_language(&sym.name) {
+                info.add_language(lang);
+            }
+        }
+
+        if let Some(commit) = find_rustc_commit(self.as_bytes()) {
+            info.rustc_commit = Some(commit);
+            info.add_language(Language::Rust);
+        }
+
+        // A C compiler with nothing else to show for it probably compiled C.
+        let c_compiler = info.compilers.iter()
+            .any(|c| c.starts_with("GCC:") || c.contains("clang version"));
+        if info.languages.is_empty() && c_compiler {
+            info.add_language(Language::C);
+        }
+        info.languages.sort();
+
+        if info.linker.is_none() {
+            info.linker = self.guess_linker(&info);
+        }
+
+        Ok(info)
+    }
+
+    /// Returns the strings in the .comment section.
+    pub fn comments(&self) -> Result<Vec<String>> {
+        let data = match self.get_section_by_name(".comment") {
+            Some(s) => self.get_section_contents(s)?,
+            None => return Ok(Vec::new()),
+        };
+
+        Ok(data.split(|&b| b == 0)
+            .filter(|s| !s.is_empty())
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+            .collect())
+    }
+
+    /// Returns the names of the DT_NEEDED libraries.
+    pub fn needed_libraries(&self) -> Result<Vec<String>> {
+        let needed: Vec<usize> = self.dynamic.iter()
+            .filter(|d| d.tag == EntryType::Needed)
+            .map(|d| d.val)
+            .collect();
+        if needed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let strtab = self.dynamic_string_table()?;
+        needed.into_iter()
+            .map(|off| c_str_at(strtab, off))
+            .collect()
+    }
+
+    /// gold records its version in a note section that is not part of any
+    /// PT_NOTE segment.
+    fn gold_version(&self) -> Result<Option<String>> {
+        for section in self.get_section_by_type(SectionType::Note) {
+            let notes = parse_notes(self.get_section_contents(section)?, section.addralign)?;
+            if let Some(note) = notes.iter().find(|n| n.name == "GNU" && n.typ == NT_GNU_GOLD_VERSION) {
+                return Ok(Some(String::from_utf8_lossy(note.desc)
+                    .trim_end_matches('\0')
+                    .trim_start_matches("gold ")
+                    .to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn gnu_properties(&self) -> Result<Vec<String>> {
+        let note = match self.get_note("GNU", NT_GNU_PROPERTY_TYPE_0)? {
+            Some(n) => n,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut props = Vec::new();
+        let mut r = Reader::new(note.desc);
+        while r.remaining() >= 8 {
+            let typ = r.u32()?;
+            let size = r.u32()? as usize;
+            let data = r.bytes(size)?;
+            r.seek(r.pos().next_multiple_of(8).min(note.desc.len()));
+
+            let bits = match data.len() {
+                4.. => u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+                _ => continue,
+            };
+            let names: &[&str] = match typ {
+                GNU_PROPERTY_X86_FEATURE_1_AND => &["IBT", "SHSTK"],
+                GNU_PROPERTY_X86_ISA_1_NEEDED => &["x86-64-baseline", "x86-64-v2", "x86-64-v3", "x86-64-v4"],
+                GNU_PROPERTY_AARCH64_FEATURE_1_AND => &["BTI", "PAC"],
+                _ => continue,
+            };
+            for (i, name) in names.iter().enumerate() {
+                if bits & (1 << i) != 0 {
+                    props.push(name.to_string());
+                }
+            }
+        }
+
+        Ok(props)
+    }
+
+    /// Guesses the linker from the segment layout when it did not identify
+    /// itself. GNU ld puts .rodata after .text in a segment of its own, LLD
+    /// places it in the read-only segment before .text.
+    fn guess_linker(&self, info: &ToolchainInfo) -> Option<Linker> {
+        if info.has_language(Language::Go) && info.compilers.is_empty() {
+            return Some(Linker::Go);
+        }
+
+        let flags: Vec<(bool, bool)> = self.get_segments_by_type(SegmentType::Load)
+            .map(|s| (s.has_flag(SegmentFlag::Write), s.has_flag(SegmentFlag::Execute)))
+            .collect();
+        let text = self.get_section_by_name(".text")?;
+        let rodata = self.get_section_by_name(".rodata")?;
+
+        match flags.as_slice() {
+            // R, RX, R, RW with -z separate-code, RX, RW without.
+            [(false, false), (false, true), (false, false), (true, false)]
+            | [(false, true), (true, false)] if rodata.addr > text.addr => Some(Linker::Bfd),
+            [(false, false), (false, true), (true, false), ..] if rodata.addr < text.addr => Some(Linker::Lld),
+            _ => None,
+        }
+    }
+}
+
+fn dwarf_language(lang: u64) -> Option<Language> {
+    match lang {
+        0x01 | 0x02 | 0x0c | 0x1d | 0x2c => Some(Language::C),
+        0x04 | 0x19 | 0x1a | 0x21 | 0x2a | 0x2b => Some(Language::Cpp),
+        0x16 => Some(Language::Go),
+        0x1c => Some(Language::Rust),
+        0x1e => Some(Language::Swift),
+        0x27 => Some(Language::Zig),
+        _ => None,
+    }
+}
+
+/// Identifies languages with distinctive symbol names.
+fn symbol_language(name: &str) -> Option<Language> {
+    if is_rust_symbol(name) {
+        return Some(Language::Rust);
+    }
+    if name.starts_with("_Z") && cpp_demangle::Symbol::new(name).is_ok() {
+        return Some(Language::Cpp);
+    }
+    if name.starts_with("$s") || name.starts_with("_$s") {
+        return Some(Language::Swift);
+    }
+    if name == "NimMain" || name == "NimMainModule" {
+        return Some(Language::Nim);
+    }
+    if name.starts_with("__zig_") || name.starts_with("start.posixCallMainAndExit") {
+        return Some(Language::Zig);
+    }
+    None
+}
+
+/// Legacy Rust names are Itanium names, only those carrying the
+/// "17h<hash>E" path element are certainly Rust.
+fn is_rust_symbol(name: &str) -> bool {
+    if name.starts_with("_R") {
+        return rustc_demangle::try_demangle(name).is_ok();
+    }
+    if !name.starts_with("_ZN") {
+        return false;
+    }
+
+    name.match_indices("17h").any(|(i, _)| {
+        let hash = name.as_bytes().get(i + 3..i + 20);
+        hash.is_some_and(|h| h[..16].iter().all(|b| b.is_ascii_hexdigit()) && h[16] == b'E')
+    })
+}
+
+/// Looks for "/rustc/<commit>/" in the paths of standard library sources.
+fn find_rustc_commit(data: &[u8]) -> Option<String> {
+    const PREFIX: &[u8] = b"/rustc/";
+
+    let mut pos = 0;
+    while let Some(i) = data[pos..].windows(PREFIX.len()).position(|w| w == PREFIX) {
+        let start = pos + i + PREFIX.len();
+        let hash = data.get(start..start + 41)?;
+        if hash[..40].iter().all(|b| b.is_ascii_hexdigit()) && hash[40] == b'/' {
+            return Some(String::from_utf8_lossy(&hash[..40]).into_owned());
+        }
+        pos = start;
+    }
+    None
+}
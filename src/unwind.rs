@@ -0,0 +1,538 @@
+use std::{
+    fmt,
+    io::*,
+    ops::Range,
+};
+
+use crate::eh_frame::*;
+use crate::elf::Elf;
+use crate::segment::SegmentType;
+use crate::utils::Reader;
+
+const EM_X86_64: u16 = 62;
+const EM_AARCH64: u16 = 183;
+
+const MAX_REGISTERS: usize = 33;
+const MAX_EXPRESSION_STEPS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+}
+
+impl Arch {
+    pub fn from_machine(machine: u16) -> Option<Self> {
+        match machine {
+            EM_X86_64 => Some(Arch::X86_64),
+            EM_AARCH64 => Some(Arch::Aarch64),
+            _ => None,
+        }
+    }
+
+    /// The architecture this crate was compiled for.
+    pub fn host() -> Option<Self> {
+        if cfg!(target_arch = "x86_64") {
+            Some(Arch::X86_64)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Arch::Aarch64)
+        } else {
+            None
+        }
+    }
+
+    /// DWARF register number of the stack pointer.
+    pub fn sp_register(&self) -> u16 {
+        match self {
+            Arch::X86_64 => 7,
+            Arch::Aarch64 => 31,
+        }
+    }
+
+    /// DWARF register number of the frame pointer.
+    pub fn fp_register(&self) -> u16 {
+        match self {
+            Arch::X86_64 => 6,
+            Arch::Aarch64 => 29,
+        }
+    }
+
+    /// DWARF register number holding the program counter.
+    pub fn pc_register(&self) -> u16 {
+        match self {
+            Arch::X86_64 => 16,
+            Arch::Aarch64 => 32,
+        }
+    }
+}
+
+/// A register set indexed by DWARF register number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Registers {
+    pub arch: Arch,
+    values: [Option<u64>; MAX_REGISTERS],
+}
+
+impl Registers {
+    pub fn new(arch: Arch) -> Self {
+        Registers { arch, values: [None; MAX_REGISTERS] }
+    }
+
+    pub fn get(&self, register: u16) -> Option<u64> {
+        self.values.get(register as usize).copied().flatten()
+    }
+
+    pub fn set(&mut self, register: u16, value: u64) {
+        if let Some(v) = self.values.get_mut(register as usize) {
+            *v = Some(value);
+        }
+    }
+
+    pub fn clear(&mut self, register: u16) {
+        if let Some(v) = self.values.get_mut(register as usize) {
+            *v = None;
+        }
+    }
+
+    pub fn pc(&self) -> Option<u64> {
+        self.get(self.arch.pc_register())
+    }
+
+    pub fn sp(&self) -> Option<u64> {
+        self.get(self.arch.sp_register())
+    }
+
+    pub fn fp(&self) -> Option<u64> {
+        self.get(self.arch.fp_register())
+    }
+
+    pub fn set_pc(&mut self, value: u64) {
+        self.set(self.arch.pc_register(), value);
+    }
+
+    pub fn set_sp(&mut self, value: u64) {
+        self.set(self.arch.sp_register(), value);
+    }
+
+    pub fn set_fp(&mut self, value: u64) {
+        self.set(self.arch.fp_register(), value);
+    }
+}
+
+/// Access to the memory of the process being unwound, e.g. a core file or
+/// a live process through `/proc/<pid>/mem`.
+pub trait MemoryProvider {
+    fn read_memory(&self, addr: u64, buf: &mut [u8]) -> Result<()>;
+
+    fn read_u64(&self, addr: u64) -> Result<u64> {
+        let mut buf = [0u8; 8];
+        self.read_memory(addr, &mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+}
+
+/// A source of threads and their register state, on top of memory access.
+pub trait ThreadProvider: MemoryProvider {
+    fn threads(&self) -> Result<Vec<u32>>;
+    fn registers(&self, tid: u32) -> Result<Registers>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnwindMethod {
+    /// The frame the unwind started from.
+    Initial,
+    Cfi,
+    FramePointer,
+}
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub pc: u64,
+    pub sp: u64,
+    pub method: UnwindMethod,
+    pub module: Option<String>,
+    pub symbol: Option<String>,
+    pub symbol_offset: u64,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#018x}", self.pc)?;
+        match &self.symbol {
+            Some(sym) if self.symbol_offset != 0 => write!(f, " {}+{:#x}", sym, self.symbol_offset)?,
+            Some(sym) => write!(f, " {}", sym)?,
+            None => write!(f, " ??")?,
+        }
+
+        if let Some(module) = &self.module {
+            write!(f, " ({})", module)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The result of unwinding one frame through CFI.
+enum CfiStep {
+    Caller(Box<Registers>, bool),
+    /// The return address is undefined, which marks the outermost frame.
+    EndOfStack,
+}
+
+/// An ELF image mapped into the unwound address space at `bias`.
+#[derive(Debug)]
+pub struct Module<'a> {
+    pub name: String,
+    pub bias: u64,
+    /// Runtime address range covered by the PT_LOAD segments.
+    pub range: Range<u64>,
+    pub elf: &'a Elf,
+    eh_frame: Option<EhFrame<'a>>,
+}
+
+impl Module<'_> {
+    pub fn contains(&self, addr: u64) -> bool {
+        self.range.contains(&addr)
+    }
+}
+
+/// Walks stacks frame by frame using `.eh_frame` CFI, falling back to the
+/// frame pointer chain where no CFI covers the program counter.
+#[derive(Debug)]
+pub struct Unwinder<'a> {
+    modules: Vec<Module<'a>>,
+    pub max_frames: usize,
+}
+
+impl Default for Unwinder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Unwinder<'a> {
+    pub fn new() -> Self {
+        Unwinder { modules: Vec::new(), max_frames: 256 }
+    }
+
+    /// Registers an ELF image loaded at `bias`, i.e. the difference between
+    /// runtime and link time addresses.
+    pub fn add_module(&mut self, name: &str, elf: &'a Elf, bias: u64) {
+        let mut start = u64::MAX;
+        let mut end = 0;
+        for seg in elf.get_segments_by_type(SegmentType::Load) {
+            start = start.min(seg.vaddr as u64);
+            end = end.max((seg.vaddr + seg.memsz) as u64);
+        }
+
+        if start > end {
+            start = 0;
+        }
+
+        self.modules.push(Module {
+            name: name.to_string(),
+            bias,
+            range: start.wrapping_add(bias)..end.wrapping_add(bias),
+            elf,
+            eh_frame: elf.eh_frame().ok(),
+        });
+    }
+
+    pub fn modules(&self) -> &[Module<'a>] {
+        &self.modules
+    }
+
+    pub fn find_module(&self, addr: u64) -> Option<&Module<'a>> {
+        self.modules.iter().find(|m| m.contains(addr))
+    }
+
+    /// Resolves `addr` into a module, symbol and offset.
+    pub fn symbolize(&self, addr: u64) -> (Option<String>, Option<String>, u64) {
+        let module = match self.find_module(addr) {
+            Some(m) => m,
+            None => return (None, None, 0),
+        };
+
+        let rel = addr.wrapping_sub(module.bias) as usize;
+        match module.elf.symbolize(rel) {
+            Some((sym, off)) => (Some(module.name.clone()), Some(sym.name.clone()), off as u64),
+            None => (Some(module.name.clone()), None, 0),
+        }
+    }
+
+    /// Unwinds the stack described by `regs`, innermost frame first.
+    pub fn unwind(&self, regs: &Registers, mem: &dyn MemoryProvider) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        let mut regs = regs.clone();
+        let mut method = UnwindMethod::Initial;
+        let mut signal_frame = false;
+
+        while frames.len() < self.max_frames.max(1) {
+            let pc = match regs.pc() {
+                Some(pc) if pc != 0 => pc,
+                _ => break,
+            };
+            let sp = regs.sp().unwrap_or(0);
+
+            // Return addresses point after the call, which may be outside the
+            // caller's FDE for noreturn calls at the end of a function
+            let lookup_pc = if method == UnwindMethod::Initial || signal_frame { pc } else { pc - 1 };
+
+            let (module, symbol, symbol_offset) = self.symbolize(lookup_pc);
+            let symbol_offset = symbol_offset + (pc - lookup_pc);
+            frames.push(Frame { pc, sp, method, module, symbol, symbol_offset });
+
+            let next = match self.step_cfi(&regs, lookup_pc, mem) {
+                Some(CfiStep::Caller(next, signal)) => {
+                    method = UnwindMethod::Cfi;
+                    signal_frame = signal;
+                    Some(*next)
+                },
+                Some(CfiStep::EndOfStack) => break,
+                None => {
+                    method = UnwindMethod::FramePointer;
+                    signal_frame = false;
+                    step_frame_pointer(&regs, mem)
+                },
+            };
+
+            let next = match next {
+                Some(n) => n,
+                None => break,
+            };
+
+            // The stack grows down, a frame which does not move up is a loop
+            let next_sp = next.sp().unwrap_or(0);
+            if next_sp < sp || (next_sp == sp && next.pc() == regs.pc()) {
+                break;
+            }
+
+            regs = next;
+        }
+
+        frames
+    }
+
+    /// Unwinds every thread known to `provider`.
+    pub fn unwind_all(&self, provider: &dyn ThreadProvider) -> Result<Vec<(u32, Vec<Frame>)>> {
+        let mut stacks = Vec::new();
+        for tid in provider.threads()? {
+            let regs = provider.registers(tid)?;
+            stacks.push((tid, self.unwind(&regs, provider)));
+        }
+
+        Ok(stacks)
+    }
+
+    fn step_cfi(&self, regs: &Registers, pc: u64, mem: &dyn MemoryProvider) -> Option<CfiStep> {
+        let module = self.find_module(pc)?;
+        let eh_frame = module.eh_frame.as_ref()?;
+        let row = eh_frame.unwind_row(pc.wrapping_sub(module.bias)).ok()??;
+
+        if matches!(row.register(row.return_address_register), Some(RegisterRule::Undefined)) {
+            return Some(CfiStep::EndOfStack);
+        }
+
+        let cfa = match &row.cfa {
+            CfaRule::RegisterOffset { register, offset } =>
+                regs.get(*register)?.wrapping_add(*offset as u64),
+            CfaRule::Expression(expr) => evaluate(expr, regs, mem, None).ok()?,
+        };
+
+        let mut next = regs.clone();
+        for (register, rule) in &row.registers {
+            let value = match rule {
+                RegisterRule::Undefined => None,
+                RegisterRule::SameValue => regs.get(*register),
+                RegisterRule::Offset(n) => mem.read_u64(cfa.wrapping_add(*n as u64)).ok(),
+                RegisterRule::ValOffset(n) => Some(cfa.wrapping_add(*n as u64)),
+                RegisterRule::Register(r) => regs.get(*r),
+                RegisterRule::Expression(expr) => {
+                    let addr = evaluate(expr, regs, mem, Some(cfa)).ok()?;
+                    mem.read_u64(addr).ok()
+                },
+                RegisterRule::ValExpression(expr) => evaluate(expr, regs, mem, Some(cfa)).ok(),
+            };
+
+            match value {
+                Some(v) => next.set(*register, v),
+                None => next.clear(*register),
+            }
+        }
+
+        let ra = next.get(row.return_address_register)?;
+        next.set_pc(strip_pointer_auth(regs.arch, ra));
+        next.set_sp(cfa);
+
+        Some(CfiStep::Caller(Box::new(next), row.signal_frame))
+    }
+}
+
+/// Follows the saved frame pointer / return address pair. Both x86_64 and
+/// aarch64 store them next to each other at the frame pointer.
+fn step_frame_pointer(regs: &Registers, mem: &dyn MemoryProvider) -> Option<Registers> {
+    let fp = regs.fp()?;
+    if fp == 0 || fp % 8 != 0 {
+        return None;
+    }
+
+    let saved_fp = mem.read_u64(fp).ok()?;
+    let ra = mem.read_u64(fp + 8).ok()?;
+
+    let mut next = regs.clone();
+    next.set_fp(saved_fp);
+    next.set_sp(fp + 16);
+    next.set_pc(strip_pointer_auth(regs.arch, ra));
+    if regs.arch == Arch::Aarch64 {
+        next.set(30, ra);
+    }
+
+    Some(next)
+}
+
+fn strip_pointer_auth(arch: Arch, addr: u64) -> u64 {
+    match arch {
+        Arch::Aarch64 => addr & 0x0000_ffff_ffff_ffff,
+        Arch::X86_64 => addr,
+    }
+}
+
+/// Evaluates a DWARF expression as used by CFI rules, returning the value at
+/// the top of the stack. `initial` is pushed before evaluation, which is how
+/// the CFA is passed to register rules.
+pub fn evaluate(expr: &[u8], regs: &Registers, mem: &dyn MemoryProvider, initial: Option<u64>) -> Result<u64> {
+    let mut stack: Vec<u64> = initial.into_iter().collect();
+    let mut r = Reader::new(expr);
+    let mut steps = 0;
+
+    let pop = |stack: &mut Vec<u64>| stack.pop()
+        .ok_or_else(|| Error::other("DWARF expression stack underflow"));
+    let reg = |n: u16| regs.get(n)
+        .ok_or_else(|| Error::other(format!("DWARF expression uses unknown register {}", n)));
+
+    while !r.is_empty() {
+        steps += 1;
+        if steps > MAX_EXPRESSION_STEPS {
+            return Err(Error::other("DWARF expression did not terminate"));
+        }
+
+        let op = r.u8()?;
+        match op {
+            0x03 => stack.push(r.u64()?),
+            0x06 => {
+                let addr = pop(&mut stack)?;
+                stack.push(mem.read_u64(addr)?);
+            },
+            0x08 => stack.push(r.u8()? as u64),
+            0x09 => stack.push(r.i8()? as i64 as u64),
+            0x0a => stack.push(r.u16()? as u64),
+            0x0b => stack.push(r.i16()? as i64 as u64),
+            0x0c => stack.push(r.u32()? as u64),
+            0x0d => stack.push(r.i32()? as i64 as u64),
+            0x0e => stack.push(r.u64()?),
+            0x0f => stack.push(r.i64()? as u64),
+            0x10 => stack.push(r.uleb128()?),
+            0x11 => stack.push(r.sleb128()? as u64),
+            0x12 => {
+                let v = *stack.last().ok_or_else(|| Error::other("DWARF expression stack underflow"))?;
+                stack.push(v);
+            },
+            0x13 => {
+                pop(&mut stack)?;
+            },
+            0x14 | 0x15 => {
+                let idx = if op == 0x14 { 1 } else { r.u8()? as usize };
+                let v = match stack.len().checked_sub(idx + 1) {
+                    Some(i) => stack[i],
+                    None => return Err(Error::other("DWARF expression stack underflow")),
+                };
+                stack.push(v);
+            },
+            0x16 => {
+                let a = pop(&mut stack)?;
+                let b = pop(&mut stack)?;
+                stack.push(a);
+                stack.push(b);
+            },
+            0x17 => {
+                let a = pop(&mut stack)?;
+                let b = pop(&mut stack)?;
+                let c = pop(&mut stack)?;
+                stack.push(a);
+                stack.push(c);
+                stack.push(b);
+            },
+            0x19 => {
+                let a = pop(&mut stack)? as i64;
+                stack.push(a.unsigned_abs());
+            },
+            0x1f => {
+                let a = pop(&mut stack)? as i64;
+                stack.push(a.wrapping_neg() as u64);
+            },
+            0x20 => {
+                let a = pop(&mut stack)?;
+                stack.push(!a);
+            },
+            0x1a | 0x1b | 0x1c | 0x1d | 0x1e | 0x21 | 0x22 | 0x24 | 0x25 | 0x26 | 0x27
+                | 0x29..=0x2e => {
+                let b = pop(&mut stack)?;
+                let a = pop(&mut stack)?;
+                let v = match op {
+                    0x1a => a & b,
+                    0x1b => if b == 0 { 0 } else { (a as i64).wrapping_div(b as i64) as u64 },
+                    0x1c => a.wrapping_sub(b),
+                    0x1d => if b == 0 { 0 } else { a % b },
+                    0x1e => a.wrapping_mul(b),
+                    0x21 => a | b,
+                    0x22 => a.wrapping_add(b),
+                    0x24 => a.checked_shl(b as u32).unwrap_or(0),
+                    0x25 => a.checked_shr(b as u32).unwrap_or(0),
+                    0x26 => (a as i64).checked_shr(b as u32).unwrap_or(0) as u64,
+                    0x27 => a ^ b,
+                    0x29 => (a == b) as u64,
+                    0x2a => ((a as i64) >= (b as i64)) as u64,
+                    0x2b => ((a as i64) > (b as i64)) as u64,
+                    0x2c => ((a as i64) <= (b as i64)) as u64,
+                    0x2d => ((a as i64) < (b as i64)) as u64,
+                    _ => (a != b) as u64,
+                };
+                stack.push(v);
+            },
+            0x23 => {
+                let a = pop(&mut stack)?;
+                stack.push(a.wrapping_add(r.uleb128()?));
+            },
+            0x28 => {
+                let offset = r.i16()? as i64;
+                if pop(&mut stack)? != 0 {
+                    r.seek((r.pos() as i64 + offset) as usize);
+                }
+            },
+            0x2f => {
+                let offset = r.i16()? as i64;
+                r.seek((r.pos() as i64 + offset) as usize);
+            },
+            0x30..=0x4f => stack.push((op - 0x30) as u64),
+            0x50..=0x6f => stack.push(reg((op - 0x50) as u16)?),
+            0x70..=0x8f => {
+                let offset = r.sleb128()?;
+                stack.push(reg((op - 0x70) as u16)?.wrapping_add(offset as u64));
+            },
+            0x90 => {
+                let n = r.uleb128()? as u16;
+                stack.push(reg(n)?);
+            },
+            0x92 => {
+                let n = r.uleb128()? as u16;
+                let offset = r.sleb128()?;
+                stack.push(reg(n)?.wrapping_add(offset as u64));
+            },
+            0x96 => (),
+            _ => return Err(Error::other(
+                    format!("Unsupported DWARF expression opcode {:#x}", op))),
+        }
+    }
+
+    pop(&mut stack)
+}
@@ -34,3 +34,185 @@ pub fn c_str_to_string(ptr: *const u8) -> Result<String> {
     }
 }
 
+
+/// Reads a NUL terminated string starting at `offset` within `data`.
+pub fn c_str_at(data: &[u8], offset: usize) -> Result<String> {
+    let bytes = match data.get(offset..) {
+        Some(b) => b,
+        None => return Err(Error::other(
+                format!("string offset {:#x} out of bounds", offset))),
+    };
+
+    let len = match bytes.iter().position(|&b| b == 0) {
+        Some(l) => l,
+        None => return Err(Error::other(
+                format!("unterminated string at offset {:#x}", offset))),
+    };
+
+    Ok(String::from_utf8_lossy(&bytes[..len]).into_owned())
+}
+
+/// A little endian cursor over a byte slice.
+///
+/// Most of the tables found in debug and metadata sections are variable length
+/// records, which are not a good fit for the `ptr::read` approach used for the
+/// fixed size headers.
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    pub fn at(data: &'a [u8], pos: usize) -> Self {
+        Reader { data, pos }
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = match self.pos.checked_add(len) {
+            Some(e) if e <= self.data.len() => e,
+            _ => return Err(Error::new(ErrorKind::UnexpectedEof,
+                    format!("read of {} bytes at {:#x} out of bounds", len, self.pos))),
+        };
+
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn skip(&mut self, len: usize) -> Result<()> {
+        self.bytes(len)?;
+        Ok(())
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        let b = self.bytes(8)?;
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(b);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub fn i8(&mut self) -> Result<i8> {
+        Ok(self.u8()? as i8)
+    }
+
+    pub fn i16(&mut self) -> Result<i16> {
+        Ok(self.u16()? as i16)
+    }
+
+    pub fn i32(&mut self) -> Result<i32> {
+        Ok(self.u32()? as i32)
+    }
+
+    pub fn i64(&mut self) -> Result<i64> {
+        Ok(self.u64()? as i64)
+    }
+
+    /// Reads an unsigned integer of `size` bytes.
+    pub fn uint(&mut self, size: usize) -> Result<u64> {
+        match size {
+            1 => Ok(self.u8()? as u64),
+            2 => Ok(self.u16()? as u64),
+            4 => Ok(self.u32()? as u64),
+            8 => self.u64(),
+            _ => Err(Error::other(format!("unsupported integer size: {}", size))),
+        }
+    }
+
+    pub fn uleb128(&mut self) -> Result<u64> {
+        let mut result: u64 = 0;
+        let mut shift = 0;
+
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= ((byte & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+
+    pub fn sleb128(&mut self) -> Result<i64> {
+        let mut result: i64 = 0;
+        let mut shift = 0;
+        let mut byte;
+
+        loop {
+            byte = self.u8()?;
+            if shift < 64 {
+                result |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        if shift < 64 && byte & 0x40 != 0 {
+            result |= -1i64 << shift;
+        }
+
+        Ok(result)
+    }
+
+    /// Reads a NUL terminated string, returning the bytes without the terminator.
+    pub fn c_str(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = match rest.iter().position(|&b| b == 0) {
+            Some(l) => l,
+            None => return Err(Error::new(ErrorKind::UnexpectedEof,
+                    format!("unterminated string at {:#x}", self.pos))),
+        };
+
+        let s = &rest[..len];
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    pub fn c_string(&mut self) -> Result<String> {
+        Ok(String::from_utf8_lossy(self.c_str()?).into_owned())
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    io::*,
+    sync::OnceLock,
+};
+
+use crate::auxv::Auxv;
+use crate::elf::Elf;
+use crate::image::ProcessImage;
+
+/// The vDSO the kernel maps into every process.
+#[derive(Debug)]
+pub struct Vdso {
+    pub base: usize,
+    pub bias: usize,
+    elf: Elf,
+}
+
+impl Vdso {
+    /// Locates the vDSO of the current process through AT_SYSINFO_EHDR.
+    pub fn this() -> Result<Self> {
+        let base = match Auxv::this() {
+            Ok(auxv) => auxv.sysinfo_ehdr(),
+            Err(_) => None,
+        };
+        let base = match base {
+            Some(base) => base as usize,
+            None => unsafe { libc::getauxval(libc::AT_SYSINFO_EHDR) as usize },
+        };
+
+        if base == 0 {
+            return Err(Error::other("no vDSO mapped"));
+        }
+
+        unsafe { Vdso::at(base) }
+    }
+
+    /// Parses the vDSO whose ELF header is at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the address of the vDSO, e.g. from AT_SYSINFO_EHDR.
+    pub unsafe fn at(base: usize) -> Result<Self> {
+        // Old kernels linked the vDSO at a fixed address, newer ones at 0,
+        // from_base works the bias out either way.
+        let image = ProcessImage::from_base("[vdso]".to_string(), base)?;
+
+        Ok(Vdso { base, bias: image.bias(), elf: image.to_elf()? })
+    }
+
+    pub fn elf(&self) -> &Elf {
+        &self.elf
+    }
+
+    /// Returns the runtime address of the function `name` with the given
+    /// version, e.g. ("__vdso_clock_gettime", "LINUX_2.6").
+    pub fn lookup(&self, name: &str, version: &str) -> Option<usize> {
+        let sym = self.elf.lookup_versioned_symbol(name, Some(version)).ok()??;
+        Some(self.bias.wrapping_add(sym.value))
+    }
+}
+
+/// Looks up a vDSO function of the current process. The vDSO is parsed once
+/// and cached. The result must be transmuted to the right function type.
+pub fn lookup(name: &str, version: &str) -> Option<*const ()> {
+    static VDSO: OnceLock<Option<Vdso>> = OnceLock::new();
+
+    VDSO.get_or_init(|| Vdso::this().ok())
+        .as_ref()?
+        .lookup(name, version)
+        .map(|addr| addr as *const ())
+}
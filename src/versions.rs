@@ -0,0 +1,246 @@
+use std::{
+    fmt,
+    io::*,
+};
+
+use crate::dynamic::EntryType;
+use crate::elf::Elf;
+use crate::symbols::Symbol;
+use crate::utils::*;
+
+pub const VER_NDX_LOCAL: u16 = 0;
+pub const VER_NDX_GLOBAL: u16 = 1;
+/* Set in a .gnu.version entry for non default versions (symbol@VERSION) */
+pub const VERSYM_HIDDEN: u16 = 0x8000;
+
+pub const VER_FLG_BASE: u16 = 0x1;
+pub const VER_FLG_WEAK: u16 = 0x2;
+
+/// An entry of .gnu.version_d (DT_VERDEF).
+#[derive(Debug, Clone)]
+pub struct VersionDefinition {
+    pub version: u16,
+    pub flags: u16,
+    /// The index used in .gnu.version to refer to this definition.
+    pub index: u16,
+    pub hash: u32,
+    /// The version name followed by the names of its predecessors.
+    pub names: Vec<String>,
+}
+
+impl VersionDefinition {
+    pub fn name(&self) -> &str {
+        self.names.first().map(|n| n.as_str()).unwrap_or("")
+    }
+
+    /// The base definition names the object itself rather than a version.
+    pub fn is_base(&self) -> bool {
+        self.flags & VER_FLG_BASE != 0
+    }
+}
+
+impl fmt::Display for VersionDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.index, self.names.join(" <- "))
+    }
+}
+
+/// An entry of .gnu.version_r (DT_VERNEED), the versions needed from `file`.
+#[derive(Debug, Clone)]
+pub struct VersionRequirement {
+    pub version: u16,
+    pub file: String,
+    pub needs: Vec<VersionNeed>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VersionNeed {
+    pub hash: u32,
+    pub flags: u16,
+    /// The index used in .gnu.version to refer to this version.
+    pub index: u16,
+    pub name: String,
+}
+
+impl fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.file)?;
+        for need in &self.needs {
+            write!(f, " {}({})", need.name, need.index)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `count` Elf64_Verdef records and their Elf64_Verdaux entries.
+pub fn parse_verdef(data: &[u8], count: usize, strtab: &[u8]) -> Result<Vec<VersionDefinition>> {
+    // The count comes from the file, a Verdef record takes at least 20 bytes.
+    let mut defs = Vec::with_capacity(count.min(data.len() / 20));
+    let mut offset = 0;
+
+    for _ in 0..count {
+        let mut r = Reader::at(data, offset);
+        let version = r.u16()?;
+        let flags = r.u16()?;
+        let index = r.u16()?;
+        let cnt = r.u16()?;
+        let hash = r.u32()?;
+        let aux = r.u32()? as usize;
+        let next = r.u32()? as usize;
+
+        let mut names = Vec::with_capacity(cnt as usize);
+        let mut aux_offset = offset + aux;
+        for _ in 0..cnt {
+            let mut r = Reader::at(data, aux_offset);
+            let name = r.u32()? as usize;
+            let aux_next = r.u32()? as usize;
+
+            names.push(c_str_at(strtab, name)?);
+            aux_offset += aux_next;
+        }
+
+        defs.push(VersionDefinition { version, flags, index, hash, names });
+
+        if next == 0 {
+            break;
+        }
+        offset += next;
+    }
+
+    Ok(defs)
+}
+
+/// Parses `count` Elf64_Verneed records and their Elf64_Vernaux entries.
+pub fn parse_verneed(data: &[u8], count: usize, strtab: &[u8]) -> Result<Vec<VersionRequirement>> {
+    // As for parse_verdef, a Verneed record takes at least 16 bytes.
+    let mut reqs = Vec::with_capacity(count.min(data.len() / 16));
+    let mut offset = 0;
+
+    for _ in 0..count {
+        let mut r = Reader::at(data, offset);
+        let version = r.u16()?;
+        let cnt = r.u16()?;
+        let file = r.u32()? as usize;
+        let aux = r.u32()? as usize;
+        let next = r.u32()? as usize;
+
+        let mut needs = Vec::with_capacity(cnt as usize);
+        let mut aux_offset = offset + aux;
+        for _ in 0..cnt {
+            let mut r = Reader::at(data, aux_offset);
+            let hash = r.u32()?;
+            let flags = r.u16()?;
+            let index = r.u16()?;
+            let name = r.u32()? as usize;
+            let aux_next = r.u32()? as usize;
+
+            needs.push(VersionNeed { hash, flags, index, name: c_str_at(strtab, name)? });
+            aux_offset += aux_next;
+        }
+
+        reqs.push(VersionRequirement { version, file: c_str_at(strtab, file)?, needs });
+
+        if next == 0 {
+            break;
+        }
+        offset += next;
+    }
+
+    Ok(reqs)
+}
+
+impl Elf {
+    pub(crate) fn dynamic_string_table(&self) -> Result<&[u8]> {
+        match (self.get_dynamic_vaddr(EntryType::Strtab), self.get_dynamic(EntryType::StrSize)) {
+            (Some(strtab), Some(size)) => self.read_vaddr(strtab, size),
+            _ => Err(Error::other("no dynamic string table")),
+        }
+    }
+
+    /// Returns the .gnu.version table, one entry per dynamic symbol.
+    pub fn get_versym(&self) -> Result<Vec<u16>> {
+        let vaddr = match self.get_dynamic_vaddr(EntryType::VerSym) {
+            Some(v) => v,
+            None => return Ok(Vec::new()),
+        };
+
+        let count = self.iter_dynamic_symbols().count();
+        let mut r = Reader::new(self.read_vaddr(vaddr, count * 2)?);
+        (0..count).map(|_| r.u16()).collect()
+    }
+
+    pub fn get_version_definitions(&self) -> Result<Vec<VersionDefinition>> {
+        match (self.get_dynamic_vaddr(EntryType::VerDef), self.get_dynamic(EntryType::VerDefNum)) {
+            (Some(vaddr), Some(count)) =>
+                parse_verdef(self.read_vaddr(vaddr, usize::MAX)?, count, self.dynamic_string_table()?),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub fn get_version_requirements(&self) -> Result<Vec<VersionRequirement>> {
+        match (self.get_dynamic_vaddr(EntryType::VerNeeded), self.get_dynamic(EntryType::VerNeededNum)) {
+            (Some(vaddr), Some(count)) =>
+                parse_verneed(self.read_vaddr(vaddr, usize::MAX)?, count, self.dynamic_string_table()?),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Returns the version name of the dynamic symbol at `index` and whether
+    /// it is hidden, i.e. not the default version of that symbol.
+    pub fn get_symbol_version(&self, index: usize) -> Result<Option<(String, bool)>> {
+        let versym = match self.get_versym()?.get(index) {
+            Some(&v) => v,
+            None => return Ok(None),
+        };
+
+        let hidden = versym & VERSYM_HIDDEN != 0;
+        let ndx = versym & !VERSYM_HIDDEN;
+        if ndx == VER_NDX_LOCAL || ndx == VER_NDX_GLOBAL {
+            return Ok(None);
+        }
+
+        if let Some(def) = self.get_version_definitions()?.iter().find(|d| d.index == ndx) {
+            return Ok(Some((def.name().to_string(), hidden)));
+        }
+
+        Ok(self.get_version_requirements()?
+            .iter()
+            .flat_map(|req| req.needs.iter())
+            .find(|need| need.index == ndx)
+            .map(|need| (need.name.clone(), hidden)))
+    }
+
+    /// Looks up a defined dynamic symbol by name and version, like dlvsym(3).
+    /// Without a version the default version of the symbol is returned.
+    pub fn lookup_versioned_symbol(&self, name: &str, version: Option<&str>) -> Result<Option<&Symbol>> {
+        let versym = self.get_versym()?;
+        let defs = self.get_version_definitions()?;
+
+        for index in self.dynamic_symbol_candidates(name)? {
+            let sym = match self.get_dynamic_symbol(index) {
+                Some(s) if s.name == name && s.shndx != 0 => s,
+                _ => continue,
+            };
+
+            // Unversioned objects, or unversioned symbols, match anything.
+            let v = match versym.get(index) {
+                Some(&v) => v,
+                None => return Ok(Some(sym)),
+            };
+            let ndx = v & !VERSYM_HIDDEN;
+            if ndx == VER_NDX_LOCAL {
+                continue;
+            }
+
+            let matches = match version {
+                Some(version) => defs.iter().any(|d| d.index == ndx && d.name() == version),
+                None => v & VERSYM_HIDDEN == 0,
+            };
+            if matches || ndx == VER_NDX_GLOBAL {
+                return Ok(Some(sym));
+            }
+        }
+
+        Ok(None)
+    }
+}